@@ -54,7 +54,7 @@ fn clean_string(s: &str) -> String {
     s.chars().filter(|&r| is_ace_rune(r)).collect()
 }
 
-fn leftmost_unit(clean: &str) -> String {
+pub fn leftmost_unit(clean: &str) -> String {
     if clean.starts_with("--") {
         "--".to_string()
     } else {
@@ -351,48 +351,85 @@ fn allocate_disambiguators(candidates: &[ElemInfo], typed_lower: &str, elements_
     assigned.into_iter().flatten().collect()
 }
 
-// Build collapsed-match strings, start positions and max length for an ordered list
-fn build_ms_maps(order: &[ElemInfo]) -> (HashMap<usize, String>, HashMap<usize, usize>, usize) {
-    let mut ms_map: HashMap<usize, String> = HashMap::new();
-    let mut start_pos_map: HashMap<usize, usize> = HashMap::new();
+// Per-candidate character tables for the filtered allocator, built once per call.
+//
+// The offset and per-candidate passes used to look characters up with `chars().nth(pos)`,
+// which walks the string on every access. With hundreds of candidates sharing a left-unit
+// (e.g. every `--flag` of git) that made each pass roughly O(n * len^2); indexing into
+// precomputed `Vec<char>`s brings it down to O(n * len) without changing any output.
+struct CharTables {
+    // collapsed lowercase match chars per element index
+    ms: HashMap<usize, Vec<char>>,
+    // original-case clean chars per element index (used to prefer original casing)
+    clean: HashMap<usize, Vec<char>>,
+    start_pos: HashMap<usize, usize>,
+    max_len: usize,
+}
+
+impl CharTables {
+    fn start_pos(&self, idx: usize) -> usize {
+        *self.start_pos.get(&idx).unwrap_or(&0)
+    }
+
+    fn clean_at(&self, idx: usize, pos: usize) -> Option<char> {
+        self.clean.get(&idx).and_then(|c| c.get(pos)).copied()
+    }
+}
+
+// Build collapsed-match chars, original-case chars, start positions and max length for an ordered list
+fn build_char_tables(order: &[ElemInfo]) -> CharTables {
+    let mut ms: HashMap<usize, Vec<char>> = HashMap::new();
+    let mut clean: HashMap<usize, Vec<char>> = HashMap::new();
+    let mut start_pos: HashMap<usize, usize> = HashMap::new();
     let mut max_len = 0usize;
     for it in order {
         let lu_lower = it.lu.to_lowercase();
-        let ms = if lu_lower != "--" && !lu_lower.is_empty() {
-            collapse_leading(&it.lower, &lu_lower)
+        let m: Vec<char> = if lu_lower != "--" && !lu_lower.is_empty() {
+            collapse_leading(&it.lower, &lu_lower).chars().collect()
         } else {
-            it.lower.clone()
+            it.lower.chars().collect()
         };
-        max_len = max_len.max(ms.chars().count());
-        ms_map.insert(it.index, ms);
-        start_pos_map.insert(it.index, lu_lower.chars().count());
+        max_len = max_len.max(m.len());
+        ms.insert(it.index, m);
+        clean.insert(it.index, it.clean.chars().collect());
+        start_pos.insert(it.index, lu_lower.chars().count());
+    }
+    CharTables { ms, clean, start_pos, max_len }
+}
+
+// true when `ch` spells the typed left unit (never true for an empty or multi-rune unit)
+fn is_left_unit(ch: char, typed_left_unit: &str) -> bool {
+    let mut buf = [0u8; 4];
+    !typed_left_unit.is_empty() && ch.encode_utf8(&mut buf) == typed_left_unit
+}
+
+// Prefer the original-case char at `pos` when it lowercases to `ch`
+fn prefer_orig_case(tables: &CharTables, idx: usize, pos: usize, ch: char) -> String {
+    match tables.clean_at(idx, pos) {
+        Some(orig_ch) if orig_ch != '-' && orig_ch.to_ascii_lowercase() == ch => orig_ch.to_string(),
+        _ => ch.to_string(),
     }
-    (ms_map, start_pos_map, max_len)
 }
 
 // Offset-based assignment pass: assign unique characters at each offset among remaining candidates
 fn offset_assignment_pass(
     order: &[ElemInfo],
-    ms_map: &HashMap<usize, String>,
-    start_pos_map: &HashMap<usize, usize>,
-    max_len: usize,
+    tables: &CharTables,
     typed_left_unit: &str,
-    assigned: &mut Vec<Option<Assignment>>,
+    assigned: &mut [Option<Assignment>],
     used: &mut HashSet<char>,
     remaining: &mut Vec<usize>,
 ) {
-    for offset in 0..max_len {
+    for offset in 0..tables.max_len {
         if remaining.is_empty() { break; }
+        let pending: HashSet<usize> = remaining.iter().copied().collect();
         let mut freq: HashMap<char, usize> = HashMap::new();
         for &idx in remaining.iter() {
-            if let Some(ms) = ms_map.get(&idx) {
-                let start_pos = *start_pos_map.get(&idx).unwrap_or(&0);
-                let pos = start_pos + offset;
-                if let Some(ch) = ms.chars().nth(pos) {
-                    if ch != '-' && !used.contains(&ch) {
-                        if typed_left_unit.is_empty() || ch.to_string() != typed_left_unit {
-                            *freq.entry(ch).or_insert(0) += 1;
-                        }
+            if let Some(ms) = tables.ms.get(&idx) {
+                let pos = tables.start_pos(idx) + offset;
+                if let Some(&ch) = ms.get(pos) {
+                    if ch != '-' && !used.contains(&ch) && !is_left_unit(ch, typed_left_unit) {
+                        *freq.entry(ch).or_insert(0) += 1;
                     }
                 }
             }
@@ -401,36 +438,14 @@ fn offset_assignment_pass(
         let mut newly_assigned: Vec<usize> = Vec::new();
         for it in order {
             let idx = it.index;
-            if !remaining.contains(&idx) { continue; }
-            if let Some(ms) = ms_map.get(&idx) {
-                let start_pos = *start_pos_map.get(&idx).unwrap_or(&0);
-                let pos = start_pos + offset;
-                if let Some(ch) = ms.chars().nth(pos) {
-                    if ch != '-' && !used.contains(&ch) && (typed_left_unit.is_empty() || ch.to_string() != typed_left_unit) {
-                        if let Some(&count) = freq.get(&ch) {
-                            if count == 1 {
-                                // prefer original-case char when possible
-                                if let Some(orig_it) = order.iter().find(|o| o.index == idx) {
-                                    if let Some(orig_ch) = orig_it.clean.chars().nth(pos) {
-                                        if orig_ch != '-' {
-                                            if orig_ch.to_ascii_lowercase() == ch {
-                                                assigned[idx] = Some(Assignment { index: idx, prefix: orig_ch.to_string() });
-                                            } else {
-                                                assigned[idx] = Some(Assignment { index: idx, prefix: ch.to_string() });
-                                            }
-                                        } else {
-                                            assigned[idx] = Some(Assignment { index: idx, prefix: ch.to_string() });
-                                        }
-                                    } else {
-                                        assigned[idx] = Some(Assignment { index: idx, prefix: ch.to_string() });
-                                    }
-                                } else {
-                                    assigned[idx] = Some(Assignment { index: idx, prefix: ch.to_string() });
-                                }
-                                used.insert(ch);
-                                newly_assigned.push(idx);
-                            }
-                        }
+            if !pending.contains(&idx) { continue; }
+            if let Some(ms) = tables.ms.get(&idx) {
+                let pos = tables.start_pos(idx) + offset;
+                if let Some(&ch) = ms.get(pos) {
+                    if ch != '-' && !used.contains(&ch) && !is_left_unit(ch, typed_left_unit) && freq.get(&ch) == Some(&1) {
+                        assigned[idx] = Some(Assignment { index: idx, prefix: prefer_orig_case(tables, idx, pos, ch) });
+                        used.insert(ch);
+                        newly_assigned.push(idx);
                     }
                 }
             }
@@ -444,41 +459,26 @@ fn offset_assignment_pass(
 // Per-candidate left-to-right contiguous pass for remaining candidates
 fn per_candidate_pass(
     order: &[ElemInfo],
-    ms_map: &HashMap<usize, String>,
-    start_pos_map: &HashMap<usize, usize>,
+    tables: &CharTables,
     typed_left_unit: &str,
-    assigned: &mut Vec<Option<Assignment>>,
+    assigned: &mut [Option<Assignment>],
     used: &mut HashSet<char>,
     remaining: &mut Vec<usize>,
 ) {
+    let pending: HashSet<usize> = remaining.iter().copied().collect();
     let mut newly_assigned_pl: Vec<usize> = Vec::new();
     for it in order {
         let idx = it.index;
-        if !remaining.contains(&idx) { continue; }
-        if let Some(ms) = ms_map.get(&idx) {
-            let start_pos = *start_pos_map.get(&idx).unwrap_or(&0);
-            let total = ms.chars().count();
-            for pos in start_pos..total {
-                if let Some(ch) = ms.chars().nth(pos) {
-                    if ch == '-' { continue; }
-                    if !used.contains(&ch) && (typed_left_unit.is_empty() || ch.to_string() != typed_left_unit) {
-                        if let Some(orig_ch) = it.clean.chars().nth(pos) {
-                            if orig_ch != '-' {
-                                if orig_ch.to_ascii_lowercase() == ch {
-                                    assigned[idx] = Some(Assignment { index: idx, prefix: orig_ch.to_string() });
-                                } else {
-                                    assigned[idx] = Some(Assignment { index: idx, prefix: ch.to_string() });
-                                }
-                            } else {
-                                assigned[idx] = Some(Assignment { index: idx, prefix: ch.to_string() });
-                            }
-                        } else {
-                            assigned[idx] = Some(Assignment { index: idx, prefix: ch.to_string() });
-                        }
-                        used.insert(ch);
-                        newly_assigned_pl.push(idx);
-                        break;
-                    }
+        if !pending.contains(&idx) { continue; }
+        if let Some(ms) = tables.ms.get(&idx) {
+            let start_pos = tables.start_pos(idx);
+            for (pos, &ch) in ms.iter().enumerate().skip(start_pos) {
+                if ch == '-' { continue; }
+                if !used.contains(&ch) && !is_left_unit(ch, typed_left_unit) {
+                    assigned[idx] = Some(Assignment { index: idx, prefix: prefer_orig_case(tables, idx, pos, ch) });
+                    used.insert(ch);
+                    newly_assigned_pl.push(idx);
+                    break;
                 }
             }
         }
@@ -491,45 +491,40 @@ fn per_candidate_pass(
 // Last-resort fallback assignment for any remaining candidates
 fn last_resort_assign(
     order: &[ElemInfo],
-    ms_map: &HashMap<usize, String>,
+    tables: &CharTables,
     typed_left_unit: &str,
-    assigned: &mut Vec<Option<Assignment>>,
-    remaining: &Vec<usize>,
+    assigned: &mut [Option<Assignment>],
+    remaining: &[usize],
 ) {
-    for idx in remaining.iter() {
-        if let Some(ms) = ms_map.get(idx) {
+    for &idx in remaining.iter() {
+        if let Some(ms) = tables.ms.get(&idx) {
             let mut chosen: Option<String> = None;
-            if let Some(ch) = ms.chars().rev().find(|&r| r != '-') {
-                if typed_left_unit.is_empty() || ch.to_string() != typed_left_unit {
-                    let total_chars = ms.chars().count();
-                    if let Some(pos_rev) = ms.chars().rev().position(|r| r == ch) {
-                        let pos = total_chars.saturating_sub(1 + pos_rev);
-                        if let Some(orig_it) = order.iter().find(|o| o.index == *idx) {
-                            if let Some(orig_ch) = orig_it.clean.chars().nth(pos) {
-                                if orig_ch != '-' && orig_ch.to_ascii_lowercase() == ch {
-                                    chosen = Some(orig_ch.to_string());
-                                }
+            if let Some(&ch) = ms.iter().rev().find(|&&r| r != '-') {
+                if !is_left_unit(ch, typed_left_unit) {
+                    if let Some(pos_rev) = ms.iter().rev().position(|&r| r == ch) {
+                        let pos = ms.len().saturating_sub(1 + pos_rev);
+                        if let Some(orig_ch) = tables.clean_at(idx, pos) {
+                            if orig_ch != '-' && orig_ch.to_ascii_lowercase() == ch {
+                                chosen = Some(orig_ch.to_string());
                             }
                         }
                     }
                     if chosen.is_none() {
                         chosen = Some(ch.to_string());
                     }
+                } else if let Some(&ch2) = ms.iter().rev().find(|&&r| r != '-' && !is_left_unit(r, typed_left_unit)) {
+                    chosen = Some(ch2.to_string());
                 } else {
-                    if let Some(ch2) = ms.chars().rev().find(|&r| r != '-' && (typed_left_unit.is_empty() || r.to_string() != typed_left_unit)) {
-                        chosen = Some(ch2.to_string());
-                    } else {
-                        chosen = Some(ch.to_string());
-                    }
+                    chosen = Some(ch.to_string());
                 }
             }
 
             if let Some(pref) = chosen {
-                assigned[*idx] = Some(Assignment { index: *idx, prefix: pref });
+                assigned[idx] = Some(Assignment { index: idx, prefix: pref });
             } else {
-                let lu = order.iter().find(|o| o.index == *idx).map(|o| o.lu.clone()).unwrap_or_default();
+                let lu = order.iter().find(|o| o.index == idx).map(|o| o.lu.clone()).unwrap_or_default();
                 let use_pref = if lu == "--" { "-".to_string() } else { lu };
-                assigned[*idx] = Some(Assignment { index: *idx, prefix: use_pref });
+                assigned[idx] = Some(Assignment { index: idx, prefix: use_pref });
             }
         }
     }
@@ -544,24 +539,24 @@ fn allocate_disambiguators_filtered(candidates: &[ElemInfo], typed_lower: &str,
     let mut order: Vec<ElemInfo> = candidates.to_vec();
     order.sort_by(|a, b| a.rune_count.cmp(&b.rune_count).then(a.index.cmp(&b.index)));
 
-    // build collapsed lowercase match strings and start positions
-    let (ms_map, start_pos_map, max_len) = build_ms_maps(&order);
+    // build collapsed lowercase match chars and start positions once
+    let tables = build_char_tables(&order);
 
     let mut assigned: Vec<Option<Assignment>> = vec![None; elements_count];
     let mut used: HashSet<char> = HashSet::new();
     let mut remaining: Vec<usize> = order.iter().map(|o| o.index).collect();
 
     // Offset loop
-    offset_assignment_pass(&order, &ms_map, &start_pos_map, max_len, &typed_left_unit, &mut assigned, &mut used, &mut remaining);
+    offset_assignment_pass(&order, &tables, &typed_left_unit, &mut assigned, &mut used, &mut remaining);
 
     // Per-candidate left-to-right contiguous pass
     if !remaining.is_empty() {
-        per_candidate_pass(&order, &ms_map, &start_pos_map, &typed_left_unit, &mut assigned, &mut used, &mut remaining);
+        per_candidate_pass(&order, &tables, &typed_left_unit, &mut assigned, &mut used, &mut remaining);
     }
 
     // Last-resort fallback
     if !remaining.is_empty() {
-        last_resort_assign(&order, &ms_map, &typed_left_unit, &mut assigned, &remaining);
+        last_resort_assign(&order, &tables, &typed_left_unit, &mut assigned, &remaining);
     }
 
     assigned.into_iter().flatten().collect()
@@ -723,6 +718,71 @@ mod acekey_tests {
     use super::*;
     use std::collections::HashSet;
 
+    // The key each candidate of the large set below got for these typed prefixes, in element
+    // order, as recorded from the allocator before the char-table refactor
+    const LARGE_SET_KEYS: [(&str, &str); 3] = [
+        ("-", concat!(
+            "0a1234567h8901234o5178901v2342678c9013345j6789012q3456749x0123456e7890123l656789",
+            "0s1234567z8781234g5678901n2345978u9012345b6789012i3456789p0123456w7890123d456789",
+            "0k1234567r8901234y5678901f2345678m9012345t6789012a3456789h0123456o7890123v456789",
+            "0c1234567j8901234q5678901x2345678e9012345l6789012s3456789z0123456g7890123n456789",
+            "0u1234567b8901234i5678901p2345678w9012345d6789012k3456789r0123456y7890123f456789",
+            "0m1234567t8901234a5678901h2345678o9012345v6789012c3456789j",
+        )),
+        ("--", concat!(
+            "0mubhcodyef1gqv5an890r2p4i6789s1l34567890t23456749012345658901236567890123456787",
+            "81234567890123459789012345678901134567890123456729012345678901234567890123456789",
+            "01234567890123456789012345678901234567890123456789012345678901234567890123456789",
+            "01234567890123456789012345678901234567890123456789012345678901234567890123456789",
+            "01234567890123456789012345678901234567890123456789012345678901234567890123456789",
+        )),
+        ("c", "05f5hevbyqudga7r8o950is5050p0m051l050t0505050n0525050505050505053505040565050505"),
+    ];
+
+    #[test]
+    fn test_filtered_allocator_keeps_its_keys_on_large_shared_left_unit() {
+        // Hundreds of `--` flags sharing a left-unit, mixed with short flags and commands
+        // sharing `c`, roughly the shape of git's flag list.
+        let words = ["all", "amend", "author", "branch", "cached", "color", "commit", "depth", "dry-run", "edit", "force", "format", "global", "quiet", "verbose", "no-verify"];
+        let mut elements: Vec<String> = Vec::new();
+        for i in 0..400 {
+            let w = words[i % words.len()];
+            elements.push(format!("--{w}-{i}"));
+            if i % 7 == 0 {
+                elements.push(format!("-{}", (b'a' + (i % 26) as u8) as char));
+            }
+            if i % 5 == 0 {
+                elements.push(format!("c{w}{i}"));
+            }
+        }
+        let infos = build_infos(&elements);
+        for (typed, want) in LARGE_SET_KEYS {
+            let typed_left_unit = compute_typed_left_unit(typed);
+            let candidates: Vec<ElemInfo> = infos
+                .iter()
+                .filter(|it| {
+                    let lu_lower = it.lu.to_lowercase();
+                    if typed_left_unit == "-" {
+                        lu_lower == "-" || lu_lower == "--"
+                    } else {
+                        lu_lower == typed_left_unit
+                    }
+                })
+                .cloned()
+                .collect();
+            assert!(candidates.len() > 50, "expected a large candidate set for {typed}");
+            let got = allocate_disambiguators_filtered(&candidates, typed, elements.len());
+            let indices: Vec<usize> = got.iter().map(|a| a.index).collect();
+            assert_eq!(indices, candidates.iter().map(|c| c.index).collect::<Vec<_>>(), "typed {typed}");
+            let keys: String = got.iter().map(|a| a.prefix.as_str()).collect();
+            assert_eq!(keys, want, "allocator output diverged for typed {typed}");
+        }
+        // the public entry point must also stay stable end to end
+        for typed in ["-", "--", "--a", "c", "ca"] {
+            assert!(assign_ace_keys(&elements, typed).is_some(), "expected assignments for {typed}");
+        }
+    }
+
     #[test]
     fn test_contiguous_unique_ch_examples() {
         let els = ["chcpu", "chpasswd", "chsh"];
@@ -743,4 +803,18 @@ mod acekey_tests {
             assert!(seen.insert(a.prefix.clone()), "duplicate prefix {}", a.prefix);
         }
     }
+
+    #[test]
+    fn single_char_shorthands_get_their_own_char_after_the_dash() {
+        let elems: Vec<String> = ["-a", "-b", "-c"].iter().map(|s| s.to_string()).collect();
+        let mut v = assign_ace_keys(&elems, "-").expect("expected assignments after '-'");
+        v.sort_by_key(|a| a.index);
+        let prefixes: Vec<&str> = v.iter().map(|a| a.prefix.as_str()).collect();
+        assert_eq!(prefixes, vec!["a", "b", "c"]);
+        // typing the char completes the one candidate
+        for (i, typed) in ["-a", "-b", "-c"].iter().enumerate() {
+            let v = assign_ace_keys(&elems, typed).unwrap();
+            assert_eq!(v, vec![Assignment { index: i, prefix: String::new() }], "{typed}");
+        }
+    }
 }
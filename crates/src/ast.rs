@@ -1,15 +1,34 @@
 use serde::Deserialize;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct FlagDef {
     pub longhand: String,
     pub shorthand: String,
     pub usage: String,
+    // extended description, when the metadata has more than the one-line usage
+    #[serde(default)]
+    pub long_usage: String,
     pub requires_value: bool,
+    // kind of value expected, e.g. "int", "file" or "on|off|auto"; empty when unknown
+    #[serde(default)]
+    pub value_hint: String,
+    // hidden flags are never offered as candidates
+    #[serde(default)]
+    pub hidden: bool,
+    // takes every following word up to the next flag as a value (`--files a b c`)
+    #[serde(default)]
+    pub variadic: bool,
+    // added by van (e.g. a missing --help), never part of exported metadata
+    #[serde(skip)]
+    pub synthesized: bool,
+    // set by van when the command also has `--no-<longhand>`: that flag's longhand, offered in
+    // this flag's item as the other side of a toggle
+    #[serde(skip)]
+    pub negation: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct CommandDef {
     pub name: String,
@@ -17,12 +36,24 @@ pub struct CommandDef {
     pub aliases: Vec<String>,
     pub flags: Vec<FlagDef>,
     pub subcommands: Vec<CommandDef>,
+    // carapace has positional completions for it (e.g. files for `cat`)
+    #[serde(default)]
+    pub takes_args: bool,
+    // hidden subcommands are never offered as candidates
+    #[serde(default)]
+    pub hidden: bool,
+    // sets of flags (by longhand, or shorthand when there is none) of which at most one may be
+    // given, e.g. `[["json", "yaml"]]`
+    #[serde(default)]
+    pub exclusive_flags: Vec<Vec<String>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct FlagInstance {
     pub form: String,
     pub value: String,
+    // the values after the first of a variadic flag, each its own word
+    pub more_values: Vec<String>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -32,10 +63,31 @@ pub struct CommandNode {
     pub positionals: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct HistoryOp {
     pub kind: String,
     pub depth: usize,
+    // for "swap-flag"/"swap-pos": the lower of the two swapped indices
+    pub index: usize,
+    // for "clear": the node's flags and positionals before they were cleared
+    pub cleared: Option<CommandNode>,
+}
+
+// Where a negatable switch (`--color` / `--no-color`) stands on a command line; `On` keeps the
+// form it was given as (`--color` or `-c`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwitchState {
+    Unset,
+    On(String),
+    Off,
+}
+
+// The most recently referenced flag or positional: what Alt+Up/Down moves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Focus {
+    pub kind: String, // "flag" or "pos"
+    pub depth: usize,
+    pub index: usize,
 }
 
 // Story 1.2: Redirection enum
@@ -45,7 +97,19 @@ pub enum Redirection {
     Output { file: String, append: bool },
 }
 
-// Story 1.2: Binary operators connecting segments (future use)
+impl Redirection {
+    // As shell text, e.g. `>> log.txt`
+    pub fn render(&self) -> String {
+        match self {
+            Redirection::Input(file) => format!("< {}", crate::parse::shell_word(file)),
+            Redirection::Output { file, append } => {
+                format!("{} {}", if *append { ">>" } else { ">" }, crate::parse::shell_word(file))
+            }
+        }
+    }
+}
+
+// Story 1.2: Binary operators connecting segments
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BinaryOp {
     Pipe,
@@ -53,6 +117,16 @@ pub enum BinaryOp {
     Or,
 }
 
+impl BinaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Pipe => "|",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        }
+    }
+}
+
 // Renamed from AST -> Segment (Story 1.1)
 #[derive(Debug, Clone, Default)]
 pub struct Segment {
@@ -60,6 +134,9 @@ pub struct Segment {
     pub stack: Vec<CommandNode>,
     pub history: Vec<HistoryOp>,
     pub redirections: Vec<Redirection>, // Story 1.2
+    pub focus: Option<Focus>,
+    // Variables env var completion inserted `$NAME` references to; those stay live in the preview
+    pub live_vars: Vec<String>,
 }
 
 impl Segment {
@@ -74,6 +151,8 @@ impl Segment {
             stack: vec![n],
             history: vec![],
             redirections: vec![],
+            focus: None,
+            live_vars: vec![],
         }
     }
 
@@ -91,9 +170,26 @@ impl Segment {
         self.history.push(HistoryOp {
             kind: "subcmd".to_string(),
             depth: self.stack.len() - 1,
+            ..Default::default()
+        });
+    }
+
+    // Redirect the segment's output or input; recorded in history, so undo takes it back in turn
+    // with the flags and positionals around it
+    pub fn add_redirection(&mut self, r: Redirection) {
+        self.redirections.push(r);
+        self.history.push(HistoryOp {
+            kind: "redirect".to_string(),
+            depth: self.stack.len().saturating_sub(1),
+            ..Default::default()
         });
     }
 
+    // Whether undo would take back a redirection next
+    pub fn undoes_redirection(&self) -> bool {
+        self.history.last().is_some_and(|op| op.kind == "redirect")
+    }
+
     pub fn pop(&mut self) {
         if self.stack.len() <= 1 {
             return;
@@ -102,17 +198,30 @@ impl Segment {
     }
 
     pub fn add_flag_to_depth(&mut self, depth: usize, form: &str, value: &str) {
+        self.add_flag_values_to_depth(depth, form, &[value.to_string()]);
+    }
+
+    // A variadic flag with all its values, rendered `--files a b c`
+    pub fn add_flag_values_to_depth(&mut self, depth: usize, form: &str, values: &[String]) {
         if depth >= self.stack.len() {
             return;
         }
+        let (value, more_values) = values.split_first().map_or((String::new(), vec![]), |(v, rest)| (v.clone(), rest.to_vec()));
         let fi = FlagInstance {
             form: form.to_string(),
-            value: value.to_string(),
+            value,
+            more_values,
         };
         self.stack[depth].flags.push(fi);
         self.history.push(HistoryOp {
             kind: "flag".to_string(),
             depth,
+            ..Default::default()
+        });
+        self.focus = Some(Focus {
+            kind: "flag".to_string(),
+            depth,
+            index: self.stack[depth].flags.len() - 1,
         });
     }
 
@@ -129,11 +238,35 @@ impl Segment {
         let node = &mut self.stack[depth];
         if let Some(pos) = node.flags.iter().rposition(|f| f.form == form) {
             node.flags.remove(pos);
+            self.clamp_focus();
             return true;
         }
         false
     }
 
+    // Switch the last `from` flag at `depth` to `to` in place (e.g. `--color` to `--no-color`),
+    // keeping its position and history entry
+    pub fn replace_flag_form(&mut self, depth: usize, from: &str, to: &str) -> bool {
+        let Some(f) = self.stack.get_mut(depth).and_then(|n| n.flags.iter_mut().rfind(|f| f.form == from)) else {
+            return false;
+        };
+        f.form = to.to_string();
+        true
+    }
+
+    // The state of the switch given as any of `on` or as `off` at `depth`; the last one counts
+    pub fn switch_state(&self, depth: usize, on: &[String], off: &str) -> SwitchState {
+        let last = self
+            .stack
+            .get(depth)
+            .and_then(|n| n.flags.iter().rev().find(|f| f.form == off || on.contains(&f.form)));
+        match last {
+            None => SwitchState::Unset,
+            Some(f) if f.form == off => SwitchState::Off,
+            Some(f) => SwitchState::On(f.form.clone()),
+        }
+    }
+
     pub fn remove_flag(&mut self, form: &str) -> bool {
         if self.stack.is_empty() {
             return false;
@@ -143,16 +276,53 @@ impl Segment {
     }
 
     pub fn add_positional(&mut self, val: &str) {
+        let depth = self.stack.len().saturating_sub(1);
         if let Some(node) = self.stack.last_mut() {
             node.positionals.push(val.to_string());
             self.history.push(HistoryOp {
                 kind: "pos".to_string(),
-                depth: self.stack.len() - 1,
+                depth,
+                ..Default::default()
+            });
+            self.focus = Some(Focus {
+                kind: "pos".to_string(),
+                depth,
+                index: node.positionals.len() - 1,
             });
         }
     }
 
+    // Drop every flag and positional of the innermost command, keeping it (and the commands
+    // above it) on the stack. A single undo brings them all back.
+    pub fn clear_top(&mut self) -> bool {
+        let Some(depth) = self.stack.len().checked_sub(1) else {
+            return false;
+        };
+        let node = &mut self.stack[depth];
+        if node.flags.is_empty() && node.positionals.is_empty() {
+            return false;
+        }
+        let cleared = CommandNode {
+            name: node.name.clone(),
+            flags: std::mem::take(&mut node.flags),
+            positionals: std::mem::take(&mut node.positionals),
+        };
+        self.history.push(HistoryOp {
+            kind: "clear".to_string(),
+            depth,
+            cleared: Some(cleared),
+            ..Default::default()
+        });
+        self.clamp_focus();
+        true
+    }
+
     pub fn remove_last(&mut self) {
+        self.undo_last();
+        self.clamp_focus();
+    }
+
+    fn undo_last(&mut self) {
         if self.history.is_empty() {
             if let Some(n) = self.stack.last_mut() {
                 if n.flags.pop().is_some() {
@@ -187,26 +357,145 @@ impl Segment {
                         self.pop();
                     }
                 }
+                "redirect" => {
+                    self.redirections.pop();
+                }
+                "clear" => {
+                    if let (Some(node), Some(cleared)) = (self.stack.get_mut(op.depth), op.cleared) {
+                        node.flags = cleared.flags;
+                        node.positionals = cleared.positionals;
+                    }
+                }
+                "swap-flag" | "swap-pos" => {
+                    let kind = op.kind.trim_start_matches("swap-");
+                    self.swap_in_node(kind, op.depth, op.index);
+                    if let Some(f) = self.focus.as_mut().filter(|f| f.kind == kind && f.depth == op.depth) {
+                        if f.index == op.index {
+                            f.index += 1;
+                        } else if f.index == op.index + 1 {
+                            f.index = op.index;
+                        }
+                    }
+                }
                 _ => {}
             }
         }
     }
 
+    // Move the focused flag or positional one place earlier (`forward == false`) or later among
+    // its node's flags or positionals. Recorded in history, so undo moves it back.
+    pub fn move_focused(&mut self, forward: bool) -> bool {
+        let Some(f) = self.focus.clone() else {
+            return false;
+        };
+        let Some(node) = self.stack.get(f.depth) else {
+            return false;
+        };
+        let len = if f.kind == "flag" { node.flags.len() } else { node.positionals.len() };
+        let lower = if forward {
+            if f.index + 1 >= len {
+                return false;
+            }
+            f.index
+        } else {
+            if f.index == 0 || f.index >= len {
+                return false;
+            }
+            f.index - 1
+        };
+        self.swap_in_node(&f.kind, f.depth, lower);
+        self.history.push(HistoryOp {
+            kind: format!("swap-{}", f.kind),
+            depth: f.depth,
+            index: lower,
+            cleared: None,
+        });
+        self.focus = Some(Focus {
+            index: if forward { lower + 1 } else { lower },
+            ..f
+        });
+        true
+    }
+
+    fn swap_in_node(&mut self, kind: &str, depth: usize, lower: usize) {
+        let Some(node) = self.stack.get_mut(depth) else {
+            return;
+        };
+        match kind {
+            "flag" if lower + 1 < node.flags.len() => node.flags.swap(lower, lower + 1),
+            "pos" if lower + 1 < node.positionals.len() => node.positionals.swap(lower, lower + 1),
+            _ => {}
+        }
+    }
+
+    // Drop the focus once the element it points at is gone
+    fn clamp_focus(&mut self) {
+        let valid = self.focus.as_ref().is_some_and(|f| {
+            self.stack.get(f.depth).is_some_and(|n| {
+                f.index < if f.kind == "flag" { n.flags.len() } else { n.positionals.len() }
+            })
+        });
+        if !valid {
+            self.focus = None;
+        }
+    }
+
+    // The command line as shell text: `to_tokens` with each word quoted where needed
     pub fn render_preview(&self) -> String {
-        let mut parts: Vec<String> = vec![self.root.clone()];
+        self.render_preview_keeping(&[])
+    }
+
+    // `render_preview`, but positionals matching any of `verbatim` are left exactly as typed
+    // (git revisions like `HEAD@{2}`) when that keeps them one literal word; anything with
+    // whitespace or other shell syntax, and flag values, are always quoted.
+    // Redirections follow the words.
+    pub fn render_preview_keeping(&self, verbatim: &[regex::Regex]) -> String {
+        self.preview_words_keeping(verbatim)
+            .into_iter()
+            .chain(self.redirections.iter().map(Redirection::render))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // The words of `render_preview_keeping`, each as shell text, without the redirections
+    pub fn preview_words_keeping(&self, verbatim: &[regex::Regex]) -> Vec<String> {
+        self.words()
+            .into_iter()
+            .map(|(t, positional)| {
+                if positional && crate::parse::is_bare_word(&t) && verbatim.iter().any(|re| re.is_match(&t)) {
+                    t
+                } else {
+                    crate::parse::shell_word_live(&t, &self.live_vars)
+                }
+            })
+            .collect()
+    }
+
+    // The command line as argv, values unquoted (for executing directly, without a shell)
+    pub fn to_tokens(&self) -> Vec<String> {
+        self.words().into_iter().map(|(t, _)| t).collect()
+    }
+
+    // Each argv word with whether it is a positional
+    fn words(&self) -> Vec<(String, bool)> {
+        let mut parts: Vec<(String, bool)> = vec![];
+        if !self.root.is_empty() {
+            parts.push((self.root.clone(), false));
+        }
 
-        let append_node = |node: &CommandNode, include_name: bool, out: &mut Vec<String>| {
+        let append_node = |node: &CommandNode, include_name: bool, out: &mut Vec<(String, bool)>| {
             if include_name {
-                out.push(node.name.clone());
+                out.push((node.name.clone(), false));
             }
             for f in &node.flags {
-                out.push(f.form.clone());
+                out.push((f.form.clone(), false));
                 if !f.value.is_empty() {
-                    out.push(f.value.clone());
+                    out.push((f.value.clone(), false));
                 }
+                out.extend(f.more_values.iter().map(|v| (v.clone(), false)));
             }
             for p in &node.positionals {
-                out.push(p.clone());
+                out.push((p.clone(), true));
             }
         };
 
@@ -218,7 +507,7 @@ impl Segment {
             }
         }
 
-        parts.join(" ")
+        parts
     }
 }
 
@@ -280,6 +569,49 @@ impl CommandLine {
     }
 }
 
+// Definitions for tests across the crate: the names given, everything else at its default
+#[cfg(test)]
+pub(crate) mod build {
+    use super::{CommandDef, FlagDef};
+
+    // A switch; either name may be empty
+    pub(crate) fn flag(long: &str, short: &str) -> FlagDef {
+        FlagDef {
+            longhand: long.to_string(),
+            shorthand: short.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // A switch with a longhand only
+    pub(crate) fn switch(long: &str) -> FlagDef {
+        flag(long, "")
+    }
+
+    // A flag taking a value, with the type hint the list shows for it
+    pub(crate) fn value_flag(long: &str, hint: &str) -> FlagDef {
+        FlagDef {
+            requires_value: true,
+            value_hint: hint.to_string(),
+            ..switch(long)
+        }
+    }
+
+    pub(crate) fn command(name: &str, flags: Vec<FlagDef>, subcommands: Vec<CommandDef>) -> CommandDef {
+        CommandDef {
+            name: name.to_string(),
+            flags,
+            subcommands,
+            ..Default::default()
+        }
+    }
+
+    // A command with no flags or subcommands of its own
+    pub(crate) fn sub(name: &str) -> CommandDef {
+        command(name, vec![], vec![])
+    }
+}
+
 // Tests for Story 1.2 (written before implementation of Redirection/BinaryOp additions)
 #[cfg(test)]
 mod tests {
@@ -321,6 +653,22 @@ mod tests {
         assert!(seg.redirections.is_empty());
     }
 
+    #[test]
+    fn undo_takes_back_redirections_in_the_order_they_were_made() {
+        let mut seg = Segment::new_empty("grep");
+        seg.add_flag_to_depth(0, "-i", "");
+        seg.add_redirection(Redirection::Output { file: "out.txt".into(), append: false });
+        seg.add_positional("x");
+        assert_eq!(seg.render_preview(), "grep -i x > out.txt");
+        assert!(!seg.undoes_redirection());
+        seg.remove_last();
+        assert!(seg.undoes_redirection());
+        seg.remove_last();
+        assert_eq!(seg.render_preview(), "grep -i");
+        seg.remove_last();
+        assert_eq!(seg.render_preview(), "grep");
+    }
+
     #[test]
     fn test_command_line_render_preview_single() {
         let mut cl = CommandLine::new();
@@ -337,24 +685,22 @@ mod tests {
         assert_eq!(cl.render_preview(), "cmd1 | cmd2");
     }
 
-    // pending
     #[test]
     fn test_command_line_render_preview_with_redirection() {
-        // pending
-        // let mut cl = CommandLine::new();
-        // cl.focused_segment_mut().root = "cmd1".into();
-        // cl.focused_segment_mut().redirections.push(Redirection::Output {
-        //     file: "out.txt".into(),
-        //     append: false,
-        // });
-        // cl.add_segment();
-        // cl.focused_segment_mut().root = "cmd2".into();
-        // cl.focused_segment_mut().redirections.push(Redirection::Input("in.txt".into()));
-        // let preview = cl.render_preview();
-        // assert!(preview.contains("> out.txt"));
-        // assert!(preview.contains("cmd1"));
-        // assert!(preview.contains("cmd2"));
-        // assert!(preview.contains("< in.txt"));
+        let mut cl = CommandLine::new();
+        cl.focused_segment_mut().root = "cmd1".into();
+        cl.focused_segment_mut().redirections.push(Redirection::Output {
+            file: "out.txt".into(),
+            append: false,
+        });
+        cl.add_segment();
+        cl.focused_segment_mut().root = "cmd2".into();
+        cl.focused_segment_mut().redirections.push(Redirection::Input("in.txt".into()));
+        let preview = cl.render_preview();
+        assert!(preview.contains("> out.txt"));
+        assert!(preview.contains("cmd1"));
+        assert!(preview.contains("cmd2"));
+        assert!(preview.contains("< in.txt"));
     }
 
     #[test]
@@ -394,4 +740,82 @@ mod tests {
         cl.remove_focused_segment();
         assert_eq!(cl.segments.len(), 1);
     }
+
+    #[test]
+    fn move_focused_swaps_positionals_and_undo_restores() {
+        let mut seg = Segment::new_empty("cp");
+        seg.add_positional("src");
+        seg.add_positional("dst");
+        assert_eq!(seg.render_preview(), "cp src dst");
+
+        // the last added positional has focus; moving it earlier swaps the two
+        assert!(seg.move_focused(false));
+        assert_eq!(seg.render_preview(), "cp dst src");
+        assert!(!seg.move_focused(false), "already first");
+
+        seg.remove_last();
+        assert_eq!(seg.render_preview(), "cp src dst");
+        assert_eq!(seg.focus.as_ref().map(|f| f.index), Some(1));
+        seg.remove_last();
+        assert_eq!(seg.render_preview(), "cp src");
+    }
+
+    #[test]
+    fn move_focused_reorders_flags_within_their_node() {
+        let mut seg = Segment::new_empty("git");
+        seg.add_flag("--no-pager", "");
+        seg.push_subcommand("log");
+        seg.add_flag("--oneline", "");
+        seg.add_flag_to_depth(0, "-C", "repo");
+        assert!(seg.move_focused(false));
+        assert_eq!(seg.render_preview(), "git -C repo --no-pager log --oneline");
+        assert!(seg.move_focused(true));
+        assert_eq!(seg.render_preview(), "git --no-pager -C repo log --oneline");
+    }
+
+    #[test]
+    fn preview_quotes_values_that_tokens_keep_whole() {
+        let mut seg = Segment::new_empty("echo");
+        seg.add_flag("-e", "");
+        seg.add_positional("hello world");
+        assert_eq!(seg.render_preview(), "echo -e 'hello world'");
+        assert_eq!(seg.to_tokens(), vec!["echo", "-e", "hello world"]);
+        assert_eq!(crate::parse::tokenize_command_line(&seg.render_preview()), seg.to_tokens());
+        assert_eq!(Segment::new_empty("").render_preview(), "");
+    }
+
+    #[test]
+    fn git_revision_positionals_stay_verbatim() {
+        let verbatim = crate::ui::Settings::default().verbatim_words;
+        let mut seg = Segment::new_empty("git");
+        seg.push_subcommand("log");
+        seg.add_flag("--grep", ":/not a revision");
+        seg.add_positional("HEAD@{2}");
+        seg.add_positional(":/fix bug");
+        seg.add_positional("a b");
+        assert_eq!(
+            seg.render_preview_keeping(&verbatim),
+            "git log --grep ':/not a revision' HEAD@{2} ':/fix bug' 'a b'"
+        );
+        assert_eq!(seg.render_preview(), "git log --grep ':/not a revision' 'HEAD@{2}' ':/fix bug' 'a b'");
+        assert_eq!(
+            seg.to_tokens(),
+            vec!["git", "log", "--grep", ":/not a revision", "HEAD@{2}", ":/fix bug", "a b"]
+        );
+        for rev in ["@{u}", "main@{yesterday}", "HEAD@{1}"] {
+            assert!(verbatim.iter().any(|re| re.is_match(rev)), "{rev}");
+        }
+
+        // a matching word with shell syntax in it is quoted all the same, so the preview
+        // tokenizes back to the same words
+        let everything = [regex::Regex::new(".").unwrap()];
+        let mut seg = Segment::new_empty("git");
+        seg.push_subcommand("show");
+        seg.add_positional(":/x; rm -rf ~");
+        seg.add_positional("HEAD@{1}~2");
+        seg.add_positional("$(id)@{u}");
+        let kept = seg.render_preview_keeping(&everything);
+        assert_eq!(kept, "git show ':/x; rm -rf ~' 'HEAD@{1}~2' '$(id)@{u}'");
+        assert_eq!(crate::parse::tokenize_command_line(&kept), seg.to_tokens());
+    }
 }
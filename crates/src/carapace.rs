@@ -1,8 +1,25 @@
 use crate::ast::{CommandDef, FlagDef};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
 use std::process::Command;
+use std::sync::Mutex;
+
+// Environment variable overriding the carapace executable (useful for wrappers and tests)
+pub const CARAPACE_BIN_ENV: &str = "VAN_CARAPACE_BIN";
+
+fn carapace_bin() -> String {
+    env::var(CARAPACE_BIN_ENV)
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "carapace".to_string())
+}
 
 fn run_carapace_cmd(args: &[&str]) -> Result<String, String> {
-    let mut cmd = Command::new("carapace");
+    let mut cmd = Command::new(carapace_bin());
     for a in args {
         cmd.arg(a);
     }
@@ -16,18 +33,60 @@ fn run_carapace_cmd(args: &[&str]) -> Result<String, String> {
     Ok(String::from_utf8_lossy(&out.stdout).to_string())
 }
 
+// First line of `carapace --version`; fails when carapace is missing or broken
+pub fn version() -> Result<String, String> {
+    let s = run_carapace_cmd(&["--version"])?;
+    Ok(s.lines().next().unwrap_or_default().trim().to_string())
+}
+
+// Which command names resolve on PATH, remembered for the life of the process (the root list
+// is rebuilt on every return to it) and forgotten when PATH itself changes
+#[derive(Default)]
+struct PathLookups {
+    path: Option<OsString>,
+    found: HashMap<String, bool>,
+}
+
+impl PathLookups {
+    fn resolves(&mut self, path: Option<OsString>, name: &str, lookup: impl FnOnce(&str) -> bool) -> bool {
+        if self.path != path {
+            self.path = path;
+            self.found.clear();
+        }
+        if let Some(&found) = self.found.get(name) {
+            return found;
+        }
+        let found = lookup(name);
+        self.found.insert(name.to_string(), found);
+        found
+    }
+}
+
+static PATH_LOOKUPS: Lazy<Mutex<PathLookups>> = Lazy::new(|| Mutex::new(PathLookups::default()));
+
+fn on_path(name: &str) -> bool {
+    let mut lookups = PATH_LOOKUPS.lock().unwrap_or_else(|e| e.into_inner());
+    lookups.resolves(env::var_os("PATH"), name, |n| which::which(n).is_ok())
+}
+
 pub fn list() -> Result<Vec<String>, String> {
     let s = run_carapace_cmd(&["--list"])?;
     Ok(s.lines()
         .map(|l| l.trim())
         .filter(|l| !l.is_empty())
         .filter_map(|l| l.split_whitespace().next())
-        .filter(|name| which::which(name).is_ok())
+        .filter(|name| on_path(name))
         .map(|s| s.to_string())
         .collect())
 }
 
 pub fn list_with_desc() -> Result<Vec<(String, String)>, String> {
+    list_entries(true)
+}
+
+// Like `list_with_desc`, but `path_filter = false` keeps commands that are not resolvable on
+// PATH (e.g. tools that only exist inside a container the command will run in).
+pub fn list_entries(path_filter: bool) -> Result<Vec<(String, String)>, String> {
     let s = run_carapace_cmd(&["--list"])?;
     let out: Vec<(String, String)> = s
         .lines()
@@ -37,7 +96,7 @@ pub fn list_with_desc() -> Result<Vec<(String, String)>, String> {
             line.split_whitespace()
                 .next()
                 .and_then(|name| {
-                    if which::which(name).is_ok() {
+                    if !path_filter || on_path(name) {
                         let short = if line.len() > name.len() {
                             line[name.len()..].trim().to_string()
                         } else {
@@ -53,28 +112,203 @@ pub fn list_with_desc() -> Result<Vec<(String, String)>, String> {
     Ok(out)
 }
 
+// Placeholder hint for a flag's value: its fixed choices when listed, else a readable type.
+fn value_hint(f: &serde_json::Value, typ: &str) -> String {
+    let choices: Vec<&str> = ["Values", "Enum"]
+        .iter()
+        .filter_map(|k| field(f, k).and_then(|v| v.as_array()))
+        .flat_map(|arr| arr.iter().filter_map(|x| x.as_str()))
+        .collect();
+    if !choices.is_empty() {
+        return choices.join("|");
+    }
+    let base = typ.trim_end_matches("Slice").trim_end_matches("Array");
+    match base {
+        "int" | "int8" | "int16" | "int32" | "int64" | "uint" | "uint8" | "uint16" | "uint32"
+        | "uint64" | "count" => "int",
+        "float32" | "float64" => "float",
+        // a boolean that takes an explicit value (`--color=true`); plain `bool` takes none
+        "bool" | "boolean" if typ != "bool" => "true|false",
+        "duration" => "duration",
+        "stringToString" | "stringToInt" | "stringToInt64" => "key=value",
+        "file" | "path" => "file",
+        "dir" | "directory" => "directory",
+        _ => "",
+    }
+    .to_string()
+}
+
+// Start of the error for an export carrying nothing to complete (`{}` or blank output)
+pub const NO_COMPLETION_DATA: &str = "no completion data for";
+
 pub fn export(cmd_name: &str) -> Result<CommandDef, String> {
     if cmd_name.trim().is_empty() {
         return Err("empty command name".to_string());
     }
+    let s = match cached_export(cmd_name) {
+        Some(s) => s,
+        None => run_carapace_cmd(&[cmd_name, "export"])?,
+    };
+    def_from_export(cmd_name, &s)
+}
+
+fn def_from_export(cmd_name: &str, s: &str) -> Result<CommandDef, String> {
+    if s.trim().is_empty() {
+        return Err(format!("{NO_COMPLETION_DATA} {cmd_name}"));
+    }
+    let mut def = parse_export(s)?;
+    if def.name.trim().is_empty() {
+        if def.flags.is_empty() && def.subcommands.is_empty() {
+            return Err(format!("{NO_COMPLETION_DATA} {cmd_name}"));
+        }
+        def.name = cmd_name.to_string();
+    }
+    Ok(def)
+}
+
+// Where `van --warm` keeps exports: $VAN_CACHE_DIR, else $XDG_CACHE_HOME/van, else ~/.cache/van
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(d) = env::var_os("VAN_CACHE_DIR").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(d));
+    }
+    if let Some(d) = env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(d).join("van"));
+    }
+    env::var_os("HOME")
+        .filter(|d| !d.is_empty())
+        .map(|h| PathBuf::from(h).join(".cache").join("van"))
+}
+
+// One file per command; a name that could point outside the directory gets none
+fn cache_file(cmd_name: &str) -> Option<PathBuf> {
+    if cmd_name.contains(['/', '\\']) || cmd_name.starts_with('.') {
+        return None;
+    }
+    cache_dir().map(|d| d.join("exports").join(format!("{cmd_name}.json")))
+}
+
+// The export `van --warm` saved for `cmd_name`, unless it came from another carapace, or the
+// command or carapace was installed again since
+fn cached_export(cmd_name: &str) -> Option<String> {
+    let path = cache_file(cmd_name)?;
+    let saved = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+    let changed = |bin: &str| {
+        which::which(bin)
+            .ok()
+            .and_then(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+            .is_some_and(|t| t > saved)
+    };
+    if changed(cmd_name) || changed(&carapace_bin()) {
+        return None;
+    }
+    let v: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+    if v["carapace"].as_str() != Some(carapace_bin().as_str()) {
+        return None;
+    }
+    v["export"].as_str().map(String::from)
+}
+
+// Export `cmd_name` and save it to the cache, so the first session using it needn't wait for
+// carapace
+pub fn warm(cmd_name: &str) -> Result<(), String> {
+    let path = cache_file(cmd_name).ok_or("no cache directory (set VAN_CACHE_DIR, XDG_CACHE_HOME or HOME)")?;
     let s = run_carapace_cmd(&[cmd_name, "export"])?;
+    def_from_export(cmd_name, &s)?;
+    let entry = serde_json::json!({"carapace": carapace_bin(), "export": s});
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+    }
+    fs::write(&path, entry.to_string()).map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
 
-    let r: serde_json::Value = serde_json::from_str(&s)
+// `<root>-<sub>` when such a binary is on PATH: how tools like `cargo` and `git` run
+// subcommands they don't implement themselves (`cargo foo` runs `cargo-foo`).
+pub fn forwarded_binary(root: &str, sub: &str) -> Option<String> {
+    if root.is_empty() || sub.is_empty() {
+        return None;
+    }
+    let name = format!("{root}-{sub}");
+    which::which(&name).ok().map(|_| name)
+}
+
+// JSON schema of the `carapace <cmd> export` output as read by `parse_export` (printed by
+// `van --schema`). Keep it in step with `map_raw` and `value_hint`.
+pub fn export_schema() -> &'static str {
+    r##"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "carapace export (as read by van)",
+  "description": "Keys are PascalCase; camelCase spellings are accepted too. Unknown keys are ignored.",
+  "$ref": "#/$defs/Command",
+  "$defs": {
+    "Command": {
+      "type": "object",
+      "properties": {
+        "Name": { "type": "string" },
+        "Short": { "type": "string", "description": "one-line description" },
+        "Aliases": { "type": "array", "items": { "type": "string" } },
+        "Hidden": { "type": "boolean", "description": "never offered as a candidate" },
+        "LocalFlags": { "type": "array", "items": { "$ref": "#/$defs/Flag" } },
+        "PersistentFlags": { "type": "array", "items": { "$ref": "#/$defs/Flag" }, "description": "read like LocalFlags" },
+        "Commands": { "type": "array", "items": { "$ref": "#/$defs/Command" } },
+        "ExclusiveFlags": { "type": "array", "items": { "type": "array", "items": { "type": "string" } }, "description": "sets of flag names (longhand, else shorthand) of which at most one may be given" },
+        "Completion": {
+          "type": "object",
+          "description": "non-empty Positional/PositionalAny marks a command taking arguments",
+          "properties": {
+            "Positional": { "type": "array" },
+            "PositionalAny": { "type": "array" }
+          }
+        }
+      }
+    },
+    "Flag": {
+      "type": "object",
+      "properties": {
+        "Longhand": { "type": "string", "description": "without the leading --" },
+        "Shorthand": { "type": "string", "description": "without the leading -" },
+        "Usage": { "type": "string" },
+        "LongUsage": { "type": "string", "description": "extended description, shown instead of Usage on Ctrl-O" },
+        "Type": { "type": "string", "default": "bool", "description": "anything but bool takes a value; int, float, duration, file, dir, boolean and stringToString-like types become placeholder hints" },
+        "Hidden": { "type": "boolean" },
+        "Nargs": { "type": "integer", "description": "-1: the flag takes every following word up to the next flag" },
+        "Values": { "type": "array", "items": { "type": "string" }, "description": "fixed choices (also read from Enum)" },
+        "Enum": { "type": "array", "items": { "type": "string" } }
+      }
+    }
+  }
+}"##
+}
+
+// Look up `key` (PascalCase, as carapace emits it) falling back to its camelCase spelling,
+// which other carapace versions and compatible tools use.
+fn field<'a>(v: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+    v.get(key).or_else(|| {
+        let mut chars = key.chars();
+        let camel: String = chars
+            .next()
+            .map(|c| c.to_ascii_lowercase())
+            .into_iter()
+            .chain(chars)
+            .collect();
+        v.get(camel)
+    })
+}
+
+// Parse the JSON printed by `carapace <cmd> export`
+pub(crate) fn parse_export(s: &str) -> Result<CommandDef, String> {
+    let r: serde_json::Value = serde_json::from_str(s)
         .map_err(|e| format!("failed to parse carapace export JSON: {e}"))?;
 
     fn map_raw(r: &serde_json::Value) -> CommandDef {
-        let name = r
-            .get("Name")
+        let name = field(r, "Name")
             .and_then(|v| v.as_str())
             .unwrap_or("")
             .to_string();
-        let short = r
-            .get("Short")
+        let short = field(r, "Short")
             .and_then(|v| v.as_str())
             .unwrap_or("")
             .to_string();
-        let aliases = r
-            .get("Aliases")
+        let aliases = field(r, "Aliases")
             .and_then(|v| v.as_array())
             .map(|arr| {
                 arr.iter()
@@ -83,47 +317,286 @@ pub fn export(cmd_name: &str) -> Result<CommandDef, String> {
             })
             .unwrap_or_default();
         let mut flags = Vec::new();
-        if let Some(local) = r.get("LocalFlags").and_then(|v| v.as_array()) {
-            for f in local {
-                let long = f
-                    .get("Longhand")
+        let declared = ["LocalFlags", "PersistentFlags"]
+            .iter()
+            .filter_map(|k| field(r, k).and_then(|v| v.as_array()));
+        for list in declared {
+            for f in list {
+                let long = field(f, "Longhand")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                let shortf = field(f, "Shorthand")
                     .and_then(|v| v.as_str())
                     .unwrap_or("")
                     .to_string();
-                let shortf = f
-                    .get("Shorthand")
+                let usage = field(f, "Usage")
                     .and_then(|v| v.as_str())
                     .unwrap_or("")
                     .to_string();
-                let usage = f
-                    .get("Usage")
+                let long_usage = field(f, "LongUsage")
                     .and_then(|v| v.as_str())
                     .unwrap_or("")
                     .to_string();
-                let typ = f.get("Type").and_then(|v| v.as_str()).unwrap_or("bool");
+                let typ = field(f, "Type").and_then(|v| v.as_str()).unwrap_or("bool");
+                let hidden = field(f, "Hidden").and_then(|v| v.as_bool()).unwrap_or(false);
+                // carapace's Nargs: -1 for "until the next flag"; a fixed count is read as one value
+                let nargs = field(f, "Nargs").and_then(|v| v.as_i64()).unwrap_or(0);
                 let fd = FlagDef {
                     longhand: long,
                     shorthand: shortf,
                     usage,
+                    long_usage,
                     requires_value: typ != "bool",
+                    value_hint: value_hint(f, typ),
+                    hidden,
+                    variadic: typ != "bool" && nargs == -1,
+                    synthesized: false,
+                    negation: String::new(),
                 };
                 flags.push(fd);
             }
         }
         let mut subs = Vec::new();
-        if let Some(cmds) = r.get("Commands").and_then(|v| v.as_array()) {
+        if let Some(cmds) = field(r, "Commands").and_then(|v| v.as_array()) {
             for c in cmds {
                 subs.push(map_raw(c));
             }
         }
+        let hidden = field(r, "Hidden").and_then(|v| v.as_bool()).unwrap_or(false);
+        let takes_args = field(r, "Completion").is_some_and(|c| {
+            ["Positional", "PositionalAny"]
+                .iter()
+                .any(|k| field(c, k).is_some_and(|v| v.as_array().is_some_and(|a| !a.is_empty())))
+        });
+        let exclusive_flags = field(r, "ExclusiveFlags")
+            .and_then(|v| v.as_array())
+            .map(|sets| {
+                sets.iter()
+                    .filter_map(|set| set.as_array())
+                    .map(|set| set.iter().filter_map(|x| x.as_str().map(String::from)).collect::<Vec<_>>())
+                    .filter(|set| set.len() > 1)
+                    .collect()
+            })
+            .unwrap_or_default();
         CommandDef {
             name,
             short,
             aliases,
             flags,
             subcommands: subs,
+            takes_args,
+            hidden,
+            exclusive_flags,
         }
     }
 
     Ok(map_raw(&r))
 }
+
+// Test helpers: point carapace at a throwaway shell script for the duration of `f`, and change
+// other environment variables safely. Tests touching the process environment are serialized
+// by one lock, held by `with_fake_carapace` and `lock_env`.
+#[cfg(all(test, unix))]
+pub(crate) mod fake {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::{Mutex, MutexGuard};
+
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    pub(crate) fn lock_env() -> MutexGuard<'static, ()> {
+        LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Variables set until dropped, then put back as they were (also when the test panics).
+    // Only create one while holding the lock.
+    pub(crate) struct EnvVars(Vec<(String, Option<OsString>)>);
+
+    impl EnvVars {
+        pub(crate) fn set<V: AsRef<std::ffi::OsStr>>(vars: &[(&str, V)]) -> Self {
+            let saved = vars.iter().map(|(k, _)| (k.to_string(), std::env::var_os(k))).collect();
+            for (k, v) in vars {
+                std::env::set_var(k, v);
+            }
+            EnvVars(saved)
+        }
+    }
+
+    impl Drop for EnvVars {
+        fn drop(&mut self) {
+            for (k, v) in self.0.iter().rev() {
+                match v {
+                    Some(v) => std::env::set_var(k, v),
+                    None => std::env::remove_var(k),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn with_fake_carapace<R>(script: &str, f: impl FnOnce() -> R) -> R {
+        let _lock = lock_env();
+        let dir = std::env::temp_dir().join(format!("van-fake-carapace-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("carapace");
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        let _env = EnvVars::set(&[(super::CARAPACE_BIN_ENV, &path)]);
+        f()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::fake::{with_fake_carapace, EnvVars};
+
+    #[test]
+    fn warming_saves_exports_that_later_lookups_use() {
+        let script = r#"case "$1" in tool) echo '{"Name":"tool","Commands":[{"Name":"build"}]}';; *) exit 1;; esac"#;
+        with_fake_carapace(script, || {
+            let dir = std::env::temp_dir().join(format!("van-cache-{}", std::process::id()));
+            let _env = EnvVars::set(&[("VAN_CACHE_DIR", &dir)]);
+            assert_eq!(super::warm("tool"), Ok(()));
+            assert!(super::warm("missing").is_err());
+            assert!(super::cache_file("tool").unwrap().exists());
+            assert!(!super::cache_file("missing").unwrap().exists());
+            assert!(super::cached_export("tool").is_some_and(|s| s.contains("build")));
+            assert_eq!(super::export("tool").unwrap().subcommands[0].name, "build");
+
+            // an export saved from another carapace is not used
+            {
+                let _other = EnvVars::set(&[(super::CARAPACE_BIN_ENV, "other-carapace")]);
+                assert!(super::cached_export("tool").is_none());
+            }
+            assert!(super::cached_export("tool").is_some());
+
+            std::fs::remove_dir_all(&dir).unwrap();
+        });
+    }
+
+    #[test]
+    fn path_lookups_are_cached_until_path_changes() {
+        let mut lookups = super::PathLookups::default();
+        let calls = std::cell::Cell::new(0);
+        let lookup = |name: &str| {
+            calls.set(calls.get() + 1);
+            name == "sh"
+        };
+        let path = |p: &str| Some(std::ffi::OsString::from(p));
+        for _ in 0..2 {
+            assert!(lookups.resolves(path("/bin"), "sh", lookup));
+            assert!(!lookups.resolves(path("/bin"), "van-offpath-tool-xyz", lookup));
+        }
+        assert_eq!(calls.get(), 2, "the second round is answered from the cache");
+        assert!(lookups.resolves(path("/usr/bin:/bin"), "sh", lookup));
+        assert_eq!(calls.get(), 3, "a new PATH starts over");
+    }
+
+    #[test]
+    fn consecutive_listings_agree() {
+        let script = r#"echo "sh            POSIX shell"
+echo "van-offpath-tool-xyz  not installed here""#;
+        with_fake_carapace(script, || {
+            let first = super::list_with_desc().unwrap();
+            assert_eq!(first, vec![("sh".to_string(), "POSIX shell".to_string())]);
+            assert_eq!(super::list_with_desc().unwrap(), first);
+        });
+    }
+
+    #[test]
+    fn list_entries_path_filter_can_be_disabled() {
+        let script = r#"echo "sh            POSIX shell"
+echo "van-offpath-tool-xyz  not installed here""#;
+        with_fake_carapace(script, || {
+            let filtered = super::list_entries(true).unwrap();
+            assert!(filtered.iter().any(|(n, _)| n == "sh"));
+            assert!(!filtered.iter().any(|(n, _)| n == "van-offpath-tool-xyz"));
+
+            let all = super::list_entries(false).unwrap();
+            assert!(all.contains(&("van-offpath-tool-xyz".to_string(), "not installed here".to_string())));
+        });
+    }
+
+    #[test]
+    fn export_without_completion_data_is_an_error() {
+        for out in ["echo '{}'", "echo '  '", "echo '{\"Name\":\"\"}'"] {
+            let err = with_fake_carapace(out, || super::export("tool")).unwrap_err();
+            assert_eq!(err, "no completion data for tool", "for {out}");
+        }
+        // nameless but with flags: keep it under the requested name
+        let def = with_fake_carapace(r#"echo '{"LocalFlags":[{"Longhand":"all"}]}'"#, || super::export("tool")).unwrap();
+        assert_eq!(def.name, "tool");
+    }
+
+    #[test]
+    fn export_parses_flag_value_hints() {
+        let script = r#"echo '{"Name":"tool","LocalFlags":[
+  {"Longhand":"jobs","Type":"int"},
+  {"Longhand":"color","Type":"string","Values":["on","off","auto"]},
+  {"Longhand":"output","Type":"file"},
+  {"Longhand":"name","Type":"string"},
+  {"Longhand":"set","Type":"stringToString"},
+  {"Longhand":"strict","Type":"boolean","LongUsage":"fail on warnings too"},
+  {"Longhand":"verbose"}]}'"#;
+        let def = with_fake_carapace(script, || super::export("tool")).unwrap();
+        let hints: Vec<(&str, &str)> = def
+            .flags
+            .iter()
+            .map(|f| (f.longhand.as_str(), f.value_hint.as_str()))
+            .collect();
+        assert_eq!(
+            hints,
+            vec![("jobs", "int"), ("color", "on|off|auto"), ("output", "file"), ("name", ""), ("set", "key=value"), ("strict", "true|false"), ("verbose", "")]
+        );
+        assert_eq!(def.flags[5].long_usage, "fail on warnings too");
+    }
+}
+
+#[cfg(test)]
+mod parse_tests {
+    const PASCAL: &str = r#"{"Name":"tool","Short":"a tool","Aliases":["t"],
+  "LocalFlags":[{"Longhand":"jobs","Shorthand":"j","Usage":"parallel jobs","Type":"int"},{"Longhand":"debug","Hidden":true}],
+  "Commands":[{"Name":"cat","Completion":{"PositionalAny":["$files"]}}]}"#;
+    const CAMEL: &str = r#"{"name":"tool","short":"a tool","aliases":["t"],
+  "localFlags":[{"longhand":"jobs","shorthand":"j","usage":"parallel jobs","type":"int"},{"longhand":"debug","hidden":true}],
+  "commands":[{"name":"cat","completion":{"positionalAny":["$files"]}}]}"#;
+
+    #[test]
+    fn camel_case_export_parses_like_pascal_case() {
+        let pascal = super::parse_export(PASCAL).unwrap();
+        let camel = super::parse_export(CAMEL).unwrap();
+        assert_eq!(format!("{pascal:?}"), format!("{camel:?}"));
+
+        assert_eq!(pascal.name, "tool");
+        assert_eq!(pascal.aliases, vec!["t".to_string()]);
+        assert_eq!(pascal.flags[0].value_hint, "int");
+        assert!(pascal.flags[1].hidden);
+        assert!(pascal.subcommands[0].takes_args);
+    }
+
+    #[test]
+    fn only_nargs_minus_one_makes_a_flag_variadic() {
+        let def = super::parse_export(
+            r#"{"Name":"tool","LocalFlags":[
+  {"Longhand":"files","Type":"stringSlice","Nargs":-1},
+  {"Longhand":"pair","Type":"string","Nargs":2},
+  {"Longhand":"name","Type":"string"}]}"#,
+        )
+        .unwrap();
+        let variadic: Vec<bool> = def.flags.iter().map(|f| f.variadic).collect();
+        assert_eq!(variadic, vec![true, false, false]);
+    }
+
+    #[test]
+    fn export_schema_names_the_fields_map_raw_reads() {
+        let schema: serde_json::Value = serde_json::from_str(super::export_schema()).unwrap();
+        let defs = &schema["$defs"];
+        for key in ["Name", "Short", "Aliases", "Hidden", "LocalFlags", "PersistentFlags", "Commands", "Completion", "ExclusiveFlags"] {
+            assert!(defs["Command"]["properties"].get(key).is_some(), "missing Command.{key}");
+        }
+        for key in ["Longhand", "Shorthand", "Usage", "LongUsage", "Type", "Hidden", "Values"] {
+            assert!(defs["Flag"]["properties"].get(key).is_some(), "missing Flag.{key}");
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// Command definitions from a clap-style JSON spec, for tools carapace knows nothing about (e.g.
+// a Rust CLI being developed). The spec mirrors clap's `Command`/`Arg` builder names:
+//
+//   {"name": "tool", "about": "...", "aliases": ["t"], "hide": false,
+//    "args": [{"id": "verbose", "short": "v", "long": "verbose", "help": "...",
+//              "long_help": "...", "action": "SetTrue", "value_names": ["FILE"],
+//              "possible_values": ["a", "b"], "value_hint": "FilePath", "global": true,
+//              "num_args": "1..", "hide": false, "conflicts_with": ["quiet"]}],
+//    "groups": [{"id": "format", "args": ["json", "yaml"], "multiple": false}],
+//    "subcommands": [{"name": "build", ...}]}
+//
+// Args with neither `short` nor `long` are positionals. `global` args are offered on every
+// subcommand below the one declaring them, as clap does. `conflicts_with` and groups not
+// allowing `multiple` become the command's exclusive flags.
+use crate::ast::{CommandDef, FlagDef};
+use serde_json::Value;
+use std::path::Path;
+
+pub fn load(path: &Path) -> Result<CommandDef, String> {
+    let s = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    parse_spec(&s)
+}
+
+pub fn parse_spec(s: &str) -> Result<CommandDef, String> {
+    let v: Value = serde_json::from_str(s).map_err(|e| format!("failed to parse clap spec JSON: {e}"))?;
+    let def = map_command(&v, &[]);
+    if def.name.trim().is_empty() {
+        return Err("clap spec has no command name".to_string());
+    }
+    Ok(def)
+}
+
+fn str_field(v: &Value, key: &str) -> String {
+    v.get(key).and_then(Value::as_str).unwrap_or("").to_string()
+}
+
+fn strings(v: &Value, key: &str) -> Vec<String> {
+    v.get(key)
+        .and_then(Value::as_array)
+        .map(|arr| arr.iter().filter_map(|x| x.as_str().map(String::from)).collect())
+        .unwrap_or_default()
+}
+
+fn map_command(v: &Value, inherited: &[FlagDef]) -> CommandDef {
+    let mut aliases = strings(v, "aliases");
+    aliases.extend(strings(v, "visible_aliases"));
+    let args = v.get("args").and_then(Value::as_array).cloned().unwrap_or_default();
+    let is_flag = |a: &Value| a.get("short").is_some() || a.get("long").is_some();
+
+    let mut flags: Vec<FlagDef> = args.iter().filter(|a| is_flag(a)).map(map_flag).collect();
+    let mut globals = inherited.to_vec();
+    globals.extend(
+        args.iter()
+            .filter(|a| is_flag(a) && a.get("global").and_then(Value::as_bool).unwrap_or(false))
+            .map(map_flag),
+    );
+    for g in inherited {
+        if !flags.iter().any(|f| f.longhand == g.longhand && f.shorthand == g.shorthand) {
+            flags.push(g.clone());
+        }
+    }
+
+    let subcommands = v
+        .get("subcommands")
+        .and_then(Value::as_array)
+        .map(|subs| subs.iter().map(|s| map_command(s, &globals)).collect())
+        .unwrap_or_default();
+    // a flag arg's name as exclusive_flags has it: its long, else its short
+    let flag_name = |id: &str| {
+        args.iter()
+            .find(|a| is_flag(a) && a.get("id").and_then(Value::as_str) == Some(id))
+            .map(|a| if str_field(a, "long").is_empty() { str_field(a, "short") } else { str_field(a, "long") })
+    };
+    let mut exclusive_flags: Vec<Vec<String>> = vec![];
+    for a in args.iter().filter(|a| is_flag(a)) {
+        let Some(name) = flag_name(&str_field(a, "id")) else { continue };
+        for other in strings(a, "conflicts_with").iter().filter_map(|id| flag_name(id)) {
+            exclusive_flags.push(vec![name.clone(), other]);
+        }
+    }
+    for g in v.get("groups").and_then(Value::as_array).into_iter().flatten() {
+        if g.get("multiple").and_then(Value::as_bool).unwrap_or(false) {
+            continue;
+        }
+        let set: Vec<String> = strings(g, "args").iter().filter_map(|id| flag_name(id)).collect();
+        if set.len() > 1 {
+            exclusive_flags.push(set);
+        }
+    }
+    CommandDef {
+        name: str_field(v, "name"),
+        short: str_field(v, "about"),
+        aliases,
+        flags,
+        subcommands,
+        takes_args: args.iter().any(|a| !is_flag(a)),
+        hidden: v.get("hide").and_then(Value::as_bool).unwrap_or(false),
+        exclusive_flags,
+    }
+}
+
+fn map_flag(a: &Value) -> FlagDef {
+    let action = str_field(a, "action");
+    let value_names = strings(a, "value_names");
+    // possible values may be plain strings or `{"name": ..., "hide": bool}` objects
+    let choices: Vec<String> = a
+        .get("possible_values")
+        .and_then(Value::as_array)
+        .map(|arr| {
+            arr.iter()
+                .filter(|x| !x.get("hide").and_then(Value::as_bool).unwrap_or(false))
+                .filter_map(|x| x.as_str().or_else(|| x.get("name").and_then(Value::as_str)).map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    // clap's default action for a named arg without values is SetTrue
+    let requires_value = match action.as_str() {
+        "Set" | "Append" => true,
+        "" => !value_names.is_empty() || !choices.is_empty(),
+        _ => false,
+    };
+    let value_hint = if !requires_value {
+        String::new()
+    } else if !choices.is_empty() {
+        choices.join("|")
+    } else {
+        match str_field(a, "value_hint").as_str() {
+            "FilePath" | "ExecutablePath" => "file".to_string(),
+            "DirPath" => "directory".to_string(),
+            "AnyPath" => "path".to_string(),
+            "Url" => "url".to_string(),
+            "Hostname" => "host".to_string(),
+            "Username" => "user".to_string(),
+            "CommandName" => "command".to_string(),
+            _ => value_names.first().map(|n| n.to_lowercase()).unwrap_or_default(),
+        }
+    };
+    FlagDef {
+        longhand: str_field(a, "long"),
+        shorthand: str_field(a, "short"),
+        usage: str_field(a, "help"),
+        long_usage: str_field(a, "long_help"),
+        requires_value,
+        value_hint,
+        hidden: a.get("hide").and_then(Value::as_bool).unwrap_or(false),
+        // num_args as clap prints ranges (`1..`, `2..=3`) or a count
+        variadic: requires_value
+            && match a.get("num_args") {
+                Some(Value::String(r)) => r.contains(".."),
+                Some(n) => n.as_u64().is_some_and(|n| n > 1),
+                None => false,
+            },
+        synthesized: false,
+        negation: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_spec;
+
+    const SPEC: &str = r#"{
+  "name": "tool", "about": "Builds things",
+  "args": [
+    {"id": "verbose", "short": "v", "long": "verbose", "help": "More output", "action": "Count", "global": true},
+    {"id": "config", "long": "config", "help": "Config file", "action": "Set", "value_hint": "FilePath"}
+  ],
+  "subcommands": [
+    {"name": "build", "about": "Compile", "visible_aliases": ["b"],
+     "args": [
+       {"id": "profile", "long": "profile", "possible_values": ["dev", {"name": "release"}, {"name": "old", "hide": true}]},
+       {"id": "target", "value_names": ["TARGET"]}
+     ]},
+    {"name": "internal", "hide": true}
+  ]
+}"#;
+
+    #[test]
+    fn maps_a_clap_spec_to_subcommands_and_flags() {
+        let def = parse_spec(SPEC).unwrap();
+        assert_eq!((def.name.as_str(), def.short.as_str()), ("tool", "Builds things"));
+        assert!(!def.takes_args);
+        let forms: Vec<(&str, &str, bool, &str)> = def
+            .flags
+            .iter()
+            .map(|f| (f.longhand.as_str(), f.shorthand.as_str(), f.requires_value, f.value_hint.as_str()))
+            .collect();
+        assert_eq!(forms, vec![("verbose", "v", false, ""), ("config", "", true, "file")]);
+
+        let names: Vec<&str> = def.subcommands.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "internal"]);
+        assert!(def.subcommands[1].hidden);
+
+        let build = &def.subcommands[0];
+        assert_eq!(build.aliases, vec!["b".to_string()]);
+        assert!(build.takes_args);
+        // the global --verbose carries over; --config does not
+        let build_flags: Vec<&str> = build.flags.iter().map(|f| f.longhand.as_str()).collect();
+        assert_eq!(build_flags, vec!["profile", "verbose"]);
+        assert_eq!(build.flags[0].value_hint, "dev|release");
+        assert!(build.flags[0].requires_value);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spec_source_completes_without_carapace() {
+        use crate::source::{Source, StaticSource};
+        let source = Source::new(StaticSource::new(vec![parse_spec(SPEC).unwrap()]));
+        let run = |args: &[&str]| crate::ui::run_with_source(source.clone(), crate::ui::Settings::from_env(), args.iter().map(|a| a.to_string()).collect());
+        // a carapace that fails proves it is never asked; the fake also serializes this test with
+        // the ones setting COMP_LINE
+        crate::carapace::fake::with_fake_carapace("exit 1", || {
+            assert_eq!(run(&["tool", "bu"]).unwrap(), "build");
+            assert_eq!(run(&["tool", "build", "--pro"]).unwrap(), "--profile");
+        });
+    }
+
+    #[test]
+    fn conflicts_and_single_choice_groups_become_exclusive_flags() {
+        let def = parse_spec(
+            r#"{"name": "fmt",
+                "args": [{"id": "json", "long": "json", "conflicts_with": ["quiet"]},
+                         {"id": "yaml", "long": "yaml"}, {"id": "quiet", "short": "q"},
+                         {"id": "color", "long": "color"}, {"id": "file"}],
+                "groups": [{"id": "format", "args": ["json", "yaml"]},
+                           {"id": "extras", "args": ["color", "quiet"], "multiple": true}]}"#,
+        )
+        .unwrap();
+        let sets: Vec<Vec<&str>> = def.exclusive_flags.iter().map(|s| s.iter().map(String::as_str).collect()).collect();
+        assert_eq!(sets, vec![vec!["json", "q"], vec!["json", "yaml"]]);
+    }
+
+    #[test]
+    fn rejects_specs_without_a_name() {
+        assert!(parse_spec("{}").unwrap_err().contains("no command name"));
+        assert!(parse_spec("not json").unwrap_err().starts_with("failed to parse clap spec JSON"));
+    }
+}
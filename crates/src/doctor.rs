@@ -0,0 +1,135 @@
+// `van --doctor`: checks of the environment van depends on, for debugging a setup where
+// completion doesn't work before filing an issue.
+
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &str, result: Result<String, String>) -> Self {
+        let (ok, detail) = match result {
+            Ok(d) => (true, d),
+            Err(d) => (false, d),
+        };
+        Check { name: name.to_string(), ok, detail }
+    }
+}
+
+// carapace runs and reports a version
+pub fn check_carapace() -> Check {
+    Check::new(
+        "carapace",
+        crate::carapace::version().map_err(|e| {
+            format!("{e} (install carapace or point {} at it)", crate::carapace::CARAPACE_BIN_ENV)
+        }),
+    )
+}
+
+// `shell` (as detected from $SHELL) has a hook of its own
+pub fn check_shell(shell: &str, supported: &[&str]) -> Check {
+    let result = if supported.contains(&shell.to_lowercase().as_str()) {
+        Ok(shell.to_string())
+    } else {
+        Err(format!("{shell:?} has no hook (supported: {}); the bash hook is used", supported.join(", ")))
+    };
+    Check::new("shell", result)
+}
+
+// The `kind` directory (`state`, `cache`, `config`) exists or can be created, and a file can be
+// written to it; without one, which variables would name it
+pub fn check_dir(kind: &str, dir: Option<&Path>) -> Check {
+    let result = match dir {
+        None => {
+            let (van, xdg) = (format!("VAN_{}_DIR", kind.to_uppercase()), format!("XDG_{}_HOME", kind.to_uppercase()));
+            Err(format!("no {kind} directory (set {van}, {xdg} or HOME)"))
+        }
+        Some(dir) => {
+            let probe = dir.join(".van-doctor-probe");
+            fs::create_dir_all(dir)
+                .and_then(|_| fs::write(&probe, b"ok"))
+                .and_then(|_| fs::remove_file(&probe))
+                .map(|_| format!("{} is writable", dir.display()))
+                .map_err(|e| format!("{} is not writable: {e}", dir.display()))
+        }
+    };
+    Check::new(&format!("{kind} dir"), result)
+}
+
+// `cmd` resolves on PATH, as top-level commands must to be listed
+pub fn check_which(cmd: &str) -> Check {
+    let result = which::which(cmd)
+        .map(|p| format!("{cmd} -> {}", p.display()))
+        .map_err(|e| format!("{cmd} not found on PATH: {e}"));
+    Check::new("PATH lookup", result)
+}
+
+// Every check, in report order
+pub fn run_checks(shell: &str, supported_shells: &[&str]) -> Vec<Check> {
+    vec![
+        check_carapace(),
+        check_shell(shell, supported_shells),
+        check_dir("state", crate::history::state_dir().as_deref()),
+        check_dir("cache", crate::carapace::cache_dir().as_deref()),
+        check_dir("config", crate::ui::settings::config_dir().as_deref()),
+        check_which("sh"),
+    ]
+}
+
+// One `[ok]`/`[FAIL]` line per check
+pub fn render(checks: &[Check]) -> String {
+    let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    checks
+        .iter()
+        .map(|c| {
+            let status = if c.ok { "[ok]  " } else { "[FAIL]" };
+            format!("{status} {:<width$}  {}\n", c.name, c.detail)
+        })
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::carapace::fake::{with_fake_carapace, EnvVars};
+
+    #[test]
+    fn carapace_check_follows_the_configured_binary() {
+        let ok = with_fake_carapace("echo 'carapace-bin 1.2.3'", check_carapace);
+        assert!(ok.ok);
+        assert_eq!(ok.detail, "carapace-bin 1.2.3");
+
+        let missing = with_fake_carapace("", || {
+            let _env = EnvVars::set(&[(crate::carapace::CARAPACE_BIN_ENV, "/nonexistent/van-doctor/carapace")]);
+            check_carapace()
+        });
+        assert!(!missing.ok);
+        assert!(missing.detail.contains("VAN_CARAPACE_BIN"), "{}", missing.detail);
+        assert!(render(&[missing]).starts_with("[FAIL] carapace"));
+    }
+
+    #[test]
+    fn shell_state_dir_and_path_checks() {
+        assert!(check_shell("zsh", &["bash", "zsh"]).ok);
+        assert!(!check_shell("xonsh", &["bash", "zsh"]).ok);
+
+        let dir = std::env::temp_dir().join(format!("van-doctor-{}", std::process::id()));
+        assert!(check_dir("state", Some(&dir)).ok);
+        let file = dir.join("file");
+        fs::write(&file, "x").unwrap();
+        assert!(!check_dir("cache", Some(&file.join("sub"))).ok);
+        let missing = check_dir("config", None);
+        assert!(!missing.ok);
+        assert_eq!(missing.name, "config dir");
+        assert!(missing.detail.contains("VAN_CONFIG_DIR, XDG_CONFIG_HOME or HOME"), "{}", missing.detail);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(check_which("sh").ok);
+        assert!(!check_which("van-doctor-no-such-command").ok);
+    }
+}
@@ -0,0 +1,117 @@
+// Aliases from git's configuration, offered as subcommands of the `git` root when
+// VAN_GIT_ALIASES is set. Choosing one puts what it stands for into the command, so
+// `co = checkout -b` continues as `git checkout -b` with checkout's flags.
+
+use crate::ast::CommandDef;
+use std::collections::HashMap;
+use std::process::Command;
+
+// Environment variable overriding the git executable (useful for wrappers and tests)
+pub const GIT_BIN_ENV: &str = "VAN_GIT_BIN";
+
+// Configured aliases, name to definition, in the order git lists them. No aliases at all is
+// not an error (git exits 1 when the pattern matches nothing).
+pub fn aliases() -> Result<Vec<(String, String)>, String> {
+    let bin = std::env::var(GIT_BIN_ENV)
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "git".to_string());
+    let out = Command::new(bin)
+        .args(["config", "--get-regexp", r"^alias\."])
+        .output()
+        .map_err(|e| format!("git config failed to run: {e}"))?;
+    Ok(parse_aliases(&String::from_utf8_lossy(&out.stdout)))
+}
+
+// `alias.<name> <definition>` lines as `git config --get-regexp` prints them
+pub fn parse_aliases(out: &str) -> Vec<(String, String)> {
+    out.lines()
+        .filter_map(|l| l.strip_prefix("alias."))
+        .filter_map(|l| l.split_once(' '))
+        .map(|(name, def)| (name.to_string(), def.trim().to_string()))
+        .collect()
+}
+
+// Add each alias to `def` as a subcommand, and return the words each one expands to. Aliases
+// shadowing a subcommand are skipped (git ignores them too); shell (`!`) aliases and ones
+// naming another alias are listed but not expanded.
+pub fn add_alias_subcommands(def: &mut CommandDef, aliases: &[(String, String)]) -> HashMap<String, Vec<String>> {
+    let mut expansions = HashMap::new();
+    let builtin = |name: &str| def.subcommands.iter().any(|sc| sc.name == name || sc.aliases.iter().any(|a| a == name));
+    let listed: Vec<&(String, String)> = aliases.iter().filter(|(name, _)| !builtin(name)).collect();
+    let mut subcommands = vec![];
+    for (name, target) in listed.iter().copied() {
+        let words = crate::parse::tokenize_command_line(target);
+        let chained = words.first().is_some_and(|w| listed.iter().any(|(n, _)| n == w));
+        if !target.starts_with('!') && !chained && !words.is_empty() {
+            expansions.insert(name.clone(), words);
+        }
+        subcommands.push(CommandDef {
+            name: name.clone(),
+            short: format!("alias for {target}"),
+            takes_args: true,
+            ..Default::default()
+        });
+    }
+    def.subcommands.extend(subcommands);
+    expansions
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::ast::{CommandDef, FlagDef};
+
+    #[test]
+    fn configured_aliases_are_git_subcommands_that_expand_in_the_preview() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("van-fake-git-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let git = dir.join("git");
+        let listing = "alias.co checkout\nalias.lg log --oneline\nalias.up !git pull --rebase\nalias.log log -p";
+        std::fs::write(&git, format!("#!/bin/sh\nprintf '{listing}\\n'\n")).unwrap();
+        std::fs::set_permissions(&git, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let _lock = crate::carapace::fake::lock_env();
+        let _env = crate::carapace::fake::EnvVars::set(&[(super::GIT_BIN_ENV, &git)]);
+
+        let sub = |name: &str, flag: &str| CommandDef {
+            name: name.to_string(),
+            flags: vec![FlagDef { longhand: flag.to_string(), ..Default::default() }],
+            ..Default::default()
+        };
+        let def = CommandDef {
+            name: "git".to_string(),
+            subcommands: vec![sub("checkout", "track"), sub("log", "oneline"), sub("pull", "rebase")],
+            ..Default::default()
+        };
+        let source = crate::source::Source::new(crate::source::StaticSource::new(vec![def]));
+        let fresh = || {
+            let mut m = crate::ui::initial_model(vec![]);
+            m.source = source.clone();
+            m.settings.git_aliases = true;
+            m
+        };
+        let words = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+
+        // `log` shadows a subcommand, so it is no alias
+        let mut m = fresh();
+        crate::ui::run::load_args(&mut m, &words("git")).unwrap();
+        let mut labels: Vec<&str> = m.items.iter().map(|it| it.label.as_str()).collect();
+        labels.sort();
+        assert_eq!(labels, vec!["checkout", "co", "lg", "log", "pull", "up"]);
+
+        crate::ui::run::load_args(&mut m, &words("git co --track")).unwrap();
+        assert_eq!(m.preview_text(), "git checkout --track");
+
+        // chosen interactively, with the keys that select it
+        for (alias, preview) in [("lg", "git log --oneline"), ("up", "git up")] {
+            let mut m = fresh();
+            crate::ui::run::load_args(&mut m, &words("git")).unwrap();
+            for r in m.keystroke_path_to(alias).unwrap().chars() {
+                m.update(crate::ui::Msg::Rune(r));
+            }
+            assert_eq!(m.preview_text(), preview);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+// Persistent state kept between sessions in van's state directory
+// ($VAN_STATE_DIR, else $XDG_STATE_HOME/van, else ~/.local/state/van).
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// values remembered per flag, most recent first
+const MAX_VALUES_PER_FLAG: usize = 20;
+
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(d) = env::var_os("VAN_STATE_DIR").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(d));
+    }
+    if let Some(d) = env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(d).join("van"));
+    }
+    env::var_os("HOME")
+        .filter(|d| !d.is_empty())
+        .map(|h| PathBuf::from(h).join(".local").join("state").join("van"))
+}
+
+// Previously entered flag values, keyed by command path + flag (e.g. "git checkout --branch").
+// Without a path nothing is persisted, which is what tests and embedders get by default.
+#[derive(Clone, Debug, Default)]
+pub struct ValueHistory {
+    pub path: Option<PathBuf>,
+    values: HashMap<String, Vec<String>>,
+}
+
+impl ValueHistory {
+    // A missing or unreadable file starts an empty history rather than failing the session
+    pub fn load(path: PathBuf) -> Self {
+        let values = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        ValueHistory {
+            path: Some(path),
+            values,
+        }
+    }
+
+    pub fn load_default() -> Self {
+        state_dir()
+            .map(|d| Self::load(d.join("values.json")))
+            .unwrap_or_default()
+    }
+
+    pub fn key(cmd_path: &[&str], flag: &str) -> String {
+        let mut parts = cmd_path.to_vec();
+        parts.push(flag);
+        parts.join(" ")
+    }
+
+    pub fn values(&self, key: &str) -> &[String] {
+        self.values.get(key).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // Remember `value` as the most recent for `key` and persist when backed by a file
+    pub fn record(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if value.is_empty() {
+            return Ok(());
+        }
+        let vals = self.values.entry(key.to_string()).or_default();
+        vals.retain(|v| v != value);
+        vals.insert(0, value.to_string());
+        vals.truncate(MAX_VALUES_PER_FLAG);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create state dir {}: {e}", dir.display()))?;
+        }
+        let json = serde_json::to_string_pretty(&self.values)
+            .map_err(|e| format!("failed to encode value history: {e}"))?;
+        fs::write(path, json).map_err(|e| format!("failed to write {}: {e}", path.display()))
+    }
+}
+
+// How often each top-level command was run from van, for listing the frequent ones first.
+// Like `ValueHistory`, nothing is persisted without a path.
+#[derive(Clone, Debug, Default)]
+pub struct CommandUsage {
+    pub path: Option<PathBuf>,
+    counts: HashMap<String, u32>,
+}
+
+impl CommandUsage {
+    pub fn load(path: PathBuf) -> Self {
+        let counts = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        CommandUsage {
+            path: Some(path),
+            counts,
+        }
+    }
+
+    pub fn load_default() -> Self {
+        state_dir()
+            .map(|d| Self::load(d.join("usage.json")))
+            .unwrap_or_default()
+    }
+
+    pub fn count(&self, command: &str) -> u32 {
+        self.counts.get(command).copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    // Count one more run of `command` and persist when backed by a file
+    pub fn record(&mut self, command: &str) -> Result<(), String> {
+        if command.is_empty() {
+            return Ok(());
+        }
+        *self.counts.entry(command.to_string()).or_default() += 1;
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create state dir {}: {e}", dir.display()))?;
+        }
+        let json = serde_json::to_string_pretty(&self.counts)
+            .map_err(|e| format!("failed to encode command usage: {e}"))?;
+        fs::write(path, json).map_err(|e| format!("failed to write {}: {e}", path.display()))
+    }
+}
+
+// The terminal size seen last session, so the first frame is laid out at (likely) the right
+// size instead of reflowing from 80x24 once the terminal reports its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WindowSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+pub fn window_size_path() -> Option<PathBuf> {
+    state_dir().map(|d| d.join("window.json"))
+}
+
+// None when the file is missing, unreadable or records an empty size
+pub fn load_window_size(path: &Path) -> Option<WindowSize> {
+    let s = fs::read_to_string(path).ok()?;
+    let size: WindowSize = serde_json::from_str(&s).ok()?;
+    (size.width > 0 && size.height > 0).then_some(size)
+}
+
+pub fn save_window_size(path: &Path, size: WindowSize) -> Result<(), String> {
+    if load_window_size(path) == Some(size) {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create state dir {}: {e}", dir.display()))?;
+    }
+    let json = serde_json::to_string(&size)
+        .map_err(|e| format!("failed to encode window size: {e}"))?;
+    fs::write(path, json).map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValueHistory;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("van-history-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("values.json")
+    }
+
+    #[test]
+    fn record_orders_most_recent_first_without_duplicates() {
+        let mut h = ValueHistory::default();
+        let key = ValueHistory::key(&["git", "checkout"], "--branch");
+        assert_eq!(key, "git checkout --branch");
+        h.record(&key, "main").unwrap();
+        h.record(&key, "dev").unwrap();
+        h.record(&key, "main").unwrap();
+        h.record(&key, "").unwrap();
+        assert_eq!(h.values(&key), ["main".to_string(), "dev".to_string()]);
+        assert!(h.values("git --other").is_empty());
+    }
+
+    #[test]
+    fn values_round_trip_through_the_state_file() {
+        let path = temp_path("roundtrip");
+        let mut h = ValueHistory::load(path.clone());
+        assert!(h.values("k --f").is_empty());
+        h.record("k --f", "one").unwrap();
+
+        let reloaded = ValueHistory::load(path.clone());
+        assert_eq!(reloaded.values("k --f"), ["one".to_string()]);
+
+        // a corrupt file degrades to an empty history
+        std::fs::write(&path, "not json").unwrap();
+        assert!(ValueHistory::load(path.clone()).values("k --f").is_empty());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn window_size_round_trips_and_ignores_bad_files() {
+        let path = temp_path("window").with_file_name("window.json");
+        assert_eq!(super::load_window_size(&path), None);
+        let size = super::WindowSize { width: 132, height: 40 };
+        super::save_window_size(&path, size).unwrap();
+        assert_eq!(super::load_window_size(&path), Some(size));
+        std::fs::write(&path, r#"{"width":0,"height":40}"#).unwrap();
+        assert_eq!(super::load_window_size(&path), None);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -7,6 +7,12 @@
 pub mod acekey;
 pub mod ast;
 pub mod carapace;
+pub mod clap_spec;
+pub mod doctor;
+pub mod gitconfig;
+pub mod history;
+pub mod parse;
+pub mod source;
 
 pub mod ui;
 
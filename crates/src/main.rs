@@ -5,15 +5,21 @@
 
 use std::env;
 use std::fs;
+use std::io::Read;
 use std::path::Path;
-use std::process::{self, Command, Stdio};
-use van::ui::{Model as UiModel, initial_model, run as noninteractive_run};
+use std::process::{self, Command, ExitStatus};
+use van::parse::shell_single_quote;
+use std::sync::OnceLock;
+use van::ui::{CliFlags, Model as UiModel, Settings, initial_model};
 
 use bubbletea_rs::{
     Program, event::KeyMsg, event::WindowSizeMsg, model::Model as TeaModel, window_size,
 };
 use crossterm::event::{KeyCode, KeyModifiers};
 
+// The leading van flags of this invocation, for the interactive model bubbletea builds in `init`
+static CLI_FLAGS: OnceLock<CliFlags> = OnceLock::new();
+
 // Adapter type implementing bubbletea-rs Model trait by delegating to our UiModel
 struct TeaAdapter {
     inner: UiModel,
@@ -22,15 +28,29 @@ struct TeaAdapter {
 impl TeaModel for TeaAdapter {
     fn init() -> (Self, Option<bubbletea_rs::command::Cmd>) {
         // preload carapace --list with descriptions so interactive UI shows top-level commands immediately
-        let entries = van::carapace::list_with_desc().unwrap_or_default();
+        let settings = Settings::from_env().with_flags(CLI_FLAGS.get().copied().unwrap_or_default());
+        let entries = van::source::Source::default().list(settings.path_filter).unwrap_or_default();
         let mut adapter = TeaAdapter {
             inner: initial_model(entries),
         };
-        let (width, height) = crossterm::terminal::size().unwrap_or((80, 24));
-        adapter.inner.update(van::ui::Msg::WindowSize {
-            width: width as usize,
-            height: height as usize,
-        });
+        if settings.record.is_some() {
+            adapter.inner.recording = Some(vec![]);
+        }
+        adapter.inner.settings = settings;
+        adapter.inner.value_history = van::history::ValueHistory::load_default();
+        adapter.inner.command_usage = van::history::CommandUsage::load_default();
+        adapter.inner.arrange_top_level();
+        // the terminal's size if it answers, else the size saved last session, else 80x24
+        let restored = adapter.inner.settings.remember_window_size
+            && van::history::window_size_path().is_some_and(|p| adapter.inner.restore_window_size(&p));
+        match crossterm::terminal::size() {
+            Ok((width, height)) => adapter.inner.update(van::ui::Msg::WindowSize {
+                width: width as usize,
+                height: height as usize,
+            }),
+            Err(_) if !restored => adapter.inner.update(van::ui::Msg::WindowSize { width: 80, height: 24 }),
+            Err(_) => {}
+        }
         let cmd = window_size();
         (adapter, Some(cmd))
     }
@@ -43,25 +63,14 @@ impl TeaModel for TeaAdapter {
                 KeyCode::Enter => {
                     // Enter -> perform ExecProcess semantics
                     self.inner.update(van::ui::Msg::KeyEnter);
-                    let preview = &self.inner.exit_preview;
-                    if preview.is_empty() {
-                        return None;
+                    if self.inner.exit_preview.is_empty() {
+                        return van::ui::run::value_command_task(&mut self.inner);
                     }
-                    let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
-                    let mut cmd = Command::new(shell);
-                    cmd.arg("-c")
-                        .arg(preview)
-                        .stdin(Stdio::inherit())
-                        .stdout(Stdio::inherit())
-                        .stderr(Stdio::inherit());
+                    save_recording(&self.inner);
+                    self.inner.save_window_size();
+                    let mut cmd = van::ui::run::exec_command(&self.inner)?;
                     match cmd.status() {
-                        Ok(status) => {
-                            if let Some(code) = status.code() {
-                                process::exit(code);
-                            } else {
-                                process::exit(0);
-                            }
-                        }
+                        Ok(status) => process::exit(exit_code_for(&status)),
                         Err(e) => {
                             eprintln!("failed to execute command: {e}");
                             process::exit(1);
@@ -71,6 +80,15 @@ impl TeaModel for TeaAdapter {
                 KeyCode::Backspace => {
                     self.inner.update(van::ui::Msg::KeyBackspace);
                 }
+                KeyCode::Delete => {
+                    self.inner.update(van::ui::Msg::ClearTyped);
+                }
+                KeyCode::Tab => {
+                    self.inner.update(van::ui::Msg::KeyTab);
+                }
+                KeyCode::BackTab => {
+                    self.inner.update(van::ui::Msg::BackTab);
+                }
                 KeyCode::Esc => {
                     // Quit immediately unless we're in value-input mode
                     if !self.inner.in_value_mode {
@@ -78,6 +96,12 @@ impl TeaModel for TeaAdapter {
                     }
                     self.inner.update(van::ui::Msg::KeyEsc);
                 }
+                KeyCode::Up if km.modifiers.contains(KeyModifiers::ALT) => {
+                    self.inner.update(van::ui::Msg::MoveArgUp);
+                }
+                KeyCode::Down if km.modifiers.contains(KeyModifiers::ALT) => {
+                    self.inner.update(van::ui::Msg::MoveArgDown);
+                }
                 KeyCode::Up => {
                     self.inner.update(van::ui::Msg::KeyUp);
                 }
@@ -87,17 +111,11 @@ impl TeaModel for TeaAdapter {
                 KeyCode::Char(ch) => {
                     // Control-key handling
                     if km.modifiers.contains(KeyModifiers::CONTROL) {
-                        match ch {
-                            'n' | 'N' => {
-                                self.inner.update(van::ui::Msg::KeyDown);
-                            }
-                            'p' | 'P' => {
-                                self.inner.update(van::ui::Msg::KeyUp);
-                            }
-                            'c' | 'C' => {
-                                return Some(bubbletea_rs::quit());
-                            }
-                            _ => {}
+                        if *ch == 'c' || *ch == 'C' {
+                            return Some(bubbletea_rs::quit());
+                        }
+                        if let Some(msg) = van::ui::keymap::ctrl_msg(*ch) {
+                            self.inner.update(msg);
                         }
                     } else if *ch == ' ' {
                         self.inner.update(van::ui::Msg::KeySpace);
@@ -108,6 +126,10 @@ impl TeaModel for TeaAdapter {
                 _ => { /* ignore other keys */ }
             }
 
+            return van::ui::run::value_command_task(&mut self.inner);
+        }
+        if let Some(msg) = msg.downcast_ref::<van::ui::Msg>() {
+            self.inner.update(msg.clone());
             return None;
         }
         if let Some(ws) = msg.downcast_ref::<WindowSizeMsg>() {
@@ -115,6 +137,7 @@ impl TeaModel for TeaAdapter {
                 width: ws.width as usize,
                 height: ws.height as usize,
             });
+            self.inner.remember_window_size(ws.width as usize, ws.height as usize);
             return None;
         }
         None
@@ -126,6 +149,47 @@ impl TeaModel for TeaAdapter {
     }
 }
 
+// With VAN_RECORD set, write the session out before van exits
+fn save_recording(m: &UiModel) {
+    if let Err(e) = van::ui::session::save(m) {
+        eprintln!("{e}");
+    }
+}
+
+// exit_code_for maps a child's exit status to our own exit code. A child killed by a signal
+// has no code; report it the way shells do (128 + signal) instead of masking it as success.
+fn exit_code_for(status: &ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(sig) = status.signal() {
+            return 128 + sig;
+        }
+    }
+    1
+}
+
+// Layout report for the hidden `--measure WxH` mode: the ANSI-stripped width of every line of
+// `render_full` for an empty model at that size, then the line count.
+fn measure_report(spec: &str) -> Result<String, String> {
+    let (w, h) = spec
+        .split_once(['x', 'X'])
+        .and_then(|(w, h)| Some((w.trim().parse::<usize>().ok()?, h.trim().parse::<usize>().ok()?)))
+        .ok_or_else(|| format!("--measure expects WxH (e.g. 80x24), got {spec:?}"))?;
+    let mut m = initial_model(vec![]);
+    m.update(van::ui::Msg::WindowSize { width: w, height: h });
+    let out = van::ui::render::strip_ansi(&m.render_full());
+    let mut report = String::new();
+    for (i, line) in out.lines().enumerate() {
+        report.push_str(&format!("{:>3}: {}\n", i + 1, line.chars().count()));
+    }
+    report.push_str(&format!("lines: {}", out.lines().count()));
+    Ok(report)
+}
+
 fn print_help() {
     println!("van - interactive command completion preview tool");
     println!();
@@ -139,8 +203,59 @@ fn print_help() {
     println!(
         "  --exe <cmd>      Optional: override the executable string to embed in the hook (e.g. './target/debug/van')."
     );
+    println!("  --only <cmd,...> With --hook: register completion only for these commands instead of all of PATH.");
+    println!("  --all            List top-level commands even when they are not on PATH (this run only; given before the command).");
+    println!("  --descriptions   Print completions as candidate:description lines (used by the zsh hook).");
+    println!("  --tab-descriptions Print completions as candidate<TAB>description lines (used by the fish hook).");
+    println!("  --no-alt-screen  Draw the picker inline instead of on the alternate screen (also VAN_NO_ALT_SCREEN=1).");
+    println!("  --clap-spec <file> [<command> [args...]]  Complete from a clap-style JSON spec instead of carapace.");
+    println!("  --exec <command> [args...]  Run the resolved command instead of printing it (see VAN_EXEC_MODE).");
+    println!("  --embed <command> [args...]  Print just the resolved command on one line, for prompts and status bars (NO_COLOR honored).");
+    println!("  --stdin          Read the command line to complete from stdin (shell quoting honored).");
+    println!("  --fzf [<command> [args...]]  Print candidates as keystroke<TAB>label<TAB>description lines for fzf.");
+    println!("  --fzf-select <line> [<command> [args...]]  Apply a line picked from --fzf output and print the command line.");
+    println!("  --replay <file>  Replay a session recorded with VAN_RECORD and print the command it built.");
+    println!("  --keys [--json]  List the interactive key bindings (as JSON with --json).");
+    println!("  --schema         Print the JSON schema of the carapace export data van reads.");
+    println!("  --warm <cmd,...> Export the commands ahead of time into the cache, reporting each; exit 1 if any fail.");
+    println!("  --doctor         Check carapace, the shell, the state, cache and config directories and PATH lookups; exit 1 on failure.");
     println!("  --help           Show this help message.");
     println!();
+    println!("Environment:");
+    println!("  VAN_SHOW_COUNTS=0     Hide the shown/hidden candidate counts in the modeline.");
+    println!("  VAN_NO_PATH_FILTER=1  List top-level commands even when they are not on PATH.");
+    println!("  VAN_CARAPACE_BIN      Path of the carapace executable to use.");
+    println!("  VAN_AUTO_SELECT       Auto-select the last candidate left: always (default), flags, never.");
+    println!("  VAN_GROUP_FLAGS=1     Group flags under a header per originating (sub)command.");
+    println!("  VAN_SYNTHESIZE_HELP=1 Offer --help on commands whose carapace export lacks it.");
+    println!("  VAN_SCROLLBAR=1       Show pagination as a scrollbar beside the list instead of page text.");
+    println!("  VAN_AUTO_POSITIONAL=1 Start argument input after choosing a command that only takes arguments.");
+    println!("  VAN_STYLE_LEFT_UNIT=1 Color the shared first character (or --) of each candidate separately.");
+    println!("  VAN_SHOW_KEYSTROKES=1 Show the keys that select each candidate before it, e.g. [--m].");
+    println!("  VAN_VALUE_COUNTER=0   Don't show the length of the value being typed, e.g. [23].");
+    println!("  VAN_KEY_COLUMN=1      Show the keys that select each candidate in a column left of the numbers.");
+    println!("  VAN_PER_PAGE_NUMBERS=1 Number candidates from 1 on every page instead of continuing across pages.");
+    println!("  VAN_NUMERIC_PREFIX=#  Select by number only after typing this key; digits alone are typed like letters.");
+    println!("  VAN_DESCRIBE_COMMAND=1 Describe the finished command in the modeline, e.g. \"git > commit: Record changes\".");
+    println!("  VAN_MATCH_DESCRIPTIONS=1 When no name matches what is typed, list candidates whose description does; Enter picks the first.");
+    println!("  VAN_BREADCRUMB_DEPTH=N Show up to N levels of the subcommand path in the modeline, e.g. \"git › remote › add\".");
+    println!("  VAN_REMEMBER_WINDOW_SIZE=1 Save the terminal size on exit to start the next session at it.");
+    println!("  VAN_EXEC_MODE         How a finished command runs: shell (default, via $SHELL -c) or direct (no shell).");
+    println!("  VAN_FREQUENT_COMMANDS List the commands run most often first: off (default), first, or only (typing brings back the rest).");
+    println!("  VAN_VALUE_COMMANDS    Offer a flag's values from a command's output lines, e.g. \"git --branch=git branch --format='%(refname:short)'\" (;-separated; each gets half a second).");
+    println!("  VAN_KV_FLAGS          Flags taking key=value pairs, e.g. \"git -c,docker --label\" (default: git -c).");
+    println!("  VAN_MAX_COMMAND_LEN   Warn when the built command nears this many bytes (default: 131072).");
+    println!("  VAN_PRESERVE_ROOT_CASE=1 Keep the command name as typed when it differs only in case.");
+    println!("  VAN_VERBATIM_WORDS=<regex> Positionals to leave unquoted in the preview (default: git `HEAD@{{2}}`; empty for none). Words with spaces or other shell syntax are always quoted.");
+    println!("  VAN_FORWARD_SUBCOMMANDS=1 Complete e.g. `cargo foo` from a cargo-foo binary when carapace knows nothing more.");
+    println!("  VAN_GIT_ALIASES=1     Offer the aliases in your git config as git subcommands, expanded when chosen.");
+    println!("  VAN_ALIASES           Aliases for commands carapace can't export, e.g. g=git,k=kubectl or \"$(alias)\".");
+    println!("  VAN_REMOTE_COMMANDS=1 Complete the command after `ssh <host>` from local definitions, keeping `ssh <host>` in front.");
+    println!("  VAN_RECORD=<file>     Record the interactive session's keys to <file> on exit, for --replay.");
+    println!("  VAN_CACHE_DIR         Where --warm keeps command exports (default: $XDG_CACHE_HOME/van).");
+    println!("  VAN_STATE_DIR         Where state such as remembered flag values is kept (default: $XDG_STATE_HOME/van).");
+    println!("  VAN_CONFIG_DIR        Where van's configuration belongs, checked by --doctor (default: $XDG_CONFIG_HOME/van).");
+    println!();
     println!("Description:");
     println!(
         "  When the hook is installed in your shell, your shell will invoke \"<exe> <command line>\" to produce completion candidates for the currently typed command line. For example, if you type 'jj commit' and press TAB, the shell will call '<exe> jj commit' to obtain completion items."
@@ -151,15 +266,6 @@ fn print_help() {
     println!("  source ~/.van_hook.sh");
 }
 
-// shell_single_quote safely single-quotes s for embedding in POSIX shells.
-fn shell_single_quote(s: &str) -> String {
-    if s.is_empty() {
-        return "''".to_string();
-    }
-    let escaped = s.replace('\'', "'\\''");
-    format!("'{escaped}'")
-}
-
 // parse_run_from_parts tries to find a '<exe> run' invocation in parts and reconstruct the run command string
 fn parse_run_from_parts(parts: &[String]) -> Option<String> {
     // look for a pair where the second token is "run" and then collect valid run args after it
@@ -293,7 +399,8 @@ fn detect_exec_from_parent() -> String {
 }
 
 // hook_script returns a shell-specific hook that will invoke exec_cmd to obtain completion items.
-fn hook_script(shell: &str, exec_cmd: &str) -> String {
+// `only` restricts the bash/zsh/fish registration to those commands instead of everything on PATH.
+fn hook_script(shell: &str, exec_cmd: &str, only: &[String]) -> String {
     let s = shell.to_lowercase();
     // single-quoted exec_cmd for safe embedding
     let esc = shell_single_quote(exec_cmd);
@@ -312,37 +419,50 @@ _van_completion() {
   done
   local IFS=$'\n'
   local out
-  out=$(eval "$EXEC_CMD \"${args[@]}\"") || return
+  # COMP_LINE/COMP_POINT let van complete the exact word under the cursor
+  out=$(COMP_LINE="$COMP_LINE" COMP_POINT="$COMP_POINT" eval "$EXEC_CMD \"${args[@]}\"") || return
   COMPREPLY=($(compgen -W "$out" -- "$cur"))
 }
-# Register _van_completion for all commands found in PATH (may be slow on very large PATHs)
-for cmd in $(compgen -c); do
-  complete -F _van_completion -o default "$cmd" 2>/dev/null || true
-done
-"#;
-            tpl.replace("{{EXEC}}", &esc)
+{{REGISTER}}"#;
+            tpl.replace("{{EXEC}}", &esc).replace("{{REGISTER}}", &bash_register(only))
         }
         "zsh" => {
             let tpl = r#"# van zsh hook
 EXEC_CMD={{EXEC}}
 _van_completion() {
   # words array contains all words; remove the command itself
-  local -a reply
-  reply=("${(@f)$(eval "$EXEC_CMD ${words[1,-1]}")}")
-  if [[ -n ${reply} ]]; then
-    compadd -- "${reply[@]}"
-  fi
+  local -a reply cands descs
+  reply=("${(@f)$(eval "$EXEC_CMD --descriptions ${words[1,-1]}")}")
+  [[ -n ${reply} ]] || return
+  # each line is `candidate:description`, colons within the candidate escaped as `\:`
+  local entry rest word
+  for entry in "${reply[@]}"; do
+    rest=${entry//\\:/$'\0'}
+    word=${${rest%%:*}//$'\0'/:}
+    cands+=("$word")
+    if [[ $rest == *:* ]]; then
+      descs+=("$word -- ${${rest#*:}//$'\0'/:}")
+    else
+      descs+=("$word")
+    fi
+  done
+  compadd -l -d descs -- "${cands[@]}"
 }
-# Register for all commands available in this shell
-for cmd in ${(k)commands}; do
-  compdef _van_completion $cmd 2>/dev/null || true
-done
-"#;
-            tpl.replace("{{EXEC}}", &esc)
+{{REGISTER}}"#;
+            let register = if only.is_empty() {
+                "# Register for all commands available in this shell\nfor cmd in ${(k)commands}; do\n  compdef _van_completion $cmd 2>/dev/null || true\ndone\n".to_string()
+            } else {
+                format!(
+                    "# Register only for the commands given to --only\nfor cmd in {}; do\n  compdef _van_completion $cmd 2>/dev/null || true\ndone\n",
+                    quoted_words(only)
+                )
+            };
+            tpl.replace("{{EXEC}}", &esc).replace("{{REGISTER}}", &register)
         }
         "fish" => {
             let tpl = r#"# van fish hook
-set -l VAN_EXEC {{EXEC}}
+# global: a local set here would be gone by the time fish calls the function
+set -g VAN_EXEC {{EXEC}}
 function __van_completion
   # get full commandline
   set -l cmdline (commandline -cp)
@@ -350,19 +470,21 @@ function __van_completion
   set -l tokens (string split ' ' -- $cmdline)
   # drop the leading command name
   set -e tokens[1]
-  # call $VAN_EXEC with remaining tokens and print each candidate on its own line
-  for item in (eval "$VAN_EXEC $tokens")
+  # call $VAN_EXEC with remaining tokens and print each `candidate<TAB>description` line
+  for item in (eval "$VAN_EXEC --tab-descriptions $tokens")
     printf "%s\n" "$item"
   end
 end
-# Register completion for every executable in $PATH (may be slow)
-for p in (string split : $PATH)
-  for cmd in (ls $p 2>/dev/null)
-    complete -c $cmd -f -a '(__van_completion)'
-  end
-end
-"#;
-            tpl.replace("{{EXEC}}", &esc)
+{{REGISTER}}"#;
+            let register = if only.is_empty() {
+                "# Register completion for every executable in $PATH (may be slow)\nfor p in (string split : $PATH)\n  for cmd in (ls $p 2>/dev/null)\n    complete -c $cmd -f -a '(__van_completion)'\n  end\nend\n".to_string()
+            } else {
+                format!(
+                    "# Register completion only for the commands given to --only\nfor cmd in {}\n  complete -c $cmd -f -a '(__van_completion)'\nend\n",
+                    quoted_words(only)
+                )
+            };
+            tpl.replace("{{EXEC}}", &esc).replace("{{REGISTER}}", &register)
         }
         "nushell" | "nu" => {
             let tpl = r#"# van nushell hook
@@ -388,24 +510,105 @@ _van_completion() {
   done
   local IFS=$'\n'
   local out
-  out=$(eval "$EXEC_CMD \"${args[@]}\"") || return
+  # COMP_LINE/COMP_POINT let van complete the exact word under the cursor
+  out=$(COMP_LINE="$COMP_LINE" COMP_POINT="$COMP_POINT" eval "$EXEC_CMD \"${args[@]}\"") || return
   COMPREPLY=($(compgen -W "$out" -- "$cur"))
 }
-# Register _van_completion for all commands found in PATH (may be slow on very large PATHs)
-for cmd in $(compgen -c); do
-  complete -F _van_completion -o default "$cmd" 2>/dev/null || true
-done
-"#;
-            tpl.replace("{{EXEC}}", &esc)
+{{REGISTER}}"#;
+            tpl.replace("{{EXEC}}", &esc).replace("{{REGISTER}}", &bash_register(only))
         }
     }
 }
 
+// Space-separated single-quoted words, valid in bash, zsh and fish alike
+fn quoted_words(words: &[String]) -> String {
+    words.iter().map(|w| shell_single_quote(w)).collect::<Vec<_>>().join(" ")
+}
+
+// The bash hook's `complete` registration: every command on PATH, or just `only`
+fn bash_register(only: &[String]) -> String {
+    let (comment, list) = if only.is_empty() {
+        (
+            "# Register _van_completion for all commands found in PATH (may be slow on very large PATHs)",
+            "$(compgen -c)".to_string(),
+        )
+    } else {
+        ("# Register _van_completion only for the commands given to --only", quoted_words(only))
+    };
+    format!("{comment}\nfor cmd in {list}; do\n  complete -F _van_completion -o default \"$cmd\" 2>/dev/null || true\ndone\n")
+}
+
+// What `--hook [shell]`, `--exe <cmd>` and `--only <cmd,...>` asked for, given in any order
+#[derive(Debug, Default, PartialEq)]
+struct HookArgs {
+    hook: bool,
+    // None: detect it from $SHELL
+    shell: Option<String>,
+    // None: detect it from the parent process
+    exe: Option<String>,
+    only: Vec<String>,
+}
+
+// The shell is the word right after `--hook` unless that is another option; `--exe` and
+// `--only` take the next word (or `=value`) unless that is one of these options, and with
+// `--hook` given a missing value is an error rather than something to guess around. Other
+// words are left for the caller.
+fn parse_hook_args(args: &[String]) -> Result<HookArgs, String> {
+    let mut parsed = HookArgs::default();
+    let mut missing = None;
+    let mut i = 0usize;
+    while i < args.len() {
+        let (opt, inline) = match args[i].split_once('=') {
+            Some((opt, val)) if opt == "--exe" || opt == "--only" => (opt, Some(val.to_string())),
+            _ => (args[i].as_str(), None),
+        };
+        match opt {
+            "--hook" => {
+                parsed.hook = true;
+                if let Some(shell) = args.get(i + 1).filter(|a| !a.starts_with('-')) {
+                    parsed.shell = Some(shell.clone());
+                    i += 1;
+                }
+            }
+            "--exe" | "--only" => {
+                let next = args.get(i + 1).filter(|a| !matches!(a.as_str(), "--hook" | "--exe" | "--only"));
+                let value = match (inline, next) {
+                    (Some(v), _) => v,
+                    (None, Some(v)) => {
+                        i += 1;
+                        v.clone()
+                    }
+                    (None, None) => {
+                        missing = Some(format!("{opt} expects a value"));
+                        i += 1;
+                        continue;
+                    }
+                };
+                if opt == "--exe" {
+                    parsed.exe = Some(value).filter(|v| !v.trim().is_empty());
+                } else {
+                    parsed.only.extend(value.split(',').map(str::trim).filter(|c| !c.is_empty()).map(String::from));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    match missing {
+        Some(e) if parsed.hook => Err(e),
+        _ => Ok(parsed),
+    }
+}
+
 fn detect_shell_from_env() -> String {
-    env::var("SHELL")
-        .ok()
+    shell_name(env::var("SHELL").ok().as_deref())
+}
+
+// The shell a $SHELL value names (its file name), bash when unset or empty
+fn shell_name(shell: Option<&str>) -> String {
+    shell
         .and_then(|p| {
-            Path::new(&p)
+            Path::new(p)
                 .file_name()
                 .and_then(|s| s.to_str().map(|s| s.to_string()))
         })
@@ -413,6 +616,20 @@ fn detect_shell_from_env() -> String {
         .unwrap_or_else(|| "bash".to_string())
 }
 
+// Shells `hook_script` has a template for; anything else gets the bash hook
+const SUPPORTED_SHELLS: &[&str] = &["bash", "zsh", "fish", "nushell", "nu"];
+
+// Stderr warning for a shell detected from $SHELL that has no hook of its own
+fn unsupported_shell_warning(shell: &str) -> Option<String> {
+    if SUPPORTED_SHELLS.contains(&shell.to_lowercase().as_str()) {
+        return None;
+    }
+    Some(format!(
+        "van: no hook for shell {shell:?} (from $SHELL); supported: {}. Emitting the bash hook; pass --hook <shell> to choose.",
+        SUPPORTED_SHELLS.join(", ")
+    ))
+}
+
 fn get_ps_command(ppid: u32) -> Option<String> {
     let out = Command::new("ps")
         .arg("-p")
@@ -431,44 +648,161 @@ fn get_ps_command(ppid: u32) -> Option<String> {
 
 #[tokio::main]
 async fn main() {
-    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // --all and the like come before the command, so they can't be flags of the command being
+    // completed
+    let flags = CliFlags::take_leading(&mut args);
+    let _ = CLI_FLAGS.set(flags);
+    let settings = || Settings::from_env().with_flags(flags);
     // simple flag handling for --help and --hook
     if !args.is_empty() {
         if args[0] == "--help" || args[0] == "-h" {
             print_help();
             return;
         }
-        // support: --hook [shell] and optional --exe <cmd> (can appear before or after)
-        let mut hook_idx: isize = -1;
-        let mut exe_val = String::new();
-        let mut i = 0usize;
-        while i < args.len() {
-            if args[i] == "--hook" {
-                hook_idx = i as isize;
-                // if next arg exists and doesn't start with '-', treat as shell token
-                if i + 1 < args.len() && !args[i + 1].starts_with('-') {
-                    i += 1;
+        if args[0] == "--schema" {
+            println!("{}", van::carapace::export_schema());
+            return;
+        }
+        if args[0] == "--keys" {
+            if args.get(1).map(String::as_str) == Some("--json") {
+                println!("{}", van::ui::keymap::keymap_json());
+            } else {
+                print!("{}", van::ui::keymap::keymap_text());
+            }
+            return;
+        }
+        // --warm <cmd1,cmd2,...>: save the commands' exports to the cache ahead of first use
+        if args[0] == "--warm" {
+            let names: Vec<&str> = args[1..].iter().flat_map(|a| a.split(',')).filter(|n| !n.is_empty()).collect();
+            if names.is_empty() {
+                eprintln!("--warm expects a comma-separated list of commands");
+                process::exit(2);
+            }
+            let mut failed = false;
+            for name in names {
+                match van::carapace::warm(name) {
+                    Ok(()) => println!("ok    {name}"),
+                    Err(e) => {
+                        failed = true;
+                        println!("fail  {name}: {e}");
+                    }
+                }
+            }
+            if failed {
+                process::exit(1);
+            }
+            return;
+        }
+        if args[0] == "--doctor" {
+            let checks = van::doctor::run_checks(&detect_shell_from_env(), SUPPORTED_SHELLS);
+            print!("{}", van::doctor::render(&checks));
+            if checks.iter().any(|c| !c.ok) {
+                process::exit(1);
+            }
+            return;
+        }
+        // --clap-spec <file> [<command> [args...]]: complete from a clap-style JSON spec instead
+        // of carapace, e.g. for a tool still being written
+        if args[0] == "--clap-spec" {
+            let result = match args.get(1) {
+                Some(path) => van::clap_spec::load(Path::new(path)).and_then(|def| {
+                    let source = van::source::Source::new(van::source::StaticSource::new(vec![def]));
+                    let rest = args[2..].to_vec();
+                    tokio::task::block_in_place(|| van::ui::run_with_source(source, settings(), rest))
+                }),
+                None => Err("--clap-spec expects a spec file".to_string()),
+            };
+            match result {
+                Ok(out) if out.is_empty() => {}
+                Ok(out) => println!("{out}"),
+                Err(e) => {
+                    eprintln!("{e}");
+                    process::exit(2);
                 }
-                i += 1;
-                continue;
             }
-            if args[i] == "--exe" && i + 1 < args.len() {
-                exe_val = args[i + 1].to_owned();
-                i += 2;
-                continue;
+            return;
+        }
+        if args[0] == "--exec" {
+            match van::ui::run::exec(settings(), &args[1..]) {
+                Ok(status) => process::exit(exit_code_for(&status)),
+                Err(e) => {
+                    eprintln!("{e}");
+                    process::exit(2);
+                }
+            }
+        }
+        if args[0] == "--embed" {
+            match van::ui::run::embed(settings(), &args[1..]) {
+                Ok(line) => println!("{line}"),
+                Err(e) => {
+                    eprintln!("{e}");
+                    process::exit(2);
+                }
             }
-            i += 1;
-        }
-        if hook_idx != -1 {
-            // determine shell param if provided
-            let shell = if (hook_idx as usize) + 1 < args.len()
-                && !args[(hook_idx as usize) + 1].starts_with('-')
-            {
-                args[(hook_idx as usize) + 1].to_owned()
+            return;
+        }
+        if args[0] == "--replay" {
+            let result = match args.get(1) {
+                Some(path) => van::ui::run::replay(settings(), Path::new(path)),
+                None => Err("--replay expects a session file (recorded with VAN_RECORD)".to_string()),
+            };
+            match result {
+                Ok(out) if out.is_empty() => {}
+                Ok(out) => println!("{out}"),
+                Err(e) => {
+                    eprintln!("{e}");
+                    process::exit(2);
+                }
+            }
+            return;
+        }
+        if args[0] == "--fzf" || args[0] == "--fzf-select" {
+            let result = if args[0] == "--fzf" {
+                van::ui::run::fzf(settings(), &args[1..], None)
             } else {
-                detect_shell_from_env()
+                match args.get(1) {
+                    Some(line) => van::ui::run::fzf(settings(), &args[2..], Some(line)).map(|s| s + "\n"),
+                    None => Err("--fzf-select expects a line printed by --fzf".to_string()),
+                }
             };
-            let mut exe_cmd = exe_val;
+            match result {
+                Ok(out) => print!("{out}"),
+                Err(e) => {
+                    eprintln!("{e}");
+                    process::exit(2);
+                }
+            }
+            return;
+        }
+        // undocumented: report layout dimensions for bug reports
+        if args[0] == "--measure" {
+            match measure_report(args.get(1).map(String::as_str).unwrap_or("")) {
+                Ok(report) => println!("{report}"),
+                Err(e) => {
+                    eprintln!("{e}");
+                    process::exit(2);
+                }
+            }
+            return;
+        }
+        // support: --hook [shell] and optional --exe <cmd> (can appear before or after)
+        let hook = match parse_hook_args(&args) {
+            Ok(hook) => hook,
+            Err(e) => {
+                eprintln!("{e}");
+                process::exit(2);
+            }
+        };
+        if hook.hook {
+            let shell = hook.shell.unwrap_or_else(|| {
+                let detected = detect_shell_from_env();
+                if let Some(warning) = unsupported_shell_warning(&detected) {
+                    eprintln!("{warning}");
+                }
+                detected
+            });
+            let mut exe_cmd = hook.exe.unwrap_or_default();
             if exe_cmd.is_empty() {
                 exe_cmd = detect_exec_from_parent();
             }
@@ -479,14 +813,31 @@ async fn main() {
                     .unwrap_or("")
                     .to_string();
             }
-            print!("{}", hook_script(&shell, &exe_cmd));
+            print!("{}", hook_script(&shell, &exe_cmd, &hook.only));
             return;
         }
     }
 
+    // --stdin: the command line to complete is read from standard input (quotes honored)
+    let args = if args.first().map(String::as_str) == Some("--stdin") {
+        let mut line = String::new();
+        if let Err(e) = std::io::stdin().read_to_string(&mut line) {
+            eprintln!("failed to read stdin: {e}");
+            process::exit(2);
+        }
+        let tokens = van::parse::tokenize_command_line(&line);
+        if tokens.is_empty() {
+            eprintln!("--stdin: no command line given");
+            process::exit(2);
+        }
+        tokens
+    } else {
+        args
+    };
+
     // If args provided, use non-interactive parsing similar to tooling (<cmd> args), else run interactive TUI
     if !args.is_empty() {
-        match noninteractive_run(args) {
+        match van::ui::run_with_source(van::source::Source::default(), settings(), args) {
             Ok(out) => {
                 if !out.is_empty() {
                     println!("{out}");
@@ -501,7 +852,8 @@ async fn main() {
     }
 
     // Run interactive program
-    let builder = Program::<TeaAdapter>::builder();
+    let alt_screen = settings().alt_screen;
+    let builder = Program::<TeaAdapter>::builder().alt_screen(alt_screen);
     let program = match builder.build() {
         Ok(p) => p,
         Err(e) => {
@@ -509,9 +861,16 @@ async fn main() {
             process::exit(2);
         }
     };
-    match program.run().await {
-        Ok(_final_model) => {
+    let result = program.run().await;
+    // drawn inline, the cursor is left where the picker hid it
+    if !alt_screen {
+        let _ = crossterm::execute!(std::io::stderr(), crossterm::cursor::Show);
+    }
+    match result {
+        Ok(final_model) => {
             // Interactive run does not print preview; simply exit successfully
+            save_recording(&final_model.inner);
+            final_model.inner.save_window_size();
             process::exit(0);
         }
         Err(e) => {
@@ -520,3 +879,137 @@ async fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn exit_code_for_maps_signals_to_128_plus_signal() {
+        use std::os::unix::process::ExitStatusExt;
+        // raw wait statuses: low 7 bits carry the terminating signal, the next byte the exit code
+        assert_eq!(exit_code_for(&ExitStatus::from_raw(9)), 137); // SIGKILL
+        assert_eq!(exit_code_for(&ExitStatus::from_raw(2)), 130); // SIGINT
+        assert_eq!(exit_code_for(&ExitStatus::from_raw(15)), 143); // SIGTERM
+        assert_eq!(exit_code_for(&ExitStatus::from_raw(0)), 0);
+        assert_eq!(exit_code_for(&ExitStatus::from_raw(3 << 8)), 3);
+    }
+
+    #[test]
+    fn measure_reports_exact_dimensions_for_an_empty_model() {
+        let report = measure_report("80x24").unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.last(), Some(&"lines: 24"));
+        assert_eq!(lines.len(), 25);
+        for (i, l) in lines[..24].iter().enumerate() {
+            assert_eq!(*l, format!("{:>3}: 80", i + 1));
+        }
+        assert!(measure_report("80by24").is_err());
+    }
+
+    #[test]
+    fn unsupported_detected_shell_warns_and_falls_back_to_bash() {
+        // as detected from SHELL=/usr/local/bin/xonsh, without changing the process environment
+        let shell = shell_name(Some("/usr/local/bin/xonsh"));
+        assert_eq!(shell, "xonsh");
+        assert_eq!(shell_name(None), "bash");
+        let warning = unsupported_shell_warning(&shell).unwrap();
+        assert!(warning.contains("\"xonsh\""), "{warning}");
+        assert!(warning.contains("bash, zsh, fish, nushell, nu"), "{warning}");
+        let script = hook_script(&shell, "van", &[]);
+        assert!(script.starts_with("# van (default=bash) hook"), "{script}");
+        assert!(script.contains("complete -F _van_completion"));
+
+        for ok in ["bash", "zsh", "fish", "nu", "Zsh"] {
+            assert_eq!(unsupported_shell_warning(ok), None);
+        }
+    }
+
+    #[test]
+    fn zsh_hook_shows_descriptions() {
+        let zsh = hook_script("zsh", "van", &[]);
+        assert!(zsh.contains("$EXEC_CMD --descriptions ${words[1,-1]}"));
+        assert!(zsh.contains("compadd -l -d descs -- \"${cands[@]}\""));
+    }
+
+    #[test]
+    fn fish_hook_asks_for_tab_separated_descriptions() {
+        let fish = hook_script("fish", "van", &[]);
+        assert!(fish.contains("(eval \"$VAN_EXEC --tab-descriptions $tokens\")"));
+    }
+
+    #[test]
+    fn hooks_run_a_multi_word_exe_with_the_completion_args_appended() {
+        let exe = "uv run van";
+        let bash = hook_script("bash", exe, &[]);
+        assert!(bash.contains("EXEC_CMD='uv run van'\n"));
+        assert!(bash.contains(r#"eval "$EXEC_CMD \"${args[@]}\"""#));
+        let zsh = hook_script("zsh", exe, &[]);
+        assert!(zsh.contains("EXEC_CMD='uv run van'\n"));
+        assert!(zsh.contains(r#"eval "$EXEC_CMD --descriptions ${words[1,-1]}""#));
+        let fish = hook_script("fish", exe, &[]);
+        assert!(fish.contains("set -g VAN_EXEC 'uv run van'\n"));
+        assert!(fish.contains(r#"eval "$VAN_EXEC --tab-descriptions $tokens""#));
+
+        // when bash is around, complete through a `uv` that reports how it was called
+        let script = format!(
+            "{bash}\nuv() {{ printf '[%s]' \"$@\" \"$COMP_LINE\" >&2; }}\nCOMP_WORDS=(git comm); COMP_CWORD=1\nCOMP_LINE='git comm'; COMP_POINT=8\n_van_completion"
+        );
+        let Ok(out) = Command::new("bash").arg("-c").arg(script).output() else {
+            return;
+        };
+        // the wrapper gets its own args, then the words after the command; van reads the whole
+        // line from COMP_LINE
+        assert_eq!(String::from_utf8_lossy(&out.stderr), "[run][van][comm][git comm]");
+    }
+
+    #[test]
+    fn hook_args_resolve_the_same_in_any_order() {
+        let parse = |line: &str| parse_hook_args(&line.split(' ').map(String::from).collect::<Vec<_>>());
+        let hook = |shell: Option<&str>, exe: Option<&str>| HookArgs {
+            hook: true,
+            shell: shell.map(String::from),
+            exe: exe.map(String::from),
+            only: vec![],
+        };
+        for line in ["--hook zsh --exe ./van", "--exe ./van --hook zsh", "--exe=./van --hook zsh"] {
+            assert_eq!(parse(line), Ok(hook(Some("zsh"), Some("./van"))), "{line}");
+        }
+        for line in ["--exe ./van --hook", "--hook --exe ./van"] {
+            assert_eq!(parse(line), Ok(hook(None, Some("./van"))), "{line}");
+        }
+        assert_eq!(parse("--hook"), Ok(hook(None, None)));
+        assert_eq!(parse("--hook fish --only git,jj").unwrap().only, vec!["git", "jj"]);
+
+        // a missing value doesn't swallow the option after it
+        assert_eq!(parse("--exe --hook bash"), Err("--exe expects a value".to_string()));
+        assert_eq!(parse("--hook bash --exe"), Err("--exe expects a value".to_string()));
+        // without --hook nothing here is an error (e.g. a command line being completed)
+        assert_eq!(parse("git log --only"), Ok(HookArgs::default()));
+    }
+
+    #[test]
+    fn hook_only_restricts_registration_to_listed_commands() {
+        let only = vec!["git".to_string(), "jj".to_string()];
+        let bash = hook_script("bash", "van", &only);
+        assert!(bash.contains("for cmd in 'git' 'jj'; do"));
+        assert!(!bash.contains("compgen -c"));
+        assert!(hook_script("bash", "van", &[]).contains("for cmd in $(compgen -c); do"));
+        assert!(hook_script("zsh", "van", &only).contains("for cmd in 'git' 'jj'; do"));
+        assert!(hook_script("fish", "van", &only).contains("for cmd in 'git' 'jj'\n"));
+
+        // when bash is around, check what the hook actually registers
+        let Ok(out) = Command::new("bash")
+            .arg("-c")
+            .arg(format!("{bash}\ncomplete -p"))
+            .output()
+        else {
+            return;
+        };
+        let registered = String::from_utf8_lossy(&out.stdout);
+        let mut names: Vec<&str> = registered.lines().filter_map(|l| l.split_whitespace().last()).collect();
+        names.sort();
+        assert_eq!(names, vec!["git", "jj"]);
+    }
+}
@@ -0,0 +1,318 @@
+// Command-line parsing helpers shared by the non-interactive entry points.
+
+// Split a shell-like command line into argv, POSIX-style: whitespace separates words,
+// '...' is literal, "..." allows \" \\ \$ \` escapes, and a backslash outside quotes escapes
+// the next character. Quotes join with adjacent text (`--msg="a b"` is one word) and an
+// unterminated quote runs to the end of the input rather than failing.
+pub fn tokenize_command_line(line: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    // a word is in progress even when empty, e.g. after ''
+    let mut in_word = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                for q in chars.by_ref() {
+                    if q == '\'' {
+                        break;
+                    }
+                    cur.push(q);
+                }
+            }
+            '"' => {
+                in_word = true;
+                while let Some(q) = chars.next() {
+                    match q {
+                        '"' => break,
+                        '\\' => match chars.peek() {
+                            Some(&n) if matches!(n, '"' | '\\' | '$' | '`') => {
+                                cur.push(n);
+                                chars.next();
+                            }
+                            Some('\n') => {
+                                chars.next();
+                            }
+                            _ => cur.push('\\'),
+                        },
+                        _ => cur.push(q),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    // line continuation
+                    Some('\n') => {}
+                    Some(n) => cur.push(n),
+                    None => cur.push('\\'),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    out.push(std::mem::take(&mut cur));
+                    in_word = false;
+                }
+            }
+            _ => {
+                in_word = true;
+                cur.push(c);
+            }
+        }
+    }
+    if in_word {
+        out.push(cur);
+    }
+    out
+}
+
+// Split a completion request into the complete words before the cursor and the (possibly
+// empty) partial word under it, as bash reports them in COMP_LINE / COMP_POINT. `point` is a
+// character offset; text after the cursor is ignored, so mid-word completion completes the
+// part of the word left of the cursor.
+pub fn split_at_cursor(line: &str, point: Option<usize>) -> (Vec<String>, String) {
+    let head: String = match point {
+        Some(p) => line.chars().take(p).collect(),
+        None => line.to_string(),
+    };
+    let mut words = tokenize_command_line(&head);
+    // a sentinel appended at the cursor either extends the last word or starts a new one
+    let probe = tokenize_command_line(&format!("{head}\u{1}"));
+    if probe.len() > words.len() {
+        return (words, String::new());
+    }
+    let partial = words.pop().unwrap_or_default();
+    (words, partial)
+}
+
+// The quote still open at the end of `line` (`git add "my fi`), as `tokenize_command_line`
+// reads it: a backslash escapes the next character outside single quotes.
+pub fn open_quote_at_end(line: &str) -> Option<char> {
+    let mut open: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (open, c) {
+            (None, '\'') | (None, '"') => open = Some(c),
+            (None, '\\') | (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => open = None,
+            _ => {}
+        }
+    }
+    open
+}
+
+// shell_single_quote safely single-quotes s for embedding in POSIX shells.
+pub fn shell_single_quote(s: &str) -> String {
+    if s.is_empty() {
+        return "''".to_string();
+    }
+    let escaped = s.replace('\'', "'\\''");
+    format!("'{escaped}'")
+}
+
+// Characters a POSIX shell would split on or interpret in an unquoted word
+fn is_shell_special(c: char) -> bool {
+    c.is_whitespace() || "|&;<>()$`\\\"'*?[]#~!{}".contains(c)
+}
+
+// A word already quoted for a remote shell (`shell_word`), quoted again for the local shell
+// that passes the command line on as a single string, as ssh does. Plain words stay bare;
+// `'a b'` becomes `"'a b'"`.
+pub fn quote_remote_word(remote: &str) -> String {
+    if shell_word(remote) == remote && !remote.contains('$') {
+        return remote.to_string();
+    }
+    if !remote.contains(['"', '\\', '`', '$', '!']) {
+        return format!("\"{remote}\"");
+    }
+    shell_single_quote(remote)
+}
+
+// Whether `s` may go into a command line bare for the sake of readability: besides `@{...}`
+// (as in `HEAD@{2}`) nothing in it is special to the shell, so it stays one literal word
+pub fn is_bare_word(s: &str) -> bool {
+    !s.is_empty() && !s.chars().any(|c| is_shell_special(c) && c != '{' && c != '}')
+}
+
+// `s` as a single shell word: bare when nothing in it is special, single-quoted otherwise
+pub fn shell_word(s: &str) -> String {
+    if s.is_empty() {
+        return "''".to_string();
+    }
+    if !s.contains(is_shell_special) {
+        return s.to_string();
+    }
+    shell_single_quote(s)
+}
+
+// Like `shell_word`, but `$NAME` / `${NAME}` references to the `live` variables (those env var
+// completion inserted) stay live for the shell: the word is double-quoted ("$HOME/my dir") when
+// every `$` in it starts such a reference and nothing else is special inside double quotes
+pub fn shell_word_live(s: &str, live: &[String]) -> String {
+    match strip_var_refs(s, live) {
+        Some(rest) if s.contains('$') && !rest.contains(['"', '\\', '`', '!']) => format!("\"{s}\""),
+        _ => shell_word(s),
+    }
+}
+
+// `s` without its `$NAME` / `${NAME}` references, or None when a `$` starts anything else or
+// names a variable outside `live`
+fn strip_var_refs(s: &str, live: &[String]) -> Option<String> {
+    let is_name = |n: &str| {
+        n.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && n.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    let mut rest = String::new();
+    let mut tail = s;
+    while let Some(i) = tail.find('$') {
+        rest.push_str(&tail[..i]);
+        let after = &tail[i + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => {
+                let end = braced.find('}')?;
+                (&braced[..end], end + 2)
+            }
+            None => {
+                let end = after.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        if !is_name(name) || !live.iter().any(|l| l == name) {
+            return None;
+        }
+        tail = &after[len..];
+    }
+    rest.push_str(tail);
+    Some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tokenize_command_line as tok;
+
+    fn v(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_on_runs_of_whitespace() {
+        assert_eq!(tok("  git   commit\t-a \n"), v(&["git", "commit", "-a"]));
+        assert!(tok("   ").is_empty());
+        assert!(tok("").is_empty());
+    }
+
+    #[test]
+    fn quoted_values_with_spaces_stay_together() {
+        assert_eq!(tok("git commit -m 'fix the thing'"), v(&["git", "commit", "-m", "fix the thing"]));
+        assert_eq!(tok(r#"echo "hello   world""#), v(&["echo", "hello   world"]));
+    }
+
+    #[test]
+    fn mixed_and_adjacent_quotes_join_into_one_word() {
+        assert_eq!(tok(r#"echo 'a "b"'"c 'd'" e"#), v(&["echo", r#"a "b"c 'd'"#, "e"]));
+        assert_eq!(tok(r#"--msg="a b""#), v(&["--msg=a b"]));
+        assert_eq!(tok("x'' ''"), v(&["x", ""]));
+    }
+
+    #[test]
+    fn escapes_inside_and_outside_quotes() {
+        assert_eq!(tok(r#"say \"hi\""#), v(&["say", r#""hi""#]));
+        assert_eq!(tok(r#""say \"hi\" \n""#), v(&[r#"say "hi" \n"#]));
+        assert_eq!(tok(r"'it\'s"), v(&[r"it\s"]));
+        assert_eq!(tok(r"cat my\ file.txt"), v(&["cat", "my file.txt"]));
+        assert_eq!(tok("a\\\nb"), v(&["ab"]));
+    }
+
+    #[test]
+    fn long_flag_with_equals_value_is_one_token() {
+        assert_eq!(tok("ls --color=auto -la"), v(&["ls", "--color=auto", "-la"]));
+        assert_eq!(tok("kubectl --namespace=kube-system get"), v(&["kubectl", "--namespace=kube-system", "get"]));
+    }
+
+    #[test]
+    fn unterminated_quote_runs_to_end() {
+        assert_eq!(tok("echo 'abc def"), v(&["echo", "abc def"]));
+        assert_eq!(tok(r#"echo "abc"#), v(&["echo", "abc"]));
+    }
+
+    #[test]
+    fn shell_word_quotes_only_what_needs_it_and_round_trips() {
+        use super::shell_word;
+        assert_eq!(shell_word("commit"), "commit");
+        assert_eq!(shell_word("--color=auto"), "--color=auto");
+        assert_eq!(shell_word("hello world"), "'hello world'");
+        assert_eq!(shell_word("it's"), r"'it'\''s'");
+        assert_eq!(shell_word("a|b"), "'a|b'");
+        assert_eq!(shell_word(""), "''");
+        // a `$` in a typed value is literal
+        assert_eq!(shell_word("pa$word"), "'pa$word'");
+        assert_eq!(shell_word("$HOME$(id)"), "'$HOME$(id)'");
+        for s in ["hello world", "it's", "a|b", "", "x;y \"z\"", "pa$word"] {
+            assert_eq!(tok(&shell_word(s)), v(&[s]), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn shell_word_live_keeps_only_completed_references_live() {
+        use super::shell_word_live;
+        let live = vec!["HOME".to_string()];
+        assert_eq!(shell_word_live("$HOME/bin", &live), "\"$HOME/bin\"");
+        assert_eq!(shell_word_live("${HOME}/my dir", &live), "\"${HOME}/my dir\"");
+        assert_eq!(shell_word_live("plain", &live), "plain");
+        // other variables, command substitution and double-quote specials stay literal
+        assert_eq!(shell_word_live("pa$word", &live), "'pa$word'");
+        assert_eq!(shell_word_live("$HOME$(id)", &live), "'$HOME$(id)'");
+        assert_eq!(shell_word_live("$HOME/`id`", &live), "'$HOME/`id`'");
+        assert_eq!(shell_word_live("$HOME/bin", &[]), "'$HOME/bin'");
+    }
+
+    #[test]
+    fn remote_words_survive_two_shells() {
+        use super::{quote_remote_word, shell_word};
+        let remote = |s: &str| quote_remote_word(&shell_word(s));
+        assert_eq!(remote("commit"), "commit");
+        assert_eq!(remote("a b"), "\"'a b'\"");
+        assert_eq!(quote_remote_word("HEAD@{2}"), "\"HEAD@{2}\"");
+        for s in ["a b", "it's", "$HOME/x", "$HOME/my dir", "x;y \"z\"", ""] {
+            let local = tok(&remote(s));
+            assert_eq!(local.len(), 1, "{s:?}");
+            assert_eq!(tok(&local[0]), v(&[s]), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn split_at_cursor_separates_partial_word() {
+        assert_eq!(super::split_at_cursor("git comm", None), (v(&["git"]), "comm".to_string()));
+        assert_eq!(super::split_at_cursor("git commit ", None), (v(&["git", "commit"]), String::new()));
+        assert_eq!(super::split_at_cursor("", None), (v(&[]), String::new()));
+    }
+
+    #[test]
+    fn split_at_cursor_honors_point_mid_line_and_mid_word() {
+        // cursor right after "com" in "commit"; the rest of the line is ignored
+        assert_eq!(super::split_at_cursor("git commit -m x", Some(7)), (v(&["git"]), "com".to_string()));
+        // cursor on the space after "git"
+        assert_eq!(super::split_at_cursor("git  commit", Some(4)), (v(&["git"]), String::new()));
+        // unterminated quote under the cursor is still the partial word
+        assert_eq!(super::split_at_cursor("git commit -m 'wip wo", None), (v(&["git", "commit", "-m"]), "wip wo".to_string()));
+        assert_eq!(super::split_at_cursor("cat my\\ fi", None), (v(&["cat"]), "my fi".to_string()));
+        // out-of-range point clamps to the end
+        assert_eq!(super::split_at_cursor("ls -", Some(99)), (v(&["ls"]), "-".to_string()));
+    }
+
+    #[test]
+    fn open_quote_at_end_tracks_unterminated_quotes() {
+        use super::open_quote_at_end as open;
+        assert_eq!(open("git add \"my fi"), Some('"'));
+        assert_eq!(open("git add 'my fi"), Some('\''));
+        assert_eq!(open("git add \"a b\" c"), None);
+        // escaped quotes don't open anything; a quote inside the other kind is literal
+        assert_eq!(open("echo \\\"x"), None);
+        assert_eq!(open("echo \"it's"), Some('"'));
+        assert_eq!(open("echo 'a\\'"), None);
+    }
+}
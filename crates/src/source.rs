@@ -0,0 +1,128 @@
+// Where command definitions come from. The UI only talks to a `CompletionSource`; carapace is
+// the default, and other backends (clap-generated specs, fig specs, fixtures) plug in here.
+use crate::ast::CommandDef;
+use std::fmt;
+use std::sync::Arc;
+
+pub trait CompletionSource: Send + Sync {
+    // Short name used in error messages, e.g. "carapace"
+    fn name(&self) -> &str;
+
+    // Top-level commands with their descriptions; `path_filter` drops those not on PATH
+    fn list(&self, path_filter: bool) -> Result<Vec<(String, String)>, String>;
+
+    // The full definition (flags, subcommands) of the command `cmd_name`
+    fn export(&self, cmd_name: &str) -> Result<CommandDef, String>;
+
+    // The name to export for `<root> <sub>` when the root forwards unknown subcommands to
+    // `<root>-<sub>`; sources without such a notion return None
+    fn forwarded_binary(&self, _root: &str, _sub: &str) -> Option<String> {
+        None
+    }
+}
+
+// The `carapace` executable (see `crate::carapace`)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Carapace;
+
+impl CompletionSource for Carapace {
+    fn name(&self) -> &str {
+        "carapace"
+    }
+
+    fn list(&self, path_filter: bool) -> Result<Vec<(String, String)>, String> {
+        crate::carapace::list_entries(path_filter)
+    }
+
+    fn export(&self, cmd_name: &str) -> Result<CommandDef, String> {
+        crate::carapace::export(cmd_name)
+    }
+
+    fn forwarded_binary(&self, root: &str, sub: &str) -> Option<String> {
+        crate::carapace::forwarded_binary(root, sub)
+    }
+}
+
+// A fixed set of definitions, e.g. loaded from a spec file or built in tests. Nothing is
+// spawned; `path_filter` is ignored.
+#[derive(Clone, Debug, Default)]
+pub struct StaticSource {
+    pub commands: Vec<CommandDef>,
+}
+
+impl StaticSource {
+    pub fn new(commands: Vec<CommandDef>) -> Self {
+        StaticSource { commands }
+    }
+}
+
+impl CompletionSource for StaticSource {
+    fn name(&self) -> &str {
+        "static"
+    }
+
+    fn list(&self, _path_filter: bool) -> Result<Vec<(String, String)>, String> {
+        Ok(self
+            .commands
+            .iter()
+            .map(|c| (c.name.clone(), c.short.clone()))
+            .collect())
+    }
+
+    fn export(&self, cmd_name: &str) -> Result<CommandDef, String> {
+        self.commands
+            .iter()
+            .find(|c| c.name == cmd_name)
+            .cloned()
+            .ok_or_else(|| format!("{} {cmd_name}", crate::carapace::NO_COMPLETION_DATA))
+    }
+}
+
+// Shared handle to the source a `Model` uses; defaults to carapace
+#[derive(Clone)]
+pub struct Source(pub Arc<dyn CompletionSource>);
+
+impl Source {
+    pub fn new(source: impl CompletionSource + 'static) -> Self {
+        Source(Arc::new(source))
+    }
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source::new(Carapace)
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Source({})", self.0.name())
+    }
+}
+
+impl std::ops::Deref for Source {
+    type Target = dyn CompletionSource;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_source_lists_and_exports_its_commands() {
+        let src = Source::new(StaticSource::new(vec![CommandDef {
+            name: "tool".to_string(),
+            short: "does things".to_string(),
+            ..Default::default()
+        }]));
+        assert_eq!(src.name(), "static");
+        assert_eq!(src.list(true).unwrap(), vec![("tool".to_string(), "does things".to_string())]);
+        assert_eq!(src.export("tool").unwrap().short, "does things");
+        let err = src.export("other").unwrap_err();
+        assert!(err.starts_with(crate::carapace::NO_COMPLETION_DATA), "{err}");
+    }
+}
@@ -1,27 +1,76 @@
 // UI module root: split implementation into focused submodules under `ui/`
 
+pub mod keymap;
 pub mod model;
 pub mod render;
 pub mod run;
+pub mod session;
+pub mod settings;
 pub mod update;
 
 // Re-export commonly used symbols so existing call sites keep working (e.g. `crate::ui::initial_model`).
-pub use model::{ChooseItem, Model, initial_model, sort_items};
+pub use model::{CandidateCount, ChooseItem, KindFilter, Model, initial_model, sort_items};
 pub use render::{
     render_full, render_main_content, render_modeline, render_modeline_padded, render_preview_block,
 };
-pub use run::run;
+pub use run::{run, run_with_source};
+pub use settings::{AutoSelect, CliFlags, ExecMode, FrequentCommands, Settings};
 pub use update::handle_update;
 
-// Messages used by the update logic
-#[derive(Clone, Debug, PartialEq, Eq)]
+// Messages used by the update logic (serialized to record and replay sessions)
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum Msg {
     WindowSize { width: usize, height: usize },
     KeyBackspace,
     KeyEnter,
     KeyEsc,
     KeySpace,
+    KeyTab,
+    BackTab,
+    ToggleNumeric,
     Rune(char),
     KeyUp,
     KeyDown,
+    // Alt+Up/Down: move the last added flag or positional within its command
+    MoveArgUp,
+    MoveArgDown,
+    // Ctrl-L / Delete: drop what has been typed, leaving the command being built alone
+    ClearTyped,
+    // Ctrl-O: switch flag descriptions between the short usage and the extended one
+    ToggleLongUsage,
+    // Ctrl-K: drop the flags and positionals of the current (sub)command, keeping the command
+    ClearNode,
+    // Ctrl-F: cycle the list between all candidates, flags only and subcommands only
+    CycleKindFilter,
+    // Ctrl-E: show or hide the explain panel
+    ToggleExplain,
+    // the output lines of a flag's value command, run in the background
+    ValueCandidates { command: String, lines: Vec<String> },
+}
+
+impl Msg {
+    // Whether the user sent this (a key), as opposed to the terminal; listed in full so a new
+    // message has to be placed on one side
+    pub fn is_user_action(&self) -> bool {
+        match self {
+            Msg::WindowSize { .. } | Msg::ValueCandidates { .. } => false,
+            Msg::KeyBackspace
+            | Msg::KeyEnter
+            | Msg::KeyEsc
+            | Msg::KeySpace
+            | Msg::KeyTab
+            | Msg::BackTab
+            | Msg::ToggleNumeric
+            | Msg::Rune(_)
+            | Msg::KeyUp
+            | Msg::KeyDown
+            | Msg::MoveArgUp
+            | Msg::MoveArgDown
+            | Msg::ClearTyped
+            | Msg::ToggleLongUsage
+            | Msg::ClearNode
+            | Msg::CycleKindFilter
+            | Msg::ToggleExplain => true,
+        }
+    }
 }
@@ -0,0 +1,113 @@
+// The interactive key bindings as data: both adapters look their Ctrl keys up here, the modeline
+// takes its hints from it, and `van --keys --json` prints it for docs and external help UIs.
+use crate::ui::Msg;
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Binding {
+    // as written in docs, e.g. "Ctrl-K"
+    pub key: &'static str,
+    // short name of what it does; the modeline shows it next to `glyph`
+    pub action: &'static str,
+    // where it applies: "list" (choosing candidates), "value" (typing a value) or "any"
+    pub context: &'static str,
+    pub description: &'static str,
+    // the modeline's symbol for the key; empty when the modeline doesn't advertise it
+    #[serde(skip)]
+    pub glyph: &'static str,
+    // what the adapters send for it; None for keys they handle themselves (quitting) and for
+    // characters they pass on as typed text
+    #[serde(skip)]
+    pub msg: Option<Msg>,
+}
+
+const fn bind(
+    key: &'static str,
+    glyph: &'static str,
+    action: &'static str,
+    context: &'static str,
+    description: &'static str,
+    msg: Option<Msg>,
+) -> Binding {
+    Binding { key, action, context, description, glyph, msg }
+}
+
+// In modeline order first, then the rest
+static KEYMAP: &[Binding] = &[
+    bind("Space", "␣", "arg", "list", "Start typing an argument for the current command.", Some(Msg::KeySpace)),
+    bind("Enter", "⏎", "run", "any", "Confirm the value or highlighted candidate; with nothing pending, finish the command.", Some(Msg::KeyEnter)),
+    bind("Backspace", "⌫", "undo", "any", "Erase typed text, else take back the last argument or leave the command.", Some(Msg::KeyBackspace)),
+    bind("Esc", "⎋", "quit", "any", "Quit; while typing a value, cancel it.", Some(Msg::KeyEsc)),
+    bind("Ctrl-T", "^T", "num", "list", "Toggle selecting candidates by their number.", Some(Msg::ToggleNumeric)),
+    bind("Tab", "", "complete", "any", "Complete typed text as far as it is unambiguous, or fill the value suggestion.", Some(Msg::KeyTab)),
+    bind("Shift-Tab", "", "uncomplete", "any", "Take back the last Tab completion, else step back through candidates.", Some(Msg::BackTab)),
+    bind("Up", "", "prev", "any", "Previous page, or previous value suggestion.", Some(Msg::KeyUp)),
+    bind("Down", "", "next", "any", "Next page, or next value suggestion.", Some(Msg::KeyDown)),
+    bind("Ctrl-P", "", "prev", "any", "Same as Up.", Some(Msg::KeyUp)),
+    bind("Ctrl-N", "", "next", "any", "Same as Down.", Some(Msg::KeyDown)),
+    bind("Alt-Up", "", "move-up", "list", "Move the last added flag or argument one place earlier.", Some(Msg::MoveArgUp)),
+    bind("Alt-Down", "", "move-down", "list", "Move the last added flag or argument one place later.", Some(Msg::MoveArgDown)),
+    bind("Ctrl-L", "", "clear-typed", "list", "Drop what has been typed, keeping the command.", Some(Msg::ClearTyped)),
+    bind("Delete", "", "clear-typed", "list", "Same as Ctrl-L.", Some(Msg::ClearTyped)),
+    bind("Ctrl-K", "", "clear-node", "list", "Drop the flags and arguments of the current (sub)command.", Some(Msg::ClearNode)),
+    bind("Ctrl-O", "", "long-usage", "list", "Switch flag descriptions between short and extended usage.", Some(Msg::ToggleLongUsage)),
+    bind("Ctrl-E", "", "explain", "list", "Show or hide the highlighted candidate's full description under the list.", Some(Msg::ToggleExplain)),
+    bind("Ctrl-F", "", "kind-filter", "list", "Cycle the list between all candidates, flags only and commands only.", Some(Msg::CycleKindFilter)),
+    bind("Ctrl-C", "", "quit", "any", "Quit.", None),
+    bind("|", "", "pipe", "list", "Pipe the command into a next one chosen from the top-level list; `||` runs it on failure.", None),
+    bind("&&", "", "and", "list", "Follow the command with a next one run on success.", None),
+    bind(">", "", "redirect-out", "list", "Write the command's output to a file typed next; `>>` appends.", None),
+    bind("<", "", "redirect-in", "list", "Read the command's input from a file typed next.", None),
+];
+
+pub fn keymap() -> &'static [Binding] {
+    KEYMAP
+}
+
+// The message for Ctrl+`c`, as bound in the keymap
+pub fn ctrl_msg(c: char) -> Option<Msg> {
+    let c = c.to_ascii_uppercase();
+    KEYMAP
+        .iter()
+        .find(|b| b.key.strip_prefix("Ctrl-").is_some_and(|k| k.len() == 1 && k.starts_with(c)))
+        .and_then(|b| b.msg.clone())
+}
+
+pub fn keymap_json() -> String {
+    serde_json::to_string_pretty(KEYMAP).unwrap_or_default()
+}
+
+// `van --keys`: one binding per line, aligned
+pub fn keymap_text() -> String {
+    let key_w = KEYMAP.iter().map(|b| b.key.len()).max().unwrap_or(0);
+    KEYMAP
+        .iter()
+        .map(|b| format!("{:<key_w$}  {}\n", b.key, b.description))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_lists_the_core_bindings() {
+        let v: serde_json::Value = serde_json::from_str(&keymap_json()).unwrap();
+        let entries = v.as_array().unwrap();
+        for (key, action) in [("Space", "arg"), ("Enter", "run"), ("Backspace", "undo")] {
+            let b = entries.iter().find(|b| b["key"] == key).unwrap_or_else(|| panic!("{key} missing"));
+            assert_eq!(b["action"], action);
+            assert!(!b["description"].as_str().unwrap().is_empty());
+            assert!(["list", "value", "any"].contains(&b["context"].as_str().unwrap()));
+        }
+    }
+
+    #[test]
+    fn ctrl_keys_resolve_through_the_keymap() {
+        assert_eq!(ctrl_msg('k'), Some(Msg::ClearNode));
+        assert_eq!(ctrl_msg('T'), Some(Msg::ToggleNumeric));
+        assert_eq!(ctrl_msg('n'), Some(Msg::KeyDown));
+        assert_eq!(ctrl_msg('c'), None);
+        assert_eq!(ctrl_msg('z'), None);
+    }
+}
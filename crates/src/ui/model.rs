@@ -1,12 +1,16 @@
 use crate::ast;
 use bubbletea_widgets::Viewport;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 // small constants reused by rendering code
 pub const PREVIEW_BLOCK_LINES: usize = 3;
 pub const MODELINE_LINES: usize = 1;
 pub const RESERVED_LINES: usize = PREVIEW_BLOCK_LINES + MODELINE_LINES;
+// the explain panel under the list: a title line and the wrapped description
+pub const EXPLAIN_LINES: usize = 5;
 pub const DEFAULT_WIDTH: usize = 80;
+// how long a flag's value command may run before its output is given up on
+pub const VALUE_COMMAND_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
 
 // Represent a choose item (flag or command)
 #[derive(Clone, Debug)]
@@ -20,6 +24,76 @@ pub struct ChooseItem {
     pub depth: usize,
 }
 
+// How many candidates the current list has and how many the typed filter leaves visible
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CandidateCount {
+    pub total: usize,
+    pub visible: usize,
+}
+
+impl CandidateCount {
+    pub fn hidden_by_filter(&self) -> usize {
+        self.total.saturating_sub(self.visible)
+    }
+}
+
+// What the modeline's mode block shows before the buffer being typed
+pub const TYPED_LABEL: &str = "Typed: ";
+
+// Where a group of value suggestions came from, which names its heading in the list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueSource {
+    EnvVars,
+    Choices,
+    History,
+}
+
+impl ValueSource {
+    pub fn heading(self) -> &'static str {
+        match self {
+            ValueSource::EnvVars => "Environment variables",
+            ValueSource::Choices => "Choices",
+            ValueSource::History => "Previous values",
+        }
+    }
+}
+
+// Which kinds of candidate the list offers, cycled with Ctrl-F on commands that have many of both
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KindFilter {
+    #[default]
+    All,
+    FlagsOnly,
+    CommandsOnly,
+}
+
+impl KindFilter {
+    pub fn next(self) -> Self {
+        match self {
+            KindFilter::All => KindFilter::FlagsOnly,
+            KindFilter::FlagsOnly => KindFilter::CommandsOnly,
+            KindFilter::CommandsOnly => KindFilter::All,
+        }
+    }
+
+    pub fn allows(self, kind: &str) -> bool {
+        match self {
+            KindFilter::All => true,
+            KindFilter::FlagsOnly => kind == "flag",
+            KindFilter::CommandsOnly => kind == "cmd",
+        }
+    }
+
+    // shown in the modeline while the filter hides something
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            KindFilter::All => None,
+            KindFilter::FlagsOnly => Some("flags only"),
+            KindFilter::CommandsOnly => Some("commands only"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Model {
     pub items: Vec<ChooseItem>,
@@ -34,6 +108,10 @@ pub struct Model {
     pub pending_pos: bool,
     pub pending_depth: usize,
     pub pending_value: String,
+    // key already entered for a `key=value` flag (e.g. `git -c`); the value is typed next
+    pub pending_kv_key: Option<String>,
+    // the operator of a redirection whose file is being typed (`file` still empty)
+    pub pending_redirect: Option<ast::Redirection>,
     pub err: String,
     pub exit_preview: String,
     pub def_cache: HashMap<String, ast::CommandDef>,
@@ -41,17 +119,128 @@ pub struct Model {
     pub page: usize,
     pub per_page: usize,
     pub screen_width: usize,
+    // the size the terminal last reported, saved on exit (see `remember_window_size`)
+    pub reported_size: Option<crate::history::WindowSize>,
     // viewport using bubbletea widgets
     pub vp: Viewport,
     // numeric mode baseline snapshot (indices into items) used by update/render logic
     pub numeric_baseline: Option<Vec<usize>>,
+    // numeric mode entered explicitly via the toggle key: digits always select by number
+    pub numeric_locked: bool,
+    // remembered flag values offered as suggestions in value mode
+    pub value_history: crate::history::ValueHistory,
+    // runs per top-level command, for `settings.frequent_commands`
+    pub command_usage: crate::history::CommandUsage,
+    pub suggestion_idx: usize,
+    // number of flags/subcommands of the loaded command left out of `items` (hidden defs)
+    pub hidden_items: usize,
+    pub settings: crate::ui::Settings,
+    // terminal lines not available to the list: preview box + modeline, plus any chrome an
+    // embedder draws around van (defaults to RESERVED_LINES)
+    pub reserved_lines: usize,
+    // (before, after) of each buffer change made by Tab, most recent last; BackTab undoes them
+    pub tab_undo: Vec<(String, String)>,
+    // describe flags by their extended usage where they have one (toggled with Ctrl-O)
+    pub long_usage: bool,
+    // Ctrl-E: show the highlighted candidate's whole description in a panel under the list,
+    // which takes EXPLAIN_LINES from the list's rows
+    pub explain: bool,
+    // where command lists and definitions come from (carapace unless replaced)
+    pub source: crate::source::Source,
+    // items of other kinds are left out of `items`, so numbers and keys only cover the rest
+    pub kind_filter: KindFilter,
+    // the top-level command list last shown, for switching to another root without backing out
+    pub top_level: Vec<(String, String)>,
+    // commands finished by typing `|`, `&&` or `||`, each with the operator that follows it;
+    // `ast` is the command after the last one
+    pub pipeline: Vec<(ast::Segment, ast::BinaryOp)>,
+    // `ssh <host>` (with any ssh options) in front of a remote command completed from local
+    // definitions; kept verbatim at the start of the preview
+    pub remote_prefix: Vec<String>,
+    // the words each git alias subcommand of the loaded `git` root stands for (`VAN_GIT_ALIASES`)
+    pub git_aliases: HashMap<String, Vec<String>>,
+    // output lines of the pending flag's value command (`settings.value_commands`), run once
+    // when the flag is chosen; they arrive as `Msg::ValueCandidates`
+    pub value_candidates: Vec<String>,
+    // the value command the event loop is to run next, off the update path (`run::value_command_task`)
+    pub value_command_request: Option<String>,
+    // every message applied so far, while recording the session (see `ui::session`)
+    pub recording: Option<Vec<crate::ui::Msg>>,
 }
 
 // derive(Default) provides the default implementation
 
+// The distinct non-empty output lines of `sh -c <command>`, or none when it fails or is still
+// running after `timeout` (it is killed then, with whatever it started). Blocks until then.
+pub(crate) fn value_command_lines(command: &str, timeout: std::time::Duration) -> Vec<String> {
+    use std::io::Read;
+    use std::process::{Command, Stdio};
+    let mut sh = Command::new("sh");
+    sh.arg("-c").arg(command).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::null());
+    // in a process group of its own, so a timeout also stops the processes it started
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut sh, 0);
+    let Ok(mut child) = sh.spawn() else {
+        return vec![];
+    };
+    let Some(mut stdout) = child.stdout.take() else {
+        stop_value_command(&mut child);
+        return vec![];
+    };
+    // read on a thread so a command filling the pipe can't outlast the deadline unnoticed
+    let (tx, rx) = std::sync::mpsc::channel();
+    let reader = std::thread::spawn(move || {
+        let mut out = String::new();
+        let _ = stdout.read_to_string(&mut out);
+        let _ = tx.send(out);
+    });
+    let deadline = std::time::Instant::now() + timeout;
+    let out = rx.recv_timeout(timeout).ok();
+    let succeeded = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status.success(),
+            Ok(None) if out.is_some() && std::time::Instant::now() < deadline => {
+                std::thread::sleep(std::time::Duration::from_millis(5))
+            }
+            _ => break false,
+        }
+    };
+    if out.is_some() {
+        // the reader has sent all there was
+        let _ = reader.join();
+    }
+    if out.is_none() || !succeeded {
+        // once the group is killed, nothing holds the pipe open any more: the reader sees its
+        // end and returns on its own
+        stop_value_command(&mut child);
+    }
+    let mut lines: Vec<String> = vec![];
+    for l in out.iter().filter(|_| succeeded).flat_map(|o| o.lines()).map(str::trim) {
+        if !l.is_empty() && !lines.iter().any(|x| x == l) {
+            lines.push(l.to_string());
+        }
+    }
+    lines
+}
+
+// Kill a value command and the processes in its group, and reap it
+fn stop_value_command(child: &mut std::process::Child) {
+    #[cfg(unix)]
+    let _ = std::process::Command::new("kill")
+        .args(["-s", "KILL", "--", &format!("-{}", child.id())])
+        .stderr(std::process::Stdio::null())
+        .status();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
 pub fn initial_model(entries: Vec<(String, String)>) -> Model {
-    let mut m = Model::default();
+    let mut m = Model {
+        reserved_lines: RESERVED_LINES,
+        ..Model::default()
+    };
     if !entries.is_empty() {
+        m.top_level = entries.clone();
         let items: Vec<ChooseItem> = entries
             .into_iter()
             .map(|(name, short)| {
@@ -79,16 +268,100 @@ impl Model {
         crate::ui::update::handle_update(self, msg);
     }
 
+    // How far into a command line we are: 0 while choosing a command, 1 at the root command,
+    // 2 and up inside subcommands
+    pub fn depth(&self) -> usize {
+        self.ast.stack.len()
+    }
+
+    // What Backspace does next, for the modeline; None when there is nothing to take back
+    pub fn backspace_hint(&self) -> Option<&'static str> {
+        if self.in_value_mode || !self.typed.is_empty() {
+            return Some("erase");
+        }
+        match self.depth() {
+            0 => None,
+            1 if self.ast.top().is_some_and(|t| t.flags.is_empty() && t.positionals.is_empty()) => Some("back"),
+            _ => Some("undo"),
+        }
+    }
+
     pub fn mode(&self) -> String {
         if !self.typed.is_empty() {
-            return format!("Typed: {}", self.typed);
+            return format!("{TYPED_LABEL}{}", self.typed);
         }
-        if self.ast.stack.is_empty() {
+        if self.depth() == 0 {
             return "van".to_string();
         }
+        if self.settings.breadcrumb_depth > 0 {
+            return self.breadcrumb(self.settings.breadcrumb_depth);
+        }
         self.last_stack_name().unwrap_or_else(|| "van".to_string())
     }
 
+    // The subcommand path as `git › remote › add`; levels beyond the innermost `depth` are
+    // elided as a leading `…`
+    pub fn breadcrumb(&self, depth: usize) -> String {
+        let names: Vec<&str> = self
+            .ast
+            .stack
+            .iter()
+            .map(|n| n.name.trim())
+            .filter(|n| !n.is_empty())
+            .collect();
+        if names.is_empty() {
+            return "van".to_string();
+        }
+        let keep = depth.max(1);
+        if names.len() > keep {
+            let tail = &names[names.len() - keep..];
+            return format!("… › {}", tail.join(" › "));
+        }
+        names.join(" › ")
+    }
+
+    // Lay out at the terminal size saved last session (see `remember_window_size`) until the
+    // terminal reports its own; returns whether a size was applied
+    pub fn restore_window_size(&mut self, path: &std::path::Path) -> bool {
+        let Some(size) = crate::history::load_window_size(path) else {
+            return false;
+        };
+        self.update(crate::ui::Msg::WindowSize {
+            width: size.width,
+            height: size.height,
+        });
+        true
+    }
+
+    // Note a size the terminal reported; only the last one is saved, when van exits
+    pub fn remember_window_size(&mut self, width: usize, height: usize) {
+        self.reported_size = Some(crate::history::WindowSize { width, height });
+    }
+
+    // Save the last reported size for the next session, when enabled; best-effort
+    pub fn save_window_size(&self) {
+        if let Some(path) = crate::history::window_size_path() {
+            self.save_window_size_to(&path);
+        }
+    }
+
+    pub fn save_window_size_to(&self, path: &std::path::Path) {
+        if let Some(size) = self.reported_size.filter(|_| self.settings.remember_window_size) {
+            let _ = crate::history::save_window_size(path, size);
+        }
+    }
+
+    // Load a whole command line (quotes and escapes honored) as if typed token by token
+    pub fn load_command_line(&mut self, line: &str) -> Result<(), String> {
+        let args = crate::parse::tokenize_command_line(line);
+        crate::ui::run::load_args(self, &args)
+    }
+
+    // The typed buffer styled to show the left-unit vs the disambiguator runes
+    pub fn typed_display(&self) -> String {
+        crate::ui::render::decorate_typed_default(&self.typed_raw, self.numeric_baseline.is_some())
+    }
+
     // helper: return last non-empty stack name if any
     fn last_stack_name(&self) -> Option<String> {
         self.ast
@@ -105,6 +378,121 @@ impl Model {
             })
     }
 
+    // Hand the current page to the viewport widget. Rendering slices `render_list_content`
+    // itself, so the widget only mirrors it: it is given that page already cut to `per_page`
+    // lines no wider than the screen, leaving it nothing to wrap or scroll.
+    pub fn sync_viewport(&mut self, content: &str) {
+        let page: Vec<String> = content
+            .lines()
+            .take(self.per_page)
+            .map(|l| crate::ui::render::util::truncate_visible(l, self.screen_width))
+            .collect();
+        self.vp = Viewport::new(self.screen_width, self.per_page);
+        self.vp.set_content(&page.join("\n"));
+    }
+
+    // Export a root command, falling back to the configured alias map when the source doesn't
+    // know `name`. Returns the root to show (the typed name for aliases) and the definition.
+    // A listed command matching `name` only case-insensitively (`GIT` for `git`) resolves to it;
+    // its canonical casing is shown unless `settings.preserve_root_case` is set.
+    pub fn export_root(&self, name: &str) -> Result<(String, ast::CommandDef), String> {
+        match self.source.export(name) {
+            Ok(def) => Ok((def.name.clone(), def)),
+            Err(e) => {
+                if let Some(target) = self.settings.resolve_alias(name).filter(|t| *t != name) {
+                    let def = self.source.export(target)?;
+                    return Ok((name.to_string(), def));
+                }
+                let listed = self.items.iter().find(|it| {
+                    it.kind == "cmd" && it.label != name && it.label.eq_ignore_ascii_case(name)
+                });
+                match listed {
+                    Some(it) => {
+                        let def = self.source.export(&it.label)?;
+                        Ok((self.root_display(name, &def), def))
+                    }
+                    None => Err(e),
+                }
+            }
+        }
+    }
+
+    // After choosing `sub` directly under the root: when forwarding is enabled and `sub` carries
+    // no flags or subcommands, export `<root>-<sub>` and graft its flags and subcommands onto
+    // `sub` in the cached root definition, so later lookups by depth see them too.
+    pub fn forward_subcommand(&mut self, sub: &ast::CommandDef) -> Option<ast::CommandDef> {
+        if !self.settings.forward_subcommands
+            || self.depth() != 2
+            || !sub.flags.is_empty()
+            || !sub.subcommands.is_empty()
+        {
+            return None;
+        }
+        let root_key = self.ast.stack[0].name.clone();
+        let root_name = self.def_cache.get(&root_key).map_or(root_key.clone(), |d| d.name.clone());
+        let bin = self.source.forwarded_binary(&root_name, &sub.name)?;
+        let export = self.source.export(&bin).ok()?;
+        let grafted = ast::CommandDef {
+            name: sub.name.clone(),
+            aliases: sub.aliases.clone(),
+            short: if sub.short.is_empty() { export.short.clone() } else { sub.short.clone() },
+            hidden: sub.hidden,
+            ..export
+        };
+        if let Some(root) = self.def_cache.get_mut(&root_key) {
+            if let Some(slot) = root.subcommands.iter_mut().find(|sc| sc.name == sub.name) {
+                *slot = grafted.clone();
+            }
+        }
+        Some(grafted)
+    }
+
+    // With `settings.git_aliases`, list the configured aliases as subcommands of a `git` root
+    // (best-effort: git failing leaves the definition as it was)
+    pub fn add_git_aliases(&mut self, def: &mut ast::CommandDef) {
+        self.git_aliases.clear();
+        if !self.settings.git_aliases || def.name != "git" {
+            return;
+        }
+        if let Ok(aliases) = crate::gitconfig::aliases() {
+            self.git_aliases = crate::gitconfig::add_alias_subcommands(def, &aliases);
+        }
+    }
+
+    // The candidate the explain panel describes: the first on the page, which is the one typing
+    // narrows the list to
+    pub fn highlighted_item(&self) -> Option<ChooseItem> {
+        let visible = self.render_visible_items();
+        let on_page = crate::ui::render::list::page_item_range(self, &visible);
+        visible.into_iter().nth(on_page.start).filter(|_| !on_page.is_empty())
+    }
+
+    // The words to put in place of `name` when it is chosen right under the root
+    pub fn git_alias_expansion(&self, name: &str) -> Option<Vec<String>> {
+        if self.ast.stack.len() != 1 {
+            return None;
+        }
+        self.git_aliases.get(name).cloned()
+    }
+
+    // How to show a root the user typed as `typed` that resolved to `def`
+    pub fn root_display(&self, typed: &str, def: &ast::CommandDef) -> String {
+        if self.settings.preserve_root_case && typed.eq_ignore_ascii_case(&def.name) {
+            typed.to_string()
+        } else {
+            def.name.clone()
+        }
+    }
+
+    // Columns to render into: the terminal width, or DEFAULT_WIDTH while it reports none
+    pub fn render_width(&self) -> usize {
+        if self.screen_width == 0 {
+            DEFAULT_WIDTH
+        } else {
+            self.screen_width
+        }
+    }
+
     pub fn get_def_for_depth(&self, depth: usize) -> Option<ast::CommandDef> {
         if depth >= self.ast.stack.len() {
             return None;
@@ -148,25 +536,62 @@ impl Model {
     pub fn build_items_from_command(&mut self, cmd: &ast::CommandDef) {
         // Preserve early-exit behavior
         let mut items: Vec<ChooseItem> = vec![];
+        self.hidden_items = 0;
         if cmd.name.is_empty() {
             self.items = items;
             return;
         }
 
         let top_depth = self.ast.stack.len().saturating_sub(1);
-        items.extend(self.collect_flag_items(top_depth));
+        // a flag declared twice for one command (e.g. in both LocalFlags and PersistentFlags)
+        // is listed once
+        let mut seen = HashSet::new();
+        items.extend(self.collect_flag_items(top_depth).into_iter().filter(|it| {
+            let fd = it.flag_def.as_ref();
+            seen.insert((it.depth, fd.map(|f| f.longhand.clone()), fd.map(|f| f.shorthand.clone())))
+        }));
         items.extend(self.collect_subcommand_items(cmd, top_depth));
+        self.hidden_items = self.count_hidden_defs(cmd, top_depth);
+        items.retain(|it| self.kind_filter.allows(&it.kind));
 
-        self.items = sort_items(items);
+        let mut items = sort_items(items);
+        if self.settings.group_flags {
+            // keep each command's flags together so they render under one header
+            items.sort_by_key(|it| (it.kind == "cmd", if it.kind == "flag" { it.depth } else { 0 }));
+        }
+        self.items = items;
         self.page = 0;
     }
 
     // helper: collect flags for every depth up to top_depth
     fn collect_flag_items(&self, top_depth: usize) -> Vec<ChooseItem> {
         let mut items: Vec<ChooseItem> = vec![];
-        for d in 0..=top_depth {
-            if let Some(def) = self.get_def_for_depth(d) {
-                for f in def.flags.iter() {
+        let defs: Vec<Option<ast::CommandDef>> = (0..=top_depth).map(|d| self.get_def_for_depth(d)).collect();
+        for (d, def) in defs.iter().enumerate() {
+            if let Some(def) = def {
+                // a flag a deeper command defines again (same longhand and shorthand, as with
+                // persistent flags) is listed there only, so a deep stack sharing flags doesn't offer
+                // each of them once per level; `git -C` and `commit -C` are different flags
+                let shadowed = |f: &ast::FlagDef| {
+                    defs[d + 1..].iter().flatten().any(|deeper| {
+                        deeper.flags.iter().any(|g| g.longhand == f.longhand && g.shorthand == f.shorthand)
+                    })
+                };
+                // a synthesized --help is only offered for the innermost command
+                let synthesized = if d == top_depth && self.settings.synthesize_help {
+                    synthesized_help_flag(def)
+                } else {
+                    None
+                };
+                let negations = negated_longhands(&def.flags);
+                for mut f in def.flags.iter().filter(|f| !f.hidden && !shadowed(f)).cloned().chain(synthesized) {
+                    // `--no-color` is folded into the `--color` item
+                    if f.longhand.strip_prefix("no-").is_some_and(|pos| negations.contains(pos)) {
+                        continue;
+                    }
+                    if negations.contains(f.longhand.as_str()) {
+                        f.negation = format!("no-{}", f.longhand);
+                    }
                     let mut forms = vec![];
                     let mut label_parts = vec![];
                     if !f.longhand.is_empty() {
@@ -177,6 +602,10 @@ impl Model {
                         forms.push(format!("-{}", f.shorthand));
                         label_parts.push(format!("-{}", f.shorthand));
                     }
+                    if !f.negation.is_empty() {
+                        forms.push(format!("--{}", f.negation));
+                        label_parts.push(format!("--{}", f.negation));
+                    }
                     let mut label = label_parts.join(", ");
                     if d < top_depth {
                         label = format!("{}: {}", def.name, label);
@@ -185,7 +614,7 @@ impl Model {
                         kind: "flag".to_string(),
                         label,
                         forms,
-                        flag_def: Some(f.clone()),
+                        flag_def: Some(f),
                         cmd_def: None,
                         short: String::new(),
                         depth: d,
@@ -199,7 +628,7 @@ impl Model {
     // helper: collect subcommands for the provided cmd at top_depth
     fn collect_subcommand_items(&self, cmd: &ast::CommandDef, top_depth: usize) -> Vec<ChooseItem> {
         let mut items: Vec<ChooseItem> = vec![];
-        for sc in cmd.subcommands.iter() {
+        for sc in cmd.subcommands.iter().filter(|sc| !sc.hidden) {
             let mut forms = vec![sc.name.clone()];
             for a in sc.aliases.iter() {
                 if !a.is_empty() {
@@ -219,6 +648,409 @@ impl Model {
         items
     }
 
+    // helper: count the flags (all depths) and subcommands skipped because they are hidden
+    fn count_hidden_defs(&self, cmd: &ast::CommandDef, top_depth: usize) -> usize {
+        let hidden_flags: usize = (0..=top_depth)
+            .filter_map(|d| self.get_def_for_depth(d))
+            .map(|def| def.flags.iter().filter(|f| f.hidden).count())
+            .sum();
+        hidden_flags + cmd.subcommands.iter().filter(|sc| sc.hidden).count()
+    }
+
+    // Total vs visible candidates for the current filter (typed letters or numeric prefix)
+    pub fn candidate_count(&self) -> CandidateCount {
+        self.candidate_count_for(&self.render_visible_items())
+    }
+
+    // `candidate_count` when the visible items are already at hand
+    pub fn candidate_count_for(&self, visible: &[ChooseItem]) -> CandidateCount {
+        CandidateCount { total: self.items.len(), visible: visible.len() }
+    }
+
+    // Summary of shown vs hidden candidates, e.g. "(3 flags, 12 subcommands; 2 hidden)".
+    // None at the top-level list, in value mode, or when disabled in settings.
+    pub fn counts_summary(&self) -> Option<String> {
+        if !self.settings.show_counts || self.in_value_mode || self.current.is_none() {
+            return None;
+        }
+        let flags = self.items.iter().filter(|it| it.kind == "flag").count();
+        let cmds = self.items.iter().filter(|it| it.kind == "cmd").count();
+        let plural = |n: usize, word: &str| {
+            if n == 1 {
+                format!("{n} {word}")
+            } else {
+                format!("{n} {word}s")
+            }
+        };
+        let mut out = format!("({}, {}", plural(flags, "flag"), plural(cmds, "subcommand"));
+        if self.hidden_items > 0 {
+            out.push_str(&format!("; {} hidden", self.hidden_items));
+        }
+        out.push(')');
+        Some(out)
+    }
+
+    // What the command being built does, from the `short` of each level in order, e.g.
+    // "git: the stupid content tracker > commit: Record changes". Only once a leaf command
+    // (no subcommands left to choose) is reached, and only with `settings.describe_command`.
+    pub fn command_description(&self) -> Option<String> {
+        if !self.settings.describe_command || self.in_value_mode {
+            return None;
+        }
+        let current = self.current.as_ref()?;
+        if current.subcommands.iter().any(|sc| !sc.hidden) {
+            return None;
+        }
+        let levels: Vec<String> = self
+            .ast
+            .stack
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| !node.name.is_empty())
+            .map(|(d, node)| {
+                let short = self.get_def_for_depth(d).map(|def| def.short).unwrap_or_default();
+                if short.is_empty() {
+                    node.name.clone()
+                } else {
+                    format!("{}: {short}", node.name)
+                }
+            })
+            .collect();
+        if levels.is_empty() {
+            None
+        } else {
+            Some(levels.join(" > "))
+        }
+    }
+
+    // History key for the flag awaiting a value: command path up to its depth + flag form
+    pub fn pending_value_key(&self) -> Option<String> {
+        let fd = self.pending_flag.as_ref()?;
+        let flag = if !fd.longhand.is_empty() {
+            format!("--{}", fd.longhand)
+        } else {
+            format!("-{}", fd.shorthand)
+        };
+        let path: Vec<&str> = self
+            .ast
+            .stack
+            .iter()
+            .take(self.pending_depth + 1)
+            .map(|n| n.name.as_str())
+            .collect();
+        Some(crate::history::ValueHistory::key(&path, &flag))
+    }
+
+    // Whether the pending flag takes `key=value` pairs, by its type or the configured list
+    pub fn pending_is_kv(&self) -> bool {
+        let Some(fd) = &self.pending_flag else {
+            return false;
+        };
+        if fd.value_hint == "key=value" {
+            return true;
+        }
+        let cmd = self.ast.stack.get(self.pending_depth).map(|n| n.name.as_str()).unwrap_or("");
+        self.settings.kv_flags.iter().any(|entry| match entry.split_once(' ') {
+            Some((c, form)) => c == cmd && form == self.pending_form,
+            None => entry == &self.pending_form,
+        })
+    }
+
+    // The pending flag's configured value command: for "<command> <form>" entries the command
+    // must be the one the flag belongs to, and any of the flag's forms names it
+    fn value_command(&self) -> Option<&str> {
+        let fd = self.pending_flag.as_ref()?;
+        let forms = crate::ui::run::flag_forms(fd);
+        let cmd = self.ast.stack.get(self.pending_depth).map(|n| n.name.as_str()).unwrap_or("");
+        self.settings
+            .value_commands
+            .iter()
+            .find(|(flag, _)| match flag.split_once(' ') {
+                Some((c, form)) => c == cmd && forms.iter().any(|f| f == form),
+                None => forms.contains(flag),
+            })
+            .map(|(_, command)| command.as_str())
+    }
+
+    // Ask for the pending flag's value command (if it has one) to be run; its output lines come
+    // back as `Msg::ValueCandidates`. A command that fails or outlasts VALUE_COMMAND_TIMEOUT
+    // offers nothing rather than an error.
+    pub fn request_value_candidates(&mut self) {
+        self.value_candidates.clear();
+        self.value_command_request = self.value_command().map(str::to_string);
+    }
+
+    // The output of a value command, kept while its flag is still waiting for a value
+    pub fn receive_value_candidates(&mut self, command: &str, lines: Vec<String>) {
+        if self.in_value_mode && self.value_command() == Some(command) {
+            self.value_candidates = lines;
+        }
+    }
+
+    // Values for the pending flag that extend what has been typed so far, in the order the
+    // list shows them (see `value_suggestion_groups`)
+    pub fn value_suggestions(&self) -> Vec<String> {
+        self.value_suggestion_groups().into_iter().flat_map(|(_, values)| values).collect()
+    }
+
+    // The value suggestions grouped by where they came from: the variables a `$NAME` being typed
+    // may name, or else the flag's choices (fixed, then its value command's output) followed by
+    // previously entered values. Once the key of a `key=value` flag is entered, only values
+    // recorded for it are offered. Empty groups are left out.
+    pub fn value_suggestion_groups(&self) -> Vec<(ValueSource, Vec<String>)> {
+        if !self.in_value_mode {
+            return vec![];
+        }
+        if let Some(vars) = env_var_suggestions(&self.pending_value, std::env::vars().map(|(k, _)| k)) {
+            return vec![(ValueSource::EnvVars, vars)].into_iter().filter(|(_, v)| !v.is_empty()).collect();
+        }
+        let from_command = self
+            .value_candidates
+            .iter()
+            .filter(|_| self.pending_flag.is_some() && self.pending_kv_key.is_none())
+            .cloned();
+        let choices: Vec<String> = self
+            .pending_value_choices()
+            .into_iter()
+            .chain(from_command)
+            .filter(|c| c.starts_with(&self.pending_value) && *c != self.pending_value)
+            .collect();
+        let mut history = Vec::new();
+        if let Some(key) = self.pending_value_key() {
+            let prefix = match &self.pending_kv_key {
+                Some(k) => format!("{k}={}", self.pending_value),
+                None => self.pending_value.clone(),
+            };
+            let strip = self.pending_kv_key.as_ref().map_or(0, |k| k.len() + 1);
+            for v in self.value_history.values(&key).iter() {
+                if v.starts_with(&prefix) && *v != prefix {
+                    let v = v[strip..].to_string();
+                    if !choices.contains(&v) && !history.contains(&v) {
+                        history.push(v);
+                    }
+                }
+            }
+        }
+        [(ValueSource::Choices, choices), (ValueSource::History, history)]
+            .into_iter()
+            .filter(|(_, v)| !v.is_empty())
+            .collect()
+    }
+
+    // The values the pending flag accepts when they form a fixed set: its listed choices
+    // (hint "on|off|auto", or "true|false" for a boolean that takes an explicit value)
+    pub fn pending_value_choices(&self) -> Vec<String> {
+        let Some(fd) = self.pending_flag.as_ref().filter(|_| self.pending_kv_key.is_none()) else {
+            return vec![];
+        };
+        if !fd.value_hint.contains('|') {
+            return vec![];
+        }
+        fd.value_hint.split('|').filter(|c| !c.is_empty()).map(str::to_string).collect()
+    }
+
+    // Nothing chosen yet: the top-level list is showing and the command line is still blank
+    pub fn is_empty_state(&self) -> bool {
+        self.current.is_none() && self.ast.root.is_empty() && self.pipeline.is_empty() && self.remote_prefix.is_empty()
+    }
+
+    // The command line as shell text, keeping `settings.verbatim_words` positionals unquoted
+    pub fn preview_text(&self) -> String {
+        self.command_line(&self.settings.verbatim_words)
+    }
+
+    // The command line with every word quoted where needed: what a shell is given to run
+    pub fn shell_text(&self) -> String {
+        self.command_line(&[])
+    }
+
+    fn command_line(&self, verbatim: &[regex::Regex]) -> String {
+        let mut out = String::new();
+        for word in &self.remote_prefix {
+            out.push_str(&crate::parse::shell_word(word));
+            out.push(' ');
+        }
+        let segments = self.pipeline.iter().map(|(seg, op)| (seg, Some(op))).chain([(&self.ast, None)]);
+        for (i, (seg, op)) in segments.enumerate() {
+            if i == 0 && !self.remote_prefix.is_empty() {
+                // ssh joins the remote command into one line for the remote shell, so its words
+                // are quoted for that shell and again for this one
+                let words = seg.preview_words_keeping(verbatim).into_iter().map(|w| crate::parse::quote_remote_word(&w));
+                let redirections = seg.redirections.iter().map(crate::ast::Redirection::render);
+                out.push_str(&words.chain(redirections).collect::<Vec<_>>().join(" "));
+            } else {
+                out.push_str(&seg.render_preview_keeping(verbatim));
+            }
+            if let Some(op) = op {
+                out.push(' ');
+                out.push_str(op.symbol());
+                out.push(' ');
+            }
+        }
+        out.trim_end().to_string()
+    }
+
+    // Put the top-level list in `settings.frequent_commands` order: the commands run most often
+    // first, by count, or only those while any were run. Other lists are left alone.
+    pub fn arrange_top_level(&mut self) {
+        let mode = self.settings.frequent_commands;
+        if mode == crate::ui::FrequentCommands::Off || self.current.is_some() || self.command_usage.is_empty() {
+            return;
+        }
+        let usage = &self.command_usage;
+        if mode == crate::ui::FrequentCommands::Only && self.items.iter().any(|it| usage.count(&it.label) > 0) {
+            self.items.retain(|it| usage.count(&it.label) > 0);
+        }
+        // stable, so equally used commands keep their order
+        self.items.sort_by_key(|it| std::cmp::Reverse(usage.count(&it.label)));
+    }
+
+    // The fewest keys that select the candidate labelled `label` from what is typed now, for
+    // docs and screenshots. Each step types either the ace key assigned to the candidate or the
+    // next character of its name, as a user following the highlighted keys would; None when no
+    // such sequence selects it.
+    pub fn keystroke_path_to(&self, label: &str) -> Option<String> {
+        const MAX_KEYS: usize = 32;
+        let forms: Vec<String> = self.items.iter().flat_map(|it| it.forms.iter().cloned()).collect();
+        let targets: Vec<(usize, &String)> = self
+            .items
+            .iter()
+            .flat_map(|it| it.forms.iter().map(move |f| (it.label == label, f)))
+            .enumerate()
+            .filter(|(_, (is_target, _))| *is_target)
+            .map(|(i, (_, f))| (i, f))
+            .collect();
+        if targets.is_empty() {
+            return None;
+        }
+        // the form index typing `typed` would select outright
+        let selected = |typed: &str| {
+            crate::acekey::assign_ace_keys(&forms, typed)
+                .filter(|asg| asg.len() == 1 && asg[0].prefix.is_empty())
+                .map(|asg| asg[0].index)
+        };
+        let start = self.typed_raw.clone();
+        let base = start.chars().count();
+        // breadth-first by number of keys typed; an ace key may take several at once
+        let mut by_len: Vec<Vec<String>> = vec![vec![start.clone()]];
+        let mut seen: HashSet<String> = HashSet::from([start.clone()]);
+        let mut n = 0;
+        while n < by_len.len() && n <= MAX_KEYS {
+            for typed in std::mem::take(&mut by_len[n]) {
+                match selected(&typed) {
+                    Some(i) if targets.iter().any(|(t, _)| *t == i) => return Some(typed[start.len()..].to_string()),
+                    // another candidate got selected on the way
+                    Some(_) => continue,
+                    None => {}
+                }
+                let mut next: Vec<String> = targets
+                    .iter()
+                    .filter_map(|(_, f)| f.chars().nth(typed.chars().count()))
+                    .map(|c| format!("{typed}{c}"))
+                    .collect();
+                if let Some(asg) = crate::acekey::assign_ace_keys(&forms, &typed) {
+                    next.extend(
+                        asg.iter()
+                            .filter(|a| !a.prefix.is_empty() && targets.iter().any(|(i, _)| *i == a.index))
+                            .map(|a| format!("{typed}{}", a.prefix)),
+                    );
+                }
+                for t in next {
+                    let len = t.chars().count() - base;
+                    if len <= MAX_KEYS && seen.insert(t.clone()) {
+                        if by_len.len() <= len {
+                            by_len.resize(len + 1, vec![]);
+                        }
+                        by_len[len].push(t);
+                    }
+                }
+            }
+            n += 1;
+        }
+        None
+    }
+
+    // Warning once the built command reaches 90% of `settings.max_command_len` bytes, past
+    // which running it may fail with "argument list too long"
+    pub fn command_length_warning(&self) -> Option<String> {
+        let limit = self.settings.max_command_len;
+        let len = self.preview_text().len();
+        if limit == 0 || len * 10 < limit * 9 {
+            return None;
+        }
+        Some(format!("⚠ {len}/{limit} bytes"))
+    }
+
+    // With `settings.match_descriptions`, the candidates whose description contains the typed
+    // text, case-insensitively, once no name matches it ("record" finds `commit`); empty otherwise
+    pub fn description_matches(&self) -> Vec<ChooseItem> {
+        if !self.settings.match_descriptions || self.typed_raw.is_empty() || self.numeric_baseline.is_some() {
+            return vec![];
+        }
+        let forms: Vec<String> = self.items.iter().flat_map(|it| it.forms.iter().cloned()).collect();
+        if crate::acekey::assign_ace_keys(&forms, &self.typed_raw).is_some() {
+            return vec![];
+        }
+        let typed = self.typed_raw.to_lowercase();
+        self.items
+            .iter()
+            .filter(|it| {
+                let usage = it.flag_def.as_ref().map(|fd| fd.usage.as_str()).unwrap_or_default();
+                [it.short.as_str(), usage].iter().any(|d| d.to_lowercase().contains(&typed))
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Whether a flag item is already present on the command node at its depth
+    pub fn flag_is_selected(&self, it: &ChooseItem) -> bool {
+        if it.kind != "flag" {
+            return false;
+        }
+        self.ast
+            .stack
+            .get(it.depth)
+            .is_some_and(|node| node.flags.iter().any(|f| it.forms.contains(&f.form)))
+    }
+
+    // Flags given together although the command's `exclusive_flags` allow only one of them:
+    // per offending set, its depth and the forms given (`--json`, `-y`)
+    pub fn conflicting_flags(&self) -> Vec<(usize, Vec<String>)> {
+        let mut out = vec![];
+        for (depth, node) in self.ast.stack.iter().enumerate() {
+            let Some(def) = self.get_def_for_depth(depth) else {
+                continue;
+            };
+            for set in &def.exclusive_flags {
+                let mut given_members = 0;
+                let mut forms = vec![];
+                let named = |f: &&ast::FlagDef| {
+                    let name = if f.longhand.is_empty() { &f.shorthand } else { &f.longhand };
+                    set.contains(name)
+                };
+                for fd in def.flags.iter().filter(named) {
+                    let fd_forms = crate::ui::run::flag_forms(fd);
+                    let given: Vec<String> =
+                        node.flags.iter().filter(|f| fd_forms.contains(&f.form)).map(|f| f.form.clone()).collect();
+                    if !given.is_empty() {
+                        given_members += 1;
+                        forms.extend(given);
+                    }
+                }
+                if given_members > 1 {
+                    out.push((depth, forms));
+                }
+            }
+        }
+        out
+    }
+
+    // Modeline warning naming the first set of conflicting flags
+    pub fn conflict_warning(&self) -> Option<String> {
+        let (_, forms) = self.conflicting_flags().into_iter().next()?;
+        Some(format!("⚠ only one of {}", forms.join(", ")))
+    }
+
     // Render helper wrappers that forward to the render module to keep this file focused on state.
     pub fn assigned_map(&self) -> HashMap<String, String> {
         crate::ui::render::assigned_map(self)
@@ -226,12 +1058,18 @@ impl Model {
     pub fn render_visible_items(&self) -> Vec<ChooseItem> {
         crate::ui::render::render_visible_items(self)
     }
+    pub fn list_row_count(&self, visible: &[ChooseItem]) -> usize {
+        crate::ui::render::list_row_count(self, visible)
+    }
     pub fn render_list_content(&self, visible: &[ChooseItem]) -> String {
         crate::ui::render::render_list_content(self, visible)
     }
     pub fn render_preview(&self) -> String {
         crate::ui::render::render_preview(self)
     }
+    pub fn render_embed(&self) -> String {
+        crate::ui::render::render_embed(self)
+    }
     pub fn render_preview_block(&self) -> Vec<String> {
         crate::ui::render::render_preview_block(self)
     }
@@ -241,6 +1079,50 @@ impl Model {
     pub fn render_full(&self) -> String {
         crate::ui::render::render_full(self)
     }
+    pub fn render_full_to(&self, out: &mut String) {
+        crate::ui::render::render_full_to(self, out)
+    }
+
+    // The visible candidates as `keystroke\tlabel\tdescription` lines for an external fuzzy
+    // picker such as fzf; `apply_fzf_line` takes a chosen line back.
+    pub fn flatten_for_fzf(&self) -> String {
+        let assigned = self.assigned_map();
+        let clean = |s: &str| s.replace(['\t', '\n', '\r'], " ");
+        let mut out = String::new();
+        for it in self.render_visible_items() {
+            let key = crate::ui::render::list::item_keystroke(&it, &assigned);
+            let desc = match &it.flag_def {
+                Some(fd) => fd.usage.clone(),
+                None => it.short.clone(),
+            };
+            out.push_str(&format!("{}\t{}\t{}\n", clean(&key), clean(&it.label), clean(&desc)));
+        }
+        out
+    }
+
+    // Select the candidate named by a line of `flatten_for_fzf` output, as if it had been typed
+    pub fn apply_fzf_line(&mut self, line: &str) -> Result<(), String> {
+        let mut cols = line.trim_end_matches(['\n', '\r']).splitn(3, '\t');
+        let key = cols.next().unwrap_or_default();
+        let label = cols.next().ok_or_else(|| format!("not a van candidate line: {line:?}"))?;
+        let it = self
+            .items
+            .iter()
+            .find(|it| it.label == label)
+            .cloned()
+            .ok_or_else(|| format!("no candidate labeled {label:?}"))?;
+        let assigned = self.assigned_map();
+        let form = it
+            .forms
+            .iter()
+            .find(|f| !key.is_empty() && assigned.get(*f).is_some_and(|k| k == key))
+            .or(it.forms.first())
+            .cloned()
+            .unwrap_or_default();
+        self.err.clear();
+        crate::ui::update::select_item(self, &it, &form);
+        if self.err.is_empty() { Ok(()) } else { Err(self.err.clone()) }
+    }
 
     // New helper to get labels of current items (replaces stored `root_list`)
     pub fn items_labels(&self) -> impl Iterator<Item = &str> {
@@ -248,6 +1130,49 @@ impl Model {
     }
 }
 
+// While the value being typed ends in a `$NAME` reference, the completions of that reference
+// among `names` (the whole value with `$NAME` filled in). None when no reference is open.
+pub fn env_var_suggestions(value: &str, names: impl Iterator<Item = String>) -> Option<Vec<String>> {
+    let idx = value.rfind('$')?;
+    let partial = &value[idx + 1..];
+    if !partial.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    let mut out: Vec<String> = names
+        .filter(|n| n.starts_with(partial) && n != partial)
+        .map(|n| format!("{}${n}", &value[..idx]))
+        .collect();
+    out.sort();
+    Some(out)
+}
+
+// Longhands of the switches that also come as `--no-<longhand>` (neither taking a value), so
+// each pair can be listed as one toggle
+fn negated_longhands(flags: &[ast::FlagDef]) -> HashSet<&str> {
+    let switch = |name: &str| flags.iter().any(|f| f.longhand == name && !f.requires_value && !f.hidden);
+    flags
+        .iter()
+        .filter(|f| !f.requires_value && !f.hidden)
+        .filter_map(|f| f.longhand.strip_prefix("no-"))
+        .filter(|pos| !pos.is_empty() && switch(pos))
+        .collect()
+}
+
+// A `--help` flag for commands whose export lacks one; `-h` only when that shorthand is free.
+fn synthesized_help_flag(def: &ast::CommandDef) -> Option<ast::FlagDef> {
+    if def.flags.iter().any(|f| f.longhand == "help") {
+        return None;
+    }
+    let shorthand = if def.flags.iter().any(|f| f.shorthand == "h") { "" } else { "h" };
+    Some(ast::FlagDef {
+        longhand: "help".to_string(),
+        shorthand: shorthand.to_string(),
+        usage: "show help".to_string(),
+        synthesized: true,
+        ..Default::default()
+    })
+}
+
 pub fn sort_items(items: Vec<ChooseItem>) -> Vec<ChooseItem> {
     let mut flags: Vec<ChooseItem> = items
         .iter()
@@ -294,9 +1219,22 @@ mod tests {
         assert_eq!(m.mode(), "Typed: abcd");
     }
 
+    #[test]
+    fn viewport_gets_the_page_cut_to_the_screen() {
+        let mut m = initial_model(vec![]);
+        m.screen_width = 8;
+        m.per_page = 3;
+        let content: Vec<String> = (0..10).map(|i| format!("\x1b[1mrow {i} with a long description\x1b[0m")).collect();
+        m.sync_viewport(&content.join("\n"));
+        let view = crate::ui::render::strip_ansi(&m.vp.view());
+        let rows: Vec<&str> = view.lines().map(str::trim_end).filter(|l| !l.is_empty()).collect();
+        assert_eq!(rows, vec!["row 0 wi", "row 1 wi", "row 2 wi"]);
+    }
+
     #[test]
     fn test_space_enters_value_mode_and_esc_cancels() {
         let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("ls");
         assert!(!m.in_value_mode);
         m.update(crate::ui::Msg::KeySpace);
         assert!(m.in_value_mode);
@@ -415,6 +1353,7 @@ mod tests {
                 shorthand: "v".to_string(),
                 usage: "v".to_string(),
                 requires_value: false,
+                ..Default::default()
             }],
             subcommands: vec![CommandDef {
                 name: "sub".to_string(),
@@ -422,7 +1361,9 @@ mod tests {
                 aliases: vec![],
                 flags: vec![],
                 subcommands: vec![],
+                ..Default::default()
             }],
+            ..Default::default()
         };
         m.ast = Segment::new_empty("root");
         m.current = Some(def.clone());
@@ -454,15 +1395,18 @@ mod tests {
                     shorthand: "m".to_string(),
                     usage: "msg".to_string(),
                     requires_value: true,
+                    ..Default::default()
                 },
                 FlagDef {
                     longhand: "verbose".to_string(),
                     shorthand: "v".to_string(),
                     usage: "v".to_string(),
                     requires_value: false,
+                    ..Default::default()
                 },
             ],
             subcommands: vec![],
+            ..Default::default()
         };
         m.ast = Segment::new_empty("root");
         m.current = Some(def.clone());
@@ -525,6 +1469,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         m.items = vec![ChooseItem {
             kind: "cmd".to_string(),
@@ -548,6 +1493,7 @@ mod tests {
             shorthand: "m".to_string(),
             usage: String::new(),
             requires_value: true,
+            ..Default::default()
         };
         m2.items = vec![ChooseItem {
             kind: "flag".to_string(),
@@ -576,6 +1522,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         let s1 = CommandDef {
             name: "serve".to_string(),
@@ -583,6 +1530,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         let s2 = CommandDef {
             name: "setup".to_string(),
@@ -590,6 +1538,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         root.subcommands = vec![s1.clone(), s2.clone()];
         m.ast = Segment::new_empty("root");
@@ -613,6 +1562,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         let root = CommandDef {
             name: "root".to_string(),
@@ -620,6 +1570,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![sub.clone()],
+            ..Default::default()
         };
         m.ast = Segment::new_empty("root");
         m.ast.root = "root".to_string();
@@ -659,6 +1610,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         let root = CommandDef {
             name: "jj".to_string(),
@@ -666,6 +1618,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![init_def.clone()],
+            ..Default::default()
         };
         m.def_cache.insert("jj".to_string(), root.clone());
         m.ast = Segment::new_empty("jj");
@@ -708,6 +1661,7 @@ mod tests {
             shorthand: "m".to_string(),
             usage: String::new(),
             requires_value: true,
+            ..Default::default()
         };
         m.items = vec![ChooseItem {
             kind: "flag".to_string(),
@@ -760,8 +1714,10 @@ mod tests {
                 shorthand: "v".to_string(),
                 usage: "v".to_string(),
                 requires_value: false,
+                ..Default::default()
             }],
             subcommands: vec![],
+            ..Default::default()
         };
         let sub = CommandDef {
             name: "sub".to_string(),
@@ -769,6 +1725,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         root.subcommands = vec![sub.clone()];
         m.ast = Segment::new_empty("root");
@@ -874,6 +1831,51 @@ mod tests {
         assert_eq!(m.mode(), "sub");
     }
 
+    #[test]
+    fn persisted_window_size_is_the_initial_layout() {
+        let dir = std::env::temp_dir().join(format!("van-window-{}", std::process::id()));
+        let path = dir.join("window.json");
+        let mut m = initial_model(vec![]);
+        let before = (m.screen_width, m.per_page);
+        assert!(!m.restore_window_size(&path));
+        assert_eq!((m.screen_width, m.per_page), before);
+
+        crate::history::save_window_size(&path, crate::history::WindowSize { width: 132, height: 40 }).unwrap();
+        let mut m = initial_model(vec![]);
+        assert!(m.restore_window_size(&path));
+        assert_eq!(m.screen_width, 132);
+        assert_eq!(m.per_page, 40 - m.reserved_lines);
+        // the size the terminal reports still wins
+        m.update(crate::ui::Msg::WindowSize { width: 100, height: 30 });
+        assert_eq!((m.screen_width, m.per_page), (100, 30 - m.reserved_lines));
+
+        // reported sizes are noted as they come and only the last is saved, when enabled
+        m.remember_window_size(100, 30);
+        m.remember_window_size(120, 35);
+        m.save_window_size_to(&path);
+        assert_eq!(crate::history::load_window_size(&path), Some(crate::history::WindowSize { width: 132, height: 40 }));
+        m.settings.remember_window_size = true;
+        m.save_window_size_to(&path);
+        assert_eq!(crate::history::load_window_size(&path), Some(crate::history::WindowSize { width: 120, height: 35 }));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn breadcrumb_mode_shows_the_path_up_to_the_configured_depth() {
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("git");
+        m.ast.push_subcommand("remote");
+        m.ast.push_subcommand("add");
+        assert_eq!(m.mode(), "add");
+        m.settings.breadcrumb_depth = 3;
+        assert_eq!(m.mode(), "git › remote › add");
+        m.settings.breadcrumb_depth = 2;
+        assert_eq!(m.mode(), "… › remote › add");
+        // typing still takes over the mode block
+        m.typed = "x".to_string();
+        assert_eq!(m.mode(), "Typed: x");
+    }
+
     #[test]
     fn test_numeric_selection_selects_flag_by_index() {
         let mut m = initial_model(vec![]);
@@ -885,6 +1887,7 @@ mod tests {
             shorthand: "f".to_string(),
             usage: String::new(),
             requires_value: false,
+            ..Default::default()
         };
         m.items = vec![ChooseItem {
             kind: "flag".to_string(),
@@ -909,6 +1912,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         let root = CommandDef {
             name: "root".to_string(),
@@ -916,6 +1920,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![sub.clone()],
+            ..Default::default()
         };
         m.ast = Segment::new_empty("root");
         m.ast.root = "root".to_string();
@@ -949,6 +1954,7 @@ mod tests {
                     shorthand: String::new(),
                     usage: String::new(),
                     requires_value: false,
+                    ..Default::default()
                 };
                 items.push(ChooseItem {
                     kind: "flag".to_string(),
@@ -989,6 +1995,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         let root = CommandDef {
             name: "ls".to_string(),
@@ -999,8 +2006,10 @@ mod tests {
                 shorthand: "a".to_string(),
                 usage: "show all".to_string(),
                 requires_value: false,
+                ..Default::default()
             }],
             subcommands: vec![init_sub.clone()],
+            ..Default::default()
         };
         // populate cache and set current
         m.def_cache.insert("ls".to_string(), root.clone());
@@ -1026,6 +2035,148 @@ mod tests {
         );
     }
 
+    fn fzf_model() -> Model {
+        let root = CommandDef {
+            name: "git".to_string(),
+            flags: vec![FlagDef {
+                longhand: "no-pager".to_string(),
+                usage: "do not\tpage".to_string(),
+                ..Default::default()
+            }],
+            subcommands: vec![CommandDef {
+                name: "commit".to_string(),
+                short: "Record changes".to_string(),
+                flags: vec![FlagDef {
+                    longhand: "message".to_string(),
+                    shorthand: "m".to_string(),
+                    requires_value: true,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut m = initial_model(vec![]);
+        m.def_cache.insert("git".to_string(), root.clone());
+        m.ast = Segment::new_empty("git");
+        m.current = Some(root.clone());
+        m.build_items_from_command(&root);
+        m
+    }
+
+    #[test]
+    fn flatten_for_fzf_emits_keystroke_label_description_lines() {
+        let m = fzf_model();
+        let out = m.flatten_for_fzf();
+        let lines: Vec<Vec<&str>> = out.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(lines.len(), m.render_visible_items().len());
+        assert!(lines.iter().all(|cols| cols.len() == 3 && !cols[0].is_empty()));
+        let labels: Vec<(&str, &str)> = lines.iter().map(|c| (c[1], c[2])).collect();
+        assert!(labels.contains(&("commit", "Record changes")));
+        // tabs inside descriptions must not break the columns
+        assert!(labels.contains(&("--no-pager", "do not page")));
+    }
+
+    #[test]
+    fn apply_fzf_line_selects_the_returned_candidate() {
+        let mut m = fzf_model();
+        let out = m.flatten_for_fzf();
+        let commit = out.lines().find(|l| l.split('\t').nth(1) == Some("commit")).unwrap().to_string();
+        m.apply_fzf_line(&commit).unwrap();
+        assert_eq!(m.ast.render_preview(), "git commit");
+
+        let out = m.flatten_for_fzf();
+        let msg = out.lines().find(|l| l.contains("--message, -m")).unwrap().to_string();
+        m.apply_fzf_line(&format!("{msg}\n")).unwrap();
+        assert!(m.in_value_mode);
+        assert_eq!(m.pending_form, "--message");
+
+        assert!(m.apply_fzf_line("x\tnope\t").is_err());
+        assert!(m.apply_fzf_line("no tabs here").is_err());
+    }
+
+    #[test]
+    fn test_flag_in_local_and_persistent_flags_is_listed_once() {
+        let root = crate::carapace::parse_export(
+            r#"{"Name":"tool",
+  "LocalFlags":[{"Longhand":"verbose","Shorthand":"v","Usage":"more output"},{"Longhand":"all"}],
+  "PersistentFlags":[{"Longhand":"verbose","Shorthand":"v","Usage":"more output"},{"Longhand":"config","Type":"string"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(root.flags.len(), 4);
+        let mut m = initial_model(vec![]);
+        m.def_cache.insert("tool".to_string(), root.clone());
+        m.ast = Segment::new_empty("tool");
+        m.current = Some(root.clone());
+        m.build_items_from_command(&root);
+
+        let labels: Vec<&str> = m.items_labels().collect();
+        assert_eq!(labels.iter().filter(|l| **l == "--verbose, -v").count(), 1, "{labels:?}");
+        assert!(labels.contains(&"--config"));
+        assert_eq!(m.items.len(), 3);
+    }
+
+    #[test]
+    fn test_parent_flag_sharing_only_a_shorthand_with_a_subcommand_stays_listed() {
+        use crate::ast::build::{command, flag};
+        let commit = command("commit", vec![flag("reuse-message", "C"), flag("verbose", "v")], vec![]);
+        let root = command("git", vec![flag("", "C"), flag("verbose", "v")], vec![commit]);
+        let mut m = initial_model(vec![]);
+        m.def_cache.insert("git".to_string(), root.clone());
+        m.ast = Segment::new_empty("git");
+        m.ast.push_subcommand("commit");
+        m.build_items_from_command(&root.subcommands[0]);
+
+        let labels: Vec<&str> = m.items_labels().collect();
+        assert!(labels.contains(&"git: -C"), "{labels:?}");
+        assert!(labels.contains(&"--reuse-message, -C"), "{labels:?}");
+        // the flag both define alike is listed once, for commit
+        assert_eq!(labels.iter().filter(|l| l.contains("--verbose")).count(), 1, "{labels:?}");
+    }
+
+    #[test]
+    fn test_help_flag_synthesized_when_export_lacks_it() {
+        let root = CommandDef {
+            name: "tool".to_string(),
+            flags: vec![FlagDef {
+                longhand: "human".to_string(),
+                shorthand: "h".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut m = initial_model(vec![]);
+        m.settings.synthesize_help = true;
+        m.def_cache.insert("tool".to_string(), root.clone());
+        m.ast = Segment::new_empty("tool");
+        m.current = Some(root.clone());
+        m.build_items_from_command(&root);
+
+        let help = m.items.iter().find(|it| it.forms.contains(&"--help".to_string()));
+        let help = help.expect("--help candidate");
+        assert!(help.flag_def.as_ref().is_some_and(|f| f.synthesized));
+        // -h belongs to --human here
+        assert_eq!(help.forms, vec!["--help".to_string()]);
+        // the exported metadata is left untouched
+        assert!(m.current.as_ref().unwrap().flags.iter().all(|f| f.longhand != "help"));
+        assert!(m.def_cache["tool"].flags.iter().all(|f| f.longhand != "help"));
+
+        m.settings.synthesize_help = false;
+        m.build_items_from_command(&root);
+        assert!(m.items.iter().all(|it| !it.forms.contains(&"--help".to_string())));
+
+        // an exported --help is never duplicated
+        let mut with_help = root.clone();
+        with_help.flags.push(FlagDef {
+            longhand: "help".to_string(),
+            ..Default::default()
+        });
+        m.settings.synthesize_help = true;
+        m.build_items_from_command(&with_help);
+        let helps = m.items.iter().filter(|it| it.forms.contains(&"--help".to_string()));
+        assert_eq!(helps.count(), 1);
+    }
+
     #[test]
     fn test_all_ambiguous_choices_selectable_via_acekeys() {
         let subs = vec!["chcpu", "chgrp", "chroot", "chpasswd"];
@@ -1035,6 +2186,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         let mut scs = vec![];
         for s in &subs {
@@ -1044,6 +2196,7 @@ mod tests {
                 aliases: vec![],
                 flags: vec![],
                 subcommands: vec![],
+                ..Default::default()
             });
         }
         root.subcommands = scs.clone();
@@ -1154,5 +2307,78 @@ mod tests {
 
     }
 
+    #[test]
+    fn keystroke_paths_select_each_prompt_list_item() {
+        let items = ["hello","test","cp","cal","cat","cut","chsh","code","comm","curl","cargo","chcpu","chgrp","chmod","chown","cksum","cfdisk","chroot","csplit","carapace","chpasswd","cargo-fmt","coredumpctl","cargo-clippy"];
+        let root = CommandDef {
+            name: "root".to_string(),
+            subcommands: items.iter().map(|s| CommandDef { name: s.to_string(), ..Default::default() }).collect(),
+            ..Default::default()
+        };
+        let fresh = || {
+            let mut m = initial_model(vec![]);
+            m.ast = Segment::new_empty("root");
+            m.current = Some(root.clone());
+            m.def_cache.insert("root".to_string(), root.clone());
+            m.build_items_from_command(&root);
+            m
+        };
+        for item in items {
+            let path = fresh().keystroke_path_to(item).unwrap_or_else(|| panic!("no path to {item}"));
+            let mut m = fresh();
+            for c in path.chars() {
+                m.update(crate::ui::Msg::Rune(c));
+            }
+            assert_eq!(m.ast.top().map(|n| n.name.as_str()), Some(item), "typing {path:?}");
+            assert!(path.len() <= item.len(), "{path:?} is longer than typing {item} out");
+        }
+        // an unambiguous name takes a single key; an unknown one has no path
+        assert_eq!(fresh().keystroke_path_to("hello").as_deref(), Some("h"));
+        assert_eq!(fresh().keystroke_path_to("nope"), None);
+    }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_load_command_line_honors_quotes_and_inline_values() {
+        let script = r#"case "$1" in
+  git) echo '{"Name":"git","Commands":[{"Name":"commit","LocalFlags":[{"Longhand":"message","Shorthand":"m","Type":"string"},{"Longhand":"author","Type":"string"}]}]}' ;;
+  *) exit 1 ;;
+esac"#;
+        crate::carapace::fake::with_fake_carapace(script, || {
+            let mut m = initial_model(vec![]);
+            m.load_command_line(r#"git commit -m "fix the thing" --author='A. U. Thor' my\ file"#)
+                .unwrap();
+            let top = m.ast.top().unwrap();
+            assert_eq!(top.name, "commit");
+            let flags: Vec<(&str, &str)> = top
+                .flags
+                .iter()
+                .map(|f| (f.form.as_str(), f.value.as_str()))
+                .collect();
+            assert_eq!(flags, vec![("-m", "fix the thing"), ("--author", "A. U. Thor")]);
+            assert_eq!(top.positionals, vec!["my file".to_string()]);
+        });
+    }
+
+    #[test]
+    fn test_load_command_line_round_trips_value_flags() {
+        let script = r#"case "$1" in
+  git) echo '{"Name":"git","Commands":[{"Name":"commit","LocalFlags":[{"Longhand":"message","Shorthand":"m","Type":"string"},{"Longhand":"amend"}]}]}' ;;
+  *) exit 1 ;;
+esac"#;
+        crate::carapace::fake::with_fake_carapace(script, || {
+            for line in ["git commit -m hello", "git commit -m 'fix the bug' --amend", "git commit --message -x"] {
+                let mut m = initial_model(vec![]);
+                m.load_command_line(line).unwrap();
+                assert!(m.ast.top().unwrap().positionals.is_empty(), "{line}");
+                assert_eq!(m.preview_text(), line);
+            }
+            // a value attached to the shorthand is reattached to that flag
+            let mut m = initial_model(vec![]);
+            m.load_command_line("git commit -mhello").unwrap();
+            let top = m.ast.top().unwrap();
+            assert_eq!((top.flags[0].form.as_str(), top.flags[0].value.as_str()), ("-m", "hello"));
+            assert_eq!(m.preview_text(), "git commit -m hello");
+        });
+    }
 }
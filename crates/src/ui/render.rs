@@ -1,6 +1,7 @@
 // Render module split into focused submodules to reduce file size and compiler warnings.
 
 pub mod decorate;
+pub mod explain;
 pub mod full;
 pub mod list;
 pub mod modeline;
@@ -8,8 +9,12 @@ pub mod preview;
 pub mod styles;
 pub mod util;
 
-pub use decorate::tested_string;
-pub use full::render_full;
-pub use list::{assigned_map, render_list_content, render_main_content, render_visible_items};
+pub use decorate::{decorate_typed, decorate_typed_default, tested_string};
+pub use full::{render_full, render_full_to};
+pub use list::{
+    assigned_map, list_row_count, render_list_content, render_list_content_to, render_main_content,
+    render_main_content_to, render_visible_items,
+};
 pub use modeline::{render_modeline, render_modeline_padded};
-pub use preview::{render_preview, render_preview_block};
+pub use preview::{render_embed, render_preview, render_preview_block};
+pub use util::{strip_ansi, visible_lines};
@@ -1,4 +1,5 @@
 use crate::ui::render::styles::{STYLE_ACE, STYLE_TYPED};
+use lipgloss::Style;
 use std::collections::HashMap;
 
 fn collect_candidate_runes(form: &str) -> (Vec<char>, Vec<usize>) {
@@ -14,6 +15,11 @@ fn collect_candidate_runes(form: &str) -> (Vec<char>, Vec<usize>) {
 }
 
 pub fn decorate_form(form: &str, typed: &str, assigned_seq: String) -> String {
+    decorate_form_styled(form, typed, assigned_seq, None)
+}
+
+// `decorate_form`, optionally rendering the left-unit (first rune, or `--`) in `left_unit`
+pub fn decorate_form_styled(form: &str, typed: &str, assigned_seq: String, left_unit: Option<&Style>) -> String {
     let (candidate_runes, candidate_pos) = collect_candidate_runes(form);
 
     let mut assigned_pos: Vec<usize> = Vec::new();
@@ -87,6 +93,12 @@ pub fn decorate_form(form: &str, typed: &str, assigned_seq: String) -> String {
         .map(|(ord, idx)| (idx, ord))
         .collect();
 
+    // runes of the shared left-unit that carry no ace highlight get the optional left-unit style
+    let lu_len = crate::acekey::leftmost_unit(form).len();
+    let push_plain = |out: &mut String, byte_idx: usize, ch: char| match left_unit {
+        Some(style) if byte_idx < lu_len => out.push_str(&style.render(&ch.to_string())),
+        _ => out.push(ch),
+    };
     for (byte_idx, ch) in form.char_indices() {
         if crate::acekey::is_ace_rune(ch) {
             let cidx_opt = candidate_pos.iter().position(|&p| p == byte_idx);
@@ -96,7 +108,7 @@ pub fn decorate_form(form: &str, typed: &str, assigned_seq: String) -> String {
                         if ord == 0 {
                             out.push_str(&STYLE_ACE.render(&ch.to_string()));
                         } else {
-                            out.push(ch);
+                            push_plain(&mut out, byte_idx, ch);
                         }
                         continue;
                     }
@@ -108,20 +120,43 @@ pub fn decorate_form(form: &str, typed: &str, assigned_seq: String) -> String {
                         out.push_str(&STYLE_ACE.render(&ch.to_string()));
                         continue;
                     }
-                    out.push(ch);
+                    push_plain(&mut out, byte_idx, ch);
                 } else {
-                    out.push(ch);
+                    push_plain(&mut out, byte_idx, ch);
                 }
             } else {
-                out.push(ch);
+                push_plain(&mut out, byte_idx, ch);
             }
         } else {
-            out.push(ch);
+            push_plain(&mut out, byte_idx, ch);
         }
     }
     out
 }
 
+// Style the typed buffer itself: the left-unit (first rune, or `--`) that picks the candidate
+// group in `left`, the disambiguator runes typed after it in `ace`. Numeric input is a plain
+// index, so it is rendered entirely in `left`.
+pub fn decorate_typed(typed_raw: &str, numeric: bool, left: &Style, ace: &Style) -> String {
+    if typed_raw.is_empty() {
+        return String::new();
+    }
+    if numeric {
+        return left.render(typed_raw);
+    }
+    let lu = crate::acekey::leftmost_unit(typed_raw);
+    let rest = &typed_raw[lu.len()..];
+    let mut out = left.render(&lu);
+    if !rest.is_empty() {
+        out.push_str(&ace.render(rest));
+    }
+    out
+}
+
+pub fn decorate_typed_default(typed_raw: &str, numeric: bool) -> String {
+    decorate_typed(typed_raw, numeric, &STYLE_TYPED, &STYLE_ACE)
+}
+
 pub fn tested_string(s: &str) -> String {
     s.to_string()
 }
@@ -140,4 +175,38 @@ mod tests {
         let out2 = decorate_form("wc", "w", assigned);
         assert!(out2.contains(&crate::ui::render::styles::STYLE_ACE.render("w")));
     }
+
+    #[test]
+    fn left_unit_style_applies_only_when_enabled() {
+        use crate::ui::render::styles::STYLE_LEFT_UNIT;
+        // "s" disambiguates; "l" is the shared left-unit
+        let on = decorate_form_styled("ls", "", "s".to_string(), Some(&STYLE_LEFT_UNIT));
+        assert!(on.starts_with(&STYLE_LEFT_UNIT.render("l")), "got: {on:?}");
+        assert!(on.contains(&STYLE_ACE.render("s")));
+
+        let off = decorate_form("ls", "", "s".to_string());
+        assert!(off.starts_with('l'), "got: {off:?}");
+        assert!(!off.contains(&STYLE_LEFT_UNIT.render("l")));
+
+        // long flags share `--`; an ace highlight on the left-unit rune still wins
+        let long = decorate_form_styled("--all", "", "a".to_string(), Some(&STYLE_LEFT_UNIT));
+        assert!(long.starts_with(&STYLE_LEFT_UNIT.render("-").repeat(2)), "got: {long:?}");
+        let ace_first = decorate_form_styled("git", "", "g".to_string(), Some(&STYLE_LEFT_UNIT));
+        assert!(ace_first.starts_with(&STYLE_ACE.render("g")), "got: {ace_first:?}");
+    }
+
+    #[test]
+    fn typed_display_styles_left_unit_and_disambiguators() {
+        let mut m = crate::ui::initial_model(vec![]);
+        m.typed_raw = "--vB".to_string();
+        m.typed = "--vb".to_string();
+        let out = m.typed_display();
+        assert_eq!(crate::ui::render::strip_ansi(&out), "--vB");
+        assert!(out.contains(&STYLE_TYPED.render("--")));
+        assert!(out.contains(&STYLE_ACE.render("vB")));
+
+        m.typed_raw = "12".to_string();
+        m.numeric_baseline = Some(vec![0]);
+        assert_eq!(m.typed_display(), STYLE_TYPED.render("12"));
+    }
 }
@@ -0,0 +1,104 @@
+use crate::ui::model::{EXPLAIN_LINES, Model};
+use crate::ui::render::styles::{STYLE_DESC, STYLE_LABEL};
+use crate::ui::render::util::normalize_and_pad_into;
+
+// `text` broken at spaces into lines of at most `width` chars; a word longer than that is cut
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while !word.is_empty() {
+            let len = line.chars().count();
+            let sep = usize::from(len > 0);
+            if len + sep + word.len() <= width {
+                if sep == 1 {
+                    line.push(' ');
+                }
+                line.extend(word.drain(..));
+            } else if len > 0 {
+                lines.push(std::mem::take(&mut line));
+            } else {
+                lines.push(word.drain(..width.max(1)).collect());
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// The explain panel: the highlighted candidate's label, then its whole description (the
+// extended usage for flags that have one) wrapped to the width, in exactly EXPLAIN_LINES lines
+pub fn render_explain_to(m: &Model, out: &mut String) {
+    let width = m.render_width();
+    let mut lines = vec![];
+    match m.highlighted_item() {
+        Some(it) => {
+            let text = match &it.flag_def {
+                Some(fd) if !fd.long_usage.is_empty() => fd.long_usage.clone(),
+                Some(fd) => fd.usage.clone(),
+                None => it.short.clone(),
+            };
+            lines.push(STYLE_LABEL.render(&format!("── {} ──", it.label)));
+            let mut text = wrap_words(&text, width);
+            if text.len() >= EXPLAIN_LINES {
+                text.truncate(EXPLAIN_LINES - 1);
+                if let Some(last) = text.last_mut() {
+                    let keep = last.chars().count().min(width.saturating_sub(1));
+                    *last = last.chars().take(keep).collect::<String>() + "…";
+                }
+            }
+            if text.is_empty() {
+                text.push("(no description)".to_string());
+            }
+            lines.extend(text.iter().map(|l| STYLE_DESC.render(l)));
+        }
+        None => lines.push(STYLE_DESC.render("(nothing to explain)")),
+    }
+    normalize_and_pad_into(lines.iter().map(String::as_str), width, EXPLAIN_LINES, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{CommandDef, FlagDef};
+    use crate::ui::Msg;
+    use crate::ui::render::strip_ansi;
+
+    #[test]
+    fn explain_panel_shows_the_whole_usage_in_place_of_list_rows() {
+        let usage = "Overwrite existing files in the destination without asking, even when they are newer than the source";
+        let def = CommandDef {
+            name: "copy".to_string(),
+            flags: vec![
+                FlagDef { longhand: "force".to_string(), usage: usage.to_string(), ..Default::default() },
+                FlagDef { longhand: "quiet".to_string(), usage: "less output".to_string(), ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let (w, h) = (40usize, 14usize);
+        let mut m = crate::ui::initial_model(vec![]);
+        m.update(Msg::WindowSize { width: w, height: h });
+        m.ast = crate::ast::Segment::new_empty("copy");
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+        let rows = m.per_page;
+
+        m.update(Msg::ToggleExplain);
+        assert_eq!(m.per_page, rows - crate::ui::model::EXPLAIN_LINES);
+        let frame = strip_ansi(&m.render_full());
+        let lines: Vec<&str> = frame.lines().collect();
+        assert_eq!(lines.len(), h, "{frame}");
+        assert!(lines.iter().all(|l| l.chars().count() == w), "{frame}");
+        // the panel sits between the list and the modeline
+        let panel = &lines[h - 1 - crate::ui::model::EXPLAIN_LINES..h - 1];
+        assert!(panel[0].contains("── --force ──"), "{frame}");
+        let text = panel[1..].iter().flat_map(|l| l.split_whitespace()).collect::<Vec<_>>().join(" ");
+        assert_eq!(text, usage);
+
+        m.update(Msg::ToggleExplain);
+        assert_eq!(m.per_page, rows);
+        assert!(!strip_ansi(&m.render_full()).contains("── --force ──"));
+    }
+}
@@ -1,25 +1,217 @@
 use crate::ui::model::Model;
 
 pub fn render_full(m: &Model) -> String {
-    let mut lines = m.render_preview_block();
-    lines.extend(m.render_main_content().lines().map(str::to_string));
-    let first_line = crate::ui::render::modeline::render_modeline_padded(m)
-        .lines()
-        .next()
-        .unwrap_or("")
-        .to_string();
-    lines.push(first_line);
-    lines.join("\n")
+    let mut out = String::new();
+    render_full_to(m, &mut out);
+    out
+}
+
+// Renders the frame into `out` (cleared first) so a caller can reuse one buffer across frames.
+// Output is byte-identical to `render_full`.
+pub fn render_full_to(m: &Model, out: &mut String) {
+    out.clear();
+    crate::ui::render::preview::render_preview_block_to(m, out);
+    // main content may be empty (no rows): drop the separator again in that case
+    let before = out.len();
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    let start = out.len();
+    crate::ui::render::list::render_main_content_to(m, out);
+    if out.len() == start {
+        out.truncate(before);
+    }
+    if m.explain {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        crate::ui::render::explain::render_explain_to(m, out);
+    }
+    let modeline = crate::ui::render::modeline::render_modeline_padded(m);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(modeline.lines().next().unwrap_or(""));
 }
 
 #[cfg(test)]
 mod tests {
-    use regex::Regex;
+    use crate::ui::render::strip_ansi;
+
+    // Frames (ANSI stripped) from the line-joining `render_full` that `render_full_to` replaced,
+    // at 40x8 with thirty commands listed
+    const LISTED: [&str; 8] = [
+        "╭──────────────────────────────────────╮",
+        "│ > type or pick a command             │",
+        "╰──────────────────────────────────────╯",
+        "  1 │ cmd0  description 0               ",
+        "  2 │ cmd1  description 1               ",
+        "  3 │ cmd2  description 2               ",
+        "  4 │ cmd3  description 3               ",
+        "  A  van  | arg:␣  run:⏎   Page 1/8 ↑/↓ ",
+    ];
+    const TYPED: [&str; 8] = [
+        "╭──────────────────────────────────────╮",
+        "│ > type or pick a command             │",
+        "╰──────────────────────────────────────╯",
+        "  1 │ cmd0  description 0               ",
+        "  2 │ cmd1  description 1               ",
+        "  3 │ cmd2  description 2               ",
+        "  4 │ cmd3  description 3               ",
+        "  A  Typed: c  | arg:␣  run:⏎Page 1/8 ↑ ",
+    ];
+    const VALUE_MODE: [&str; 8] = [
+        "╭──────────────────────────────────────╮",
+        "│ > type or pick a command             │",
+        "╰──────────────────────────────────────╯",
+        "Value input: abc [3]                    ",
+        "Press Enter to confirm, Esc to cancel   ",
+        "                                        ",
+        "                                        ",
+        "  A  Typed: c  | arg:␣  run:⏎Page 1/8 ↑ ",
+    ];
+
+    #[test]
+    fn render_full_to_matches_the_frames_of_the_joined_renderer() {
+        let entries = (0..30).map(|i| (format!("cmd{i}"), format!("description {i}"))).collect();
+        let mut m = crate::ui::initial_model(entries);
+        m.update(crate::ui::Msg::WindowSize { width: 40, height: 8 });
+        let mut buf = String::from("stale contents from a previous frame");
+        let mut check = |m: &crate::ui::Model, golden: &[&str], what: &str| {
+            m.render_full_to(&mut buf);
+            assert_eq!(strip_ansi(&buf).split('\n').collect::<Vec<_>>(), golden, "{what}");
+            assert_eq!(buf, m.render_full(), "{what}");
+        };
+        check(&m, &LISTED, "listed");
+        // filtered and value-mode frames go through the same buffer
+        m.update(crate::ui::Msg::Rune('c'));
+        check(&m, &TYPED, "typed");
+        m.in_value_mode = true;
+        m.pending_value = "abc".to_string();
+        check(&m, &VALUE_MODE, "value mode");
+    }
+
+    #[test]
+    fn render_full_keeps_exact_dimensions_at_pathological_widths() {
+        // rows far wider than the terminal must not wrap onto extra lines
+        for w in [7usize, 13, 500] {
+            let entries = (0..40).map(|i| (format!("command-{i}"), "a rather long description".repeat(3))).collect();
+            let mut m = crate::ui::initial_model(entries);
+            m.update(crate::ui::Msg::WindowSize { width: w, height: 12 });
+            m.update(crate::ui::Msg::KeyDown);
+            let stripped = strip_ansi(&m.render_full());
+            let lines: Vec<&str> = stripped.lines().collect();
+            assert_eq!(lines.len(), 12, "height mismatch at width {w}\n{stripped}");
+            for (i, line) in lines.iter().enumerate() {
+                assert_eq!(line.chars().count(), w, "width mismatch at line {i} for width {w}: `{line}`");
+            }
+        }
+    }
+
+    #[test]
+    fn render_full_keeps_exact_dimensions_at_zero_and_one_column() {
+        // a terminal reporting no width renders at DEFAULT_WIDTH; one column stays one column
+        for (w, expect) in [(0usize, crate::ui::model::DEFAULT_WIDTH), (1, 1), (2, 2), (4, 4), (5, 5)] {
+            let entries = (0..20).map(|i| (format!("command-{i}"), "description".to_string())).collect();
+            let mut m = crate::ui::initial_model(entries);
+            m.update(crate::ui::Msg::WindowSize { width: w, height: 10 });
+            m.ast = crate::ast::Segment::new_empty("git");
+            m.ast.push_subcommand("commit");
+            let stripped = strip_ansi(&m.render_full());
+            let lines: Vec<&str> = stripped.lines().collect();
+            assert_eq!(lines.len(), 10, "height mismatch at width {w}\n{stripped}");
+            for (i, line) in lines.iter().enumerate() {
+                assert_eq!(line.chars().count(), expect, "width mismatch at line {i} for width {w}: `{line}`");
+            }
+            assert!(lines[0].starts_with('>') || lines[0].starts_with('╭'), "preview first at width {w}: `{}`", lines[0]);
+        }
+    }
+
+    #[test]
+    fn deep_stacks_keep_the_innermost_command_in_view_within_the_terminal() {
+        use crate::ast::build::{command, switch};
+        use crate::source::{Source, StaticSource};
+        // six levels, each with its own flag and a shared --verbose
+        let mut def = crate::ast::CommandDef::default();
+        for level in (0..6).rev() {
+            let sub = std::mem::take(&mut def);
+            let subcommands = if level < 5 { vec![sub] } else { vec![] };
+            def = command(&format!("level{level}"), vec![switch(&format!("opt{level}")), switch("verbose")], subcommands);
+        }
+        let (w, h) = (40usize, 12usize);
+        let mut m = crate::ui::initial_model(vec![]);
+        m.source = Source::new(StaticSource::new(vec![def]));
+        m.update(crate::ui::Msg::WindowSize { width: w, height: h });
+        let args: Vec<String> = "level0 level1 level2 level3 level4 level5 --opt5".split(' ').map(String::from).collect();
+        crate::ui::run::load_args(&mut m, &args).unwrap();
+
+        let stripped = strip_ansi(&m.render_full());
+        let lines: Vec<&str> = stripped.lines().collect();
+        assert_eq!(lines.len(), h, "{stripped}");
+        assert!(lines.iter().all(|l| l.chars().count() == w), "{stripped}");
+        // the preview line shows the end of the command, cut at the start
+        assert!(lines[1].contains("> …") && lines[1].contains("level5 --opt5"), "{stripped}");
 
-    // helper to strip ANSI CSI sequences from rendered output for assertions
-    fn strip_ansi(s: &str) -> String {
-        let re = Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]").unwrap();
-        re.replace_all(s, "").to_string()
+        // --verbose is offered once, for the innermost command
+        let verbose: Vec<usize> = m.items.iter().filter(|it| it.forms.contains(&"--verbose".to_string())).map(|it| it.depth).collect();
+        assert_eq!(verbose, vec![5]);
+        assert_eq!(m.items.iter().filter(|it| it.kind == "flag").count(), 7);
+    }
+
+    #[test]
+    fn multi_line_descriptions_render_on_one_row() {
+        use crate::ast::{CommandDef, FlagDef};
+        let def = CommandDef {
+            name: "tool".to_string(),
+            flags: vec![FlagDef {
+                longhand: "force".to_string(),
+                usage: "overwrite files\nwithout asking\n\n(dangerous)".to_string(),
+                ..Default::default()
+            }],
+            subcommands: vec![CommandDef {
+                name: "sync".to_string(),
+                short: "sync state\r\nacross hosts".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let (w, h) = (100usize, 12usize);
+        let mut m = crate::ui::initial_model(vec![]);
+        m.update(crate::ui::Msg::WindowSize { width: w, height: h });
+        m.ast = crate::ast::Segment::new_empty("tool");
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+
+        let visible = m.render_visible_items();
+        let list = strip_ansi(&m.render_list_content(&visible));
+        assert_eq!(list.lines().count(), visible.len(), "{list}");
+        assert!(list.contains("overwrite files without asking (dangerous)"), "{list}");
+        assert!(list.contains("sync state across hosts"), "{list}");
+
+        let stripped = strip_ansi(&m.render_full());
+        let lines: Vec<&str> = stripped.lines().collect();
+        assert_eq!(lines.len(), h, "{stripped}");
+        for (i, line) in lines.iter().enumerate() {
+            assert_eq!(line.chars().count(), w, "width mismatch at line {i}: `{line}`");
+        }
+    }
+
+    #[test]
+    fn extra_reserved_lines_shrink_the_page_and_the_frame() {
+        let entries: Vec<(String, String)> = (0..50).map(|i| (format!("cmd{i}"), String::new())).collect();
+        let mut m = crate::ui::initial_model(entries.clone());
+        m.update(crate::ui::Msg::WindowSize { width: 60, height: 24 });
+        assert_eq!(m.per_page, 20);
+
+        // an embedder drawing a 2-line header and 1-line footer
+        let mut m = crate::ui::initial_model(entries);
+        m.reserved_lines += 3;
+        m.update(crate::ui::Msg::WindowSize { width: 60, height: 24 });
+        assert_eq!(m.per_page, 17);
+        let stripped = strip_ansi(&m.render_full());
+        let lines: Vec<&str> = stripped.lines().collect();
+        assert_eq!(lines.len(), 21, "{stripped}");
+        assert!(lines.iter().all(|l| l.chars().count() == 60));
     }
 
     #[test]
@@ -1,9 +1,9 @@
 use crate::acekey::assign_ace_keys;
 use crate::ui::model::leading_hyphen_count;
-use crate::ui::model::{ChooseItem, DEFAULT_WIDTH, Model};
-use crate::ui::render::decorate::decorate_form;
-use crate::ui::render::styles::{STYLE_DESC, STYLE_LABEL, STYLE_LINENUM};
-use crate::ui::render::util::normalize_and_pad;
+use crate::ui::model::{ChooseItem, Model};
+use crate::ui::render::decorate::decorate_form_styled;
+use crate::ui::render::styles::{STYLE_DESC, STYLE_LABEL, STYLE_LEFT_UNIT, STYLE_LINENUM, STYLE_SELECTED, STYLE_WARNING};
+use crate::ui::render::util::{first_line, normalize_and_pad_into, one_line};
 use std::collections::{HashMap, HashSet};
 
 // Collect forms in baseline order for a numeric baseline subset
@@ -91,6 +91,8 @@ fn render_visible_items_alpha(m: &Model) -> Vec<ChooseItem> {
         }
     } else if m.typed.is_empty() {
         visible_forms = forms.into_iter().collect();
+    } else {
+        return m.description_matches();
     }
 
     m.items
@@ -116,14 +118,54 @@ fn compute_gutter_width(total: usize) -> usize {
     usize::max(gw, 3)
 }
 
+// Widths of the columns left of each label: the ace keys (when shown in a column) and the numbers
+#[derive(Clone, Copy)]
+struct Gutter {
+    key: Option<usize>,
+    num: usize,
+}
+
+impl Gutter {
+    // columns taken before the label, separators included
+    fn width(self) -> usize {
+        self.key.map_or(0, |k| k + 3) + self.num + 3
+    }
+}
+
 fn format_num_str(num: usize, gutter_width: usize) -> String {
     format!("{:>1$} │ ", num, gutter_width)
 }
 
+// The number shown for (and typed to select) the baseline's `pos`-th candidate, item
+// `orig_idx`: its place in the whole list, or with per-page numbers in the page it was on
+pub fn candidate_number(m: &Model, pos: usize, orig_idx: usize) -> usize {
+    if m.settings.per_page_numbers { pos + 1 } else { orig_idx + 1 }
+}
+
+// Indices into `visible` of the candidates on the current page
+pub fn page_item_range(m: &Model, visible: &[ChooseItem]) -> std::ops::Range<usize> {
+    let rows = group_rows(m, visible.iter());
+    let per = if m.per_page == 0 { rows.len() } else { m.per_page };
+    let items: Vec<usize> = rows
+        .iter()
+        .skip(m.page.saturating_mul(per))
+        .take(per)
+        .filter_map(|r| if let Row::Item(k) = r { Some(*k) } else { None })
+        .collect();
+    match (items.first(), items.last()) {
+        (Some(&first), Some(&last)) => first..last + 1,
+        _ => 0..0,
+    }
+}
+
 // Build baseline numbers and order when numeric baseline is active
 fn build_baseline(m: &Model) -> Option<(Vec<String>, Vec<usize>)> {
     if let Some(nb) = &m.numeric_baseline {
-        let v: Vec<String> = nb.iter().map(|&orig_idx| (orig_idx + 1).to_string()).collect();
+        let v: Vec<String> = nb
+            .iter()
+            .enumerate()
+            .map(|(pos, &orig_idx)| candidate_number(m, pos, orig_idx).to_string())
+            .collect();
         if v.is_empty() {
             None
         } else {
@@ -135,12 +177,11 @@ fn build_baseline(m: &Model) -> Option<(Vec<String>, Vec<usize>)> {
 }
 
 // Given a baseline order and typed buffer, produce positions to render (vis_pos, orig_idx)
-fn collect_numeric_positions(nb_order: &[usize], typed: &str) -> Vec<(usize, usize)> {
+fn collect_numeric_positions(nb_order: &[usize], nums: &[String], typed: &str) -> Vec<(usize, usize)> {
     let mut positions = Vec::new();
     if !typed.is_empty() && typed.chars().all(|c| c.is_ascii_digit()) {
         for (vis_pos, &orig_idx) in nb_order.iter().enumerate() {
-            let num = (orig_idx + 1).to_string();
-            if num.starts_with(typed) {
+            if nums.get(vis_pos).is_some_and(|num| num.starts_with(typed)) {
                 positions.push((vis_pos, orig_idx));
             }
         }
@@ -152,13 +193,24 @@ fn collect_numeric_positions(nb_order: &[usize], typed: &str) -> Vec<(usize, usi
     positions
 }
 
+// The keys that select `it`: the assignment of its first form that has one
+pub fn item_keystroke(it: &ChooseItem, assigned: &HashMap<String, String>) -> String {
+    it.forms
+        .iter()
+        .filter_map(|f| assigned.get(f))
+        .find(|k| !k.is_empty())
+        .cloned()
+        .unwrap_or_default()
+}
+
 fn build_label(it: &ChooseItem, assigned: &HashMap<String, String>, t_hyph: usize, m: &Model) -> Option<String> {
     let mut parts = Vec::new();
     for f in &it.forms {
         if t_hyph >= 2 && leading_hyphen_count(f) < t_hyph {
             continue;
         }
-        parts.push(decorate_form(f, &m.typed_raw, assigned.get(f).cloned().unwrap_or_default()));
+        let left_unit = m.settings.style_left_unit.then_some(&*STYLE_LEFT_UNIT);
+        parts.push(decorate_form_styled(f, &m.typed_raw, assigned.get(f).cloned().unwrap_or_default(), left_unit));
     }
     if parts.is_empty() {
         None
@@ -172,7 +224,9 @@ fn flag_suffix(it: &ChooseItem, m: &Model) -> Vec<String> {
     if let Some(fd) = &it.flag_def {
         if fd.requires_value {
             let mut placeholder = "VALUE".to_string();
-            if !fd.longhand.is_empty() {
+            if !fd.value_hint.is_empty() {
+                placeholder = format!("<{}>", fd.value_hint);
+            } else if !fd.longhand.is_empty() {
                 placeholder = fd.longhand.to_uppercase();
             } else if !fd.shorthand.is_empty() {
                 placeholder = fd.shorthand.to_uppercase();
@@ -182,8 +236,12 @@ fn flag_suffix(it: &ChooseItem, m: &Model) -> Vec<String> {
         } else {
             suffix.push(STYLE_DESC.render("  "));
         }
-        if !fd.usage.is_empty() {
-            suffix.push(STYLE_DESC.render(&fd.usage));
+        let usage = if m.long_usage && !fd.long_usage.is_empty() { &fd.long_usage } else { &fd.usage };
+        if !usage.is_empty() {
+            suffix.push(STYLE_DESC.render(&one_line(usage)));
+        }
+        if fd.synthesized {
+            suffix.push(STYLE_DESC.render(" (assumed)"));
         }
         let top_depth = m.ast.stack.len().saturating_sub(1);
         if it.depth < top_depth && it.depth < m.ast.stack.len() {
@@ -207,20 +265,58 @@ fn cmd_suffix(it: &ChooseItem) -> Option<String> {
     if short_ref.is_empty() {
         None
     } else {
-        Some(STYLE_DESC.render(&format!("  {short_ref}")))
+        Some(STYLE_DESC.render(&format!("  {}", one_line(short_ref))))
     }
 }
 
-// Render a single ChooseItem into a line (without trailing newline). Returns None when nothing should be rendered.
-fn render_item_line(
-    it: &ChooseItem,
-    assigned: &HashMap<String, String>,
+// What every row of a frame consults, worked out once per render
+struct RowContext<'a> {
+    assigned: &'a HashMap<String, String>,
     t_hyph: usize,
-    num_str: String,
-    m: &Model,
-) -> Option<String> {
-    let label = build_label(it, assigned, t_hyph, m)?;
-    let mut line_pieces: Vec<String> = vec![STYLE_LINENUM.render(&num_str), STYLE_LABEL.render(&label)];
+    gutter: Gutter,
+    // columns the list takes, headers being ruled out to it
+    width: usize,
+    // the flags given together against `exclusive_flags` (see `Model::conflicting_flags`)
+    conflicts: Vec<(usize, Vec<String>)>,
+}
+
+impl RowContext<'_> {
+    fn flag_conflicts(&self, it: &ChooseItem) -> bool {
+        it.kind == "flag"
+            && self
+                .conflicts
+                .iter()
+                .any(|(depth, forms)| *depth == it.depth && forms.iter().any(|f| it.forms.contains(f)))
+    }
+}
+
+// Render a single ChooseItem into a line (without trailing newline). Returns None when nothing should be rendered.
+fn render_item_line(it: &ChooseItem, num_str: String, ctx: &RowContext, m: &Model) -> Option<String> {
+    let assigned = ctx.assigned;
+    let label = build_label(it, assigned, ctx.t_hyph, m)?;
+    let mut line_pieces: Vec<String> = Vec::new();
+    if let Some(w) = ctx.gutter.key {
+        let key = item_keystroke(it, assigned);
+        line_pieces.push(STYLE_LINENUM.render(&format!("{key:<w$} │ ")));
+    }
+    line_pieces.push(STYLE_LINENUM.render(&num_str));
+    if m.settings.show_keystrokes {
+        let key = item_keystroke(it, assigned);
+        if !key.is_empty() {
+            line_pieces.push(STYLE_LINENUM.render(&format!("[{key}] ")));
+        }
+    }
+    // already-added flags are marked; selecting them again removes them. Those excluding each
+    // other are marked as a warning instead.
+    if ctx.flag_conflicts(it) {
+        line_pieces.push(STYLE_WARNING.render(&label));
+        line_pieces.push(STYLE_WARNING.render(" ⚠"));
+    } else if m.flag_is_selected(it) {
+        line_pieces.push(STYLE_SELECTED.render(&label));
+        line_pieces.push(STYLE_SELECTED.render(" ✓"));
+    } else {
+        line_pieces.push(STYLE_LABEL.render(&label));
+    }
     line_pieces.extend(flag_suffix(it, m));
     if let Some(s) = cmd_suffix(it) {
         line_pieces.push(s);
@@ -228,129 +324,285 @@ fn render_item_line(
     Some(line_pieces.join(""))
 }
 
+// In sectioned mode the list is a sequence of rows: faint per-command headers over each group
+// of flags, and items (by position in the sequence being rendered). Headers take a line but
+// no number, so numbering and ace keys still refer to items only.
+enum Row {
+    Header(String),
+    Item(usize),
+}
+
+fn group_rows<'a>(m: &Model, items: impl Iterator<Item = &'a ChooseItem>) -> Vec<Row> {
+    let items: Vec<&ChooseItem> = items.collect();
+    let first_flag_depth = items.iter().find(|it| it.kind == "flag").map(|it| it.depth);
+    // a single origin needs no headers
+    let grouped = m.settings.group_flags
+        && items
+            .iter()
+            .any(|it| it.kind == "flag" && Some(it.depth) != first_flag_depth);
+    let mut rows = Vec::with_capacity(items.len());
+    let mut last_depth = None;
+    for (k, it) in items.iter().enumerate() {
+        if grouped && it.kind == "flag" && last_depth != Some(it.depth) {
+            last_depth = Some(it.depth);
+            let origin = m.ast.stack.get(it.depth).map(|n| n.name.clone()).unwrap_or_default();
+            rows.push(Row::Header(origin));
+        }
+        rows.push(Row::Item(k));
+    }
+    rows
+}
+
+// Number of list lines the visible items take up (items plus any section headers)
+pub fn list_row_count(m: &Model, visible: &[ChooseItem]) -> usize {
+    if !m.settings.group_flags {
+        return visible.len();
+    }
+    group_rows(m, visible.iter()).len()
+}
+
+// A section header ruled out to the list's width, as wide as the item rows beside it
+fn header_line(origin: &str, ctx: &RowContext) -> String {
+    let pad = " ".repeat(ctx.gutter.width());
+    let title = format!("── {origin} ");
+    let rule = "─".repeat(ctx.width.saturating_sub(ctx.gutter.width() + title.chars().count()).max(2));
+    format!("{pad}{}", STYLE_DESC.render(&format!("{title}{rule}")))
+}
+
 // Render when numeric baseline is active
-fn render_numeric_content(m: &Model, assigned: &HashMap<String, String>, bs: &Vec<String>, nb_order: &Vec<usize>, t_hyph: usize, gutter_width: usize) -> String {
-    let mut b = String::new();
-    let positions = collect_numeric_positions(nb_order, &m.typed_raw);
+fn render_numeric_content(m: &Model, b: &mut String, bs: &Vec<String>, nb_order: &Vec<usize>, ctx: &RowContext) {
+    let gutter = ctx.gutter;
+    let positions = collect_numeric_positions(nb_order, bs, &m.typed_raw);
     if positions.is_empty() {
-        return b;
+        return;
     }
-    let total_positions = positions.len();
-    let per_page = if m.per_page == 0 { total_positions } else { m.per_page };
-    let start_pos = m.page.saturating_mul(per_page);
-    let end_pos = usize::min(start_pos + per_page, total_positions);
+    let rows = group_rows(m, positions.iter().filter_map(|&(_, orig_idx)| m.items.get(orig_idx)));
+    let per_page = if m.per_page == 0 { rows.len() } else { m.per_page };
 
-    for pos_idx in start_pos..end_pos {
+    for row in rows.iter().skip(m.page.saturating_mul(per_page)).take(per_page) {
+        let pos_idx = match row {
+            Row::Header(origin) => {
+                b.push_str(&header_line(origin, ctx));
+                b.push('\n');
+                continue;
+            }
+            Row::Item(k) => *k,
+        };
         let (vis_pos, orig_idx) = positions[pos_idx];
         if let Some(it) = m.items.get(orig_idx) {
             let num_str = if vis_pos < bs.len() {
-                format!("{:>1$} │ ", bs[vis_pos], gutter_width)
+                format!("{:>1$} │ ", bs[vis_pos], gutter.num)
             } else {
-                format_num_str(orig_idx + 1, gutter_width)
+                format_num_str(orig_idx + 1, gutter.num)
             };
-            if let Some(line) = render_item_line(it, assigned, t_hyph, num_str, m) {
+            if let Some(line) = render_item_line(it, num_str, ctx, m) {
                 b.push_str(&line);
                 b.push('\n');
             }
         }
     }
-    b
 }
 
 // Default non-numeric render path
-fn render_default_content(m: &Model, visible: &[ChooseItem], baseline_num_strs: &Option<Vec<String>>, assigned: &HashMap<String, String>, t_hyph: usize, gutter_width: usize, start: usize, end: usize) -> String {
-    let mut b = String::new();
-    for (idx, it) in visible.iter().enumerate().skip(start).take(end.saturating_sub(start)) {
+fn render_default_content(m: &Model, b: &mut String, visible: &[ChooseItem], baseline_num_strs: &Option<Vec<String>>, ctx: &RowContext, start: usize, end: usize) {
+    let gutter = ctx.gutter;
+    let rows = group_rows(m, visible.iter());
+    let page_start = page_item_range(m, visible).start;
+    for row in rows.iter().skip(start).take(end.saturating_sub(start)) {
+        let idx = match row {
+            Row::Header(origin) => {
+                b.push_str(&header_line(origin, ctx));
+                b.push('\n');
+                continue;
+            }
+            Row::Item(k) => *k,
+        };
+        let it = &visible[idx];
         let num_str = if let Some(bs) = baseline_num_strs {
             if idx < bs.len() {
-                format!("{:>1$} │ ", bs[idx], gutter_width)
+                format!("{:>1$} │ ", bs[idx], gutter.num)
             } else {
-                format_num_str(idx + 1, gutter_width)
+                format_num_str(idx + 1, gutter.num)
             }
+        } else if m.settings.per_page_numbers {
+            format_num_str(idx - page_start + 1, gutter.num)
         } else {
-            format_num_str(idx + 1, gutter_width)
+            format_num_str(idx + 1, gutter.num)
         };
 
-        if let Some(line) = render_item_line(it, assigned, t_hyph, num_str, m) {
+        if let Some(line) = render_item_line(it, num_str, ctx, m) {
             b.push_str(&line);
             b.push('\n');
         }
     }
-    b
 }
 
 pub fn render_list_content(m: &Model, visible: &[ChooseItem]) -> String {
+    let mut b = String::new();
+    render_list_content_to(m, visible, &mut b);
+    b
+}
+
+// Appends the list rows to `b` (the hot path of every frame)
+pub fn render_list_content_to(m: &Model, visible: &[ChooseItem], b: &mut String) {
     let assigned = m.assigned_map();
 
     // If numeric baseline is active, compute total from baseline for gutter width
-    let (total, per) = if let Some(nb) = &m.numeric_baseline {
+    let total = if let Some(nb) = &m.numeric_baseline {
         // total for gutter calculation should reflect the largest original index number
         // use the maximum orig_idx+1 so gutter width does not shrink during numeric filtering
-        let max_num = nb.iter().map(|&i| i + 1).max().unwrap_or(0);
-        let t = max_num;
-        (t, if m.per_page == 0 { t } else { m.per_page })
+        nb.iter().map(|&i| i + 1).max().unwrap_or(0)
     } else {
-        let t = visible.len();
-        (t, if m.per_page == 0 { t } else { m.per_page })
+        visible.len()
     };
+    // pages are counted in rows, section headers included, in either mode
+    let rows_total = m.list_row_count(visible);
+    let per = if m.per_page == 0 { rows_total } else { m.per_page };
 
     if per == 0 {
-        return String::new();
+        return;
     }
     let start = m.page.saturating_mul(per);
-    let end = usize::min(start + per, total);
+    let end = usize::min(start + per, rows_total);
     let t_hyph = leading_hyphen_count(&m.typed_raw);
-    let gutter_width = compute_gutter_width(total);
+    // the key column is as wide as the longest key assigned, so every row lines up
+    let key = m
+        .settings
+        .key_column
+        .then(|| assigned.values().map(|k| k.chars().count()).max().unwrap_or(0).max(1));
+    let gutter = Gutter { key, num: compute_gutter_width(total) };
+    // the scrollbar, when shown, takes the last column
+    let width = m.render_width().saturating_sub(usize::from(m.settings.scrollbar));
+    let ctx = RowContext { assigned: &assigned, t_hyph, gutter, width, conflicts: m.conflicting_flags() };
 
     let baseline = build_baseline(m);
 
     // Numeric baseline path
     if let Some((bs, nb_order)) = baseline.as_ref() {
-        return render_numeric_content(m, &assigned, bs, &nb_order, t_hyph, gutter_width);
+        render_numeric_content(m, b, bs, nb_order, &ctx);
+        return;
     }
 
     // Default non-numeric path
-    render_default_content(m, visible, &baseline.map(|(v, _)| v), &assigned, t_hyph, gutter_width, start, end)
+    render_default_content(m, b, visible, &baseline.map(|(v, _)| v), &ctx, start, end)
+}
+
+// The end of a value being typed that fits in `room` columns, behind `…` when the start is cut
+fn value_tail(value: &str, room: usize) -> std::borrow::Cow<'_, str> {
+    let len = value.chars().count();
+    if len <= room {
+        return std::borrow::Cow::Borrowed(value);
+    }
+    let keep = room.saturating_sub(1);
+    let tail: String = value.chars().skip(len - keep).collect();
+    std::borrow::Cow::Owned(format!("…{tail}"))
 }
 
 pub fn render_main_content(m: &Model) -> String {
-    let total_width = if m.screen_width > 0 {
-        m.screen_width
-    } else {
-        DEFAULT_WIDTH
-    };
+    let mut out = String::new();
+    render_main_content_to(m, &mut out);
+    out
+}
+
+// Appends the padded main area to `out`; `render_main_content` is a wrapper around this
+pub fn render_main_content_to(m: &Model, out: &mut String) {
+    let total_width = m.render_width();
 
     if m.in_value_mode {
-        let lines: Vec<String> = vec![
-            lipgloss::Style::new().bold(true).render("Value input: ") + &m.pending_value,
+        let prompt = match &m.pending_kv_key {
+            Some(key) => format!("Value for {key}: "),
+            None if m.pending_redirect.is_some() => match &m.pending_redirect {
+                Some(crate::ast::Redirection::Input(_)) => "Read from (<): ".to_string(),
+                Some(crate::ast::Redirection::Output { append: true, .. }) => "Append to (>>): ".to_string(),
+                _ => "Write to (>): ".to_string(),
+            },
+            None if m.pending_is_kv() => "Key (then = or Enter): ".to_string(),
+            None => "Value input: ".to_string(),
+        };
+        let counter = if m.settings.value_counter && !m.pending_value.is_empty() {
+            format!(" [{}]", m.pending_value.chars().count())
+        } else {
+            String::new()
+        };
+        let room = total_width.saturating_sub(prompt.chars().count() + counter.chars().count());
+        let mut lines: Vec<String> = vec![
+            lipgloss::Style::new().bold(true).render(&prompt)
+                + &value_tail(&m.pending_value, room)
+                + &STYLE_DESC.render(&counter),
             lipgloss::Style::new()
                 .faint(true)
                 .render("Press Enter to confirm, Esc to cancel"),
         ];
+        // each source gets its heading; the key hint goes on the first
+        let mut i = 0;
+        for (n, (source, values)) in m.value_suggestion_groups().into_iter().enumerate() {
+            let hint = if n == 0 { " (↑/↓ choose, Tab fills)" } else { "" };
+            lines.push(STYLE_DESC.render(&format!("{}{hint}:", source.heading())));
+            for v in values {
+                if i == m.suggestion_idx {
+                    lines.push(STYLE_SELECTED.render(&format!("› {v}")));
+                } else {
+                    lines.push(STYLE_LABEL.render(&format!("  {v}")));
+                }
+                i += 1;
+            }
+        }
         let per = if m.per_page == 0 { lines.len() } else { m.per_page };
-        return normalize_and_pad(lines, total_width, per);
+        normalize_and_pad_into(lines.iter().map(String::as_str), total_width, per, out);
+        return;
     }
 
     let visible = m.render_visible_items();
-    let list_block = m.render_list_content(&visible);
-    let lines: Vec<String> = list_block.lines().map(|s| s.to_string()).collect();
+    let mut list_block = String::new();
+    render_list_content_to(m, &visible, &mut list_block);
     let per = if m.per_page == 0 {
-        lines.len()
+        list_block.lines().count()
     } else {
         m.per_page
     };
-    // Ensure we return exactly `per` lines each normalized to the terminal width.
-    normalize_and_pad(lines, total_width, per)
+    let total_pages = page_count(m, &visible);
+    if m.settings.scrollbar && total_pages > 1 && total_width > 1 {
+        // the last column carries the scrollbar instead of list text
+        let line_style = lipgloss::Style::new().width((total_width - 1) as i32);
+        let mut rows = list_block.lines();
+        for (i, glyph) in scrollbar_column(m.page, total_pages, per).iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str(first_line(&line_style.render(rows.next().unwrap_or(""))));
+            out.push_str(&STYLE_DESC.render(glyph));
+        }
+        return;
+    }
+    // Ensure we emit exactly `per` lines each normalized to the terminal width.
+    normalize_and_pad_into(list_block.lines(), total_width, per, out);
+}
+
+// Number of list pages, as shown by the modeline pagination or the scrollbar
+pub fn page_count(m: &Model, visible: &[ChooseItem]) -> usize {
+    let total = m.list_row_count(visible);
+    let per = if m.per_page == 0 { total } else { m.per_page };
+    if per > 0 { total.div_ceil(per) } else { 1 }
+}
+
+// One glyph per list row: the thumb spans rows/total_pages rows (at least one) and moves
+// from the top on the first page to the bottom on the last.
+fn scrollbar_column(page: usize, total_pages: usize, rows: usize) -> Vec<&'static str> {
+    let thumb = (rows / total_pages.max(1)).max(1).min(rows);
+    let start = if total_pages > 1 {
+        (rows - thumb) * page.min(total_pages - 1) / (total_pages - 1)
+    } else {
+        0
+    };
+    (0..rows)
+        .map(|r| if r >= start && r < start + thumb { "█" } else { "░" })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use regex::Regex;
-
-    fn strip_ansi(s: &str) -> String {
-        let re = Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]").unwrap();
-        re.replace_all(s, "").to_string()
-    }
+    use crate::ast::build::{command, flag, sub, switch, value_flag};
+    use crate::ui::render::strip_ansi;
 
     #[test]
     fn render_assigned_map_initial_prefixes_shows_labels() {
@@ -405,6 +657,7 @@ mod tests {
                 shorthand: "v".to_string(),
                 usage: "v".to_string(),
                 requires_value: false,
+                ..Default::default()
             }],
             subcommands: vec![crate::ast::CommandDef {
                 name: "sub".to_string(),
@@ -412,7 +665,9 @@ mod tests {
                 aliases: vec![],
                 flags: vec![],
                 subcommands: vec![],
+                ..Default::default()
             }],
+            ..Default::default()
         };
         m.ast = crate::ast::Segment::new_empty("root");
         m.current = Some(def.clone());
@@ -437,15 +692,18 @@ mod tests {
                     shorthand: "m".to_string(),
                     usage: "msg".to_string(),
                     requires_value: true,
+                    ..Default::default()
                 },
                 crate::ast::FlagDef {
                     longhand: "verbose".to_string(),
                     shorthand: "v".to_string(),
                     usage: "v".to_string(),
                     requires_value: false,
+                    ..Default::default()
                 },
             ],
             subcommands: vec![],
+            ..Default::default()
         };
         m.ast = crate::ast::Segment::new_empty("root");
         m.current = Some(def.clone());
@@ -583,4 +841,283 @@ mod tests {
             "expected at least one ACE-styled disambiguator present in rendered list"
         );
     }
+
+    #[test]
+    fn render_marks_already_selected_flags() {
+        let mut m = crate::ui::initial_model(vec![]);
+        let def = command("root", vec![flag("verbose", "v"), flag("quiet", "q")], vec![]);
+        m.ast = crate::ast::Segment::new_empty("root");
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+
+        let row = |m: &crate::ui::Model, needle: &str| {
+            let visible = m.render_visible_items();
+            strip_ansi(&m.render_list_content(&visible))
+                .lines()
+                .find(|l| l.contains(needle))
+                .unwrap_or_default()
+                .to_string()
+        };
+        assert!(!row(&m, "--verbose").contains('✓'));
+
+        m.ast.add_flag_to_depth(0, "-v", "");
+        assert!(row(&m, "--verbose").contains('✓'), "selected flag row must be marked");
+        assert!(!row(&m, "--quiet").contains('✓'));
+    }
+
+    #[test]
+    fn render_shows_keystrokes_in_brackets_when_enabled() {
+        let mut m = crate::ui::initial_model(vec![]);
+        let def = command("root", vec![flag("message", "m"), flag("merge", ""), flag("quiet", "q")], vec![sub("show")]);
+        m.ast = crate::ast::Segment::new_empty("root");
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+        let visible = m.render_visible_items();
+        assert!(!strip_ansi(&m.render_list_content(&visible)).contains('['), "off by default");
+
+        m.settings.show_keystrokes = true;
+        let assigned = m.assigned_map();
+        let out = strip_ansi(&m.render_list_content(&visible));
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(rows.len(), visible.len());
+        for (row, it) in rows.iter().zip(&visible) {
+            let key = super::item_keystroke(it, &assigned);
+            assert!(!key.is_empty(), "no assignment for {}", it.label);
+            let shown = row.split_once(" │ ").map(|(_, rest)| rest).unwrap_or_default();
+            assert!(shown.starts_with(&format!("[{key}] ")), "row `{row}` should start with [{key}]");
+        }
+    }
+
+    #[test]
+    fn conflicting_flags_render_as_warnings_and_block_running() {
+        use crate::ast::{CommandDef, Segment};
+        let def = CommandDef {
+            exclusive_flags: vec![vec!["json".to_string(), "yaml".to_string()]],
+            ..command("tool", vec![flag("json", "j"), flag("yaml", ""), flag("verbose", "v")], vec![])
+        };
+        let mut m = crate::ui::initial_model(vec![]);
+        m.ast = Segment::new_empty("tool");
+        m.current = Some(def.clone());
+        m.def_cache.insert("tool".to_string(), def.clone());
+        m.build_items_from_command(&def);
+        m.ast.add_flag("-j", "");
+        m.ast.add_flag("--verbose", "");
+        let render = |m: &crate::ui::Model| m.render_list_content(&m.render_visible_items());
+        // one of the set is fine
+        assert!(m.conflicting_flags().is_empty());
+        assert!(!strip_ansi(&render(&m)).contains('⚠'));
+
+        m.ast.add_flag("--yaml", "");
+        assert_eq!(m.conflicting_flags(), vec![(0, vec!["-j".to_string(), "--yaml".to_string()])]);
+        let out = render(&m);
+        for line in out.lines() {
+            let plain = strip_ansi(line);
+            let warned = line.contains(&super::STYLE_WARNING.render(" ⚠"));
+            assert_eq!(warned, plain.contains("--json") || plain.contains("--yaml"), "{plain}");
+        }
+        assert!(strip_ansi(&out).contains("--verbose, -v ✓"));
+        let modeline = strip_ansi(&crate::ui::render::render_modeline_padded(&m));
+        assert!(modeline.contains("⚠ only one of -j, --yaml"), "{modeline}");
+
+        // Enter says why instead of finishing
+        m.update(crate::ui::Msg::KeyEnter);
+        assert!(m.exit_preview.is_empty());
+        assert!(m.err.contains("-j, --yaml can't be used together"), "{}", m.err);
+        m.ast.remove_flag("--yaml");
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.exit_preview, "tool -j --verbose");
+    }
+
+    #[test]
+    fn render_key_column_aligns_keys_left_of_the_numbers() {
+        let mut m = crate::ui::initial_model(vec![]);
+        let def = command("tool", vec![], vec![sub("serve"), sub("show"), sub("build")]);
+        m.ast = crate::ast::Segment::new_empty("tool");
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+        m.settings.key_column = true;
+        m.typed_raw = "s".to_string();
+        m.typed = "s".to_string();
+        let visible = m.render_visible_items();
+        let assigned = m.assigned_map();
+        let out = strip_ansi(&m.render_list_content(&visible));
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(rows.len(), visible.len());
+        let width = assigned.values().map(|k| k.chars().count()).max().unwrap();
+        for (row, it) in rows.iter().zip(&visible) {
+            let key = super::item_keystroke(it, &assigned);
+            assert!(!key.is_empty(), "no assignment for {}", it.label);
+            let (cell, rest) = row.split_once(" │ ").unwrap();
+            assert_eq!(cell, format!("{key:<width$}"), "row `{row}`");
+            assert!(rest.contains(" │ ") && rest.ends_with(&it.label), "row `{row}`");
+        }
+        // every row's number column starts at the same offset
+        let offsets: std::collections::HashSet<usize> = rows.iter().map(|r| r.find(" │ ").unwrap()).collect();
+        assert_eq!(offsets.len(), 1, "{rows:?}");
+    }
+
+    #[test]
+    fn long_value_input_scrolls_to_its_tail_and_shows_its_length() {
+        let mut m = crate::ui::initial_model(vec![]);
+        m.ast = crate::ast::Segment::new_empty("echo");
+        m.screen_width = 40;
+        m.per_page = 4;
+        m.in_value_mode = true;
+        m.pending_pos = true;
+        m.pending_value = "short".to_string();
+        let out = strip_ansi(&m.render_main_content());
+        assert!(out.lines().next().unwrap().starts_with("Value input: short [5]"), "{out}");
+
+        m.pending_value = (0..60).map(|i| char::from(b'a' + (i % 26) as u8)).collect();
+        let out = strip_ansi(&m.render_main_content());
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 4);
+        let first = lines[0].trim_end();
+        assert!(first.starts_with("Value input: …"), "{first}");
+        assert!(first.ends_with("tuvwxyzabcdefgh [60]"), "{first}");
+        assert_eq!(first.chars().count(), m.render_width());
+    }
+
+    #[test]
+    fn render_value_placeholder_uses_type_hint() {
+        let mut m = crate::ui::initial_model(vec![]);
+        let def = command("root", vec![value_flag("jobs", "int"), value_flag("color", "on|off|auto"), value_flag("name", "")], vec![]);
+        m.ast = crate::ast::Segment::new_empty("root");
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+
+        let visible = m.render_visible_items();
+        let stripped = strip_ansi(&m.render_list_content(&visible));
+        let row = |needle: &str| stripped.lines().find(|l| l.contains(needle)).unwrap_or_default();
+        assert!(row("--jobs").contains("--jobs <int>"), "got: {stripped}");
+        assert!(row("--color").contains("--color <on|off|auto>"), "got: {stripped}");
+        // no hint: fall back to the uppercased longhand
+        assert!(row("--name").contains("--name NAME"), "got: {stripped}");
+    }
+
+    #[test]
+    fn toggling_long_usage_switches_the_flag_description() {
+        let mut m = crate::ui::initial_model(vec![]);
+        let def = crate::ast::CommandDef {
+            name: "root".to_string(),
+            flags: vec![
+                crate::ast::FlagDef {
+                    longhand: "force".to_string(),
+                    usage: "overwrite".to_string(),
+                    long_usage: "overwrite existing files without asking".to_string(),
+                    ..Default::default()
+                },
+                crate::ast::FlagDef {
+                    longhand: "quiet".to_string(),
+                    usage: "less output".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        m.ast = crate::ast::Segment::new_empty("root");
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+        let rows = |m: &crate::ui::Model| strip_ansi(&m.render_list_content(&m.render_visible_items()));
+
+        let short = rows(&m);
+        assert!(short.contains("overwrite") && !short.contains("without asking"), "got: {short}");
+        m.update(crate::ui::Msg::ToggleLongUsage);
+        let long = rows(&m);
+        assert!(long.contains("overwrite existing files without asking"), "got: {long}");
+        // flags without an extended description keep their usage
+        assert!(long.contains("less output"), "got: {long}");
+        m.update(crate::ui::Msg::ToggleLongUsage);
+        assert_eq!(rows(&m), short);
+    }
+
+    #[test]
+    fn render_groups_flags_under_origin_headers_without_consuming_numbers() {
+        let commit = command("commit", vec![switch("amend"), switch("all")], vec![]);
+        let git = command("git", vec![switch("verbose"), switch("no-pager")], vec![commit.clone()]);
+        let mut m = crate::ui::initial_model(vec![]);
+        m.settings.group_flags = true;
+        m.ast = crate::ast::Segment::new_empty("git");
+        m.ast.stack[0].name = "git".to_string();
+        m.def_cache.insert("git".to_string(), git.clone());
+        m.ast.push_subcommand("commit");
+        m.current = Some(commit.clone());
+        m.build_items_from_command(&commit);
+
+        let visible = m.render_visible_items();
+        assert_eq!(visible.len(), 4);
+        assert_eq!(m.list_row_count(&visible), 6);
+        let stripped = strip_ansi(&m.render_list_content(&visible));
+        let lines: Vec<&str> = stripped.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].contains("── git ──"), "got: {stripped}");
+        assert!(lines[3].contains("── commit ──"), "got: {stripped}");
+        // headers carry no number; items are numbered 1..=4 in order
+        let numbers: Vec<&str> = lines
+            .iter()
+            .filter_map(|l| l.split('│').next())
+            .map(str::trim)
+            .filter(|n| !n.is_empty() && !n.starts_with('─'))
+            .collect();
+        assert_eq!(numbers, vec!["1", "2", "3", "4"]);
+        assert!(lines[1].contains("--no-pager") || lines[1].contains("--verbose"));
+        assert!(lines[4].contains("--all") || lines[4].contains("--amend"));
+
+        // headers are ruled out to the list's width
+        m.update(crate::ui::Msg::WindowSize { width: 60, height: 24 });
+        let stripped = strip_ansi(&m.render_list_content(&visible));
+        let header = stripped.lines().next().unwrap();
+        assert_eq!(header.chars().count(), 60, "{header:?}");
+        assert!(header.trim_start().starts_with("── git ─"), "{header:?}");
+
+        // numeric mode counts and renders the same rows
+        m.update(crate::ui::Msg::ToggleNumeric);
+        assert!(m.numeric_baseline.is_some());
+        let visible = m.render_visible_items();
+        assert_eq!(m.list_row_count(&visible), 6);
+        let stripped = strip_ansi(&m.render_list_content(&visible));
+        assert_eq!(stripped.lines().count(), 6, "got: {stripped}");
+        assert_eq!(crate::ui::render::list::page_count(&m, &visible), 1);
+        m.update(crate::ui::Msg::ToggleNumeric);
+
+        // off by default: no headers
+        m.settings.group_flags = false;
+        let stripped = strip_ansi(&m.render_list_content(&visible));
+        assert!(!stripped.contains("──"));
+    }
+
+    #[test]
+    fn scrollbar_thumb_tracks_the_current_page() {
+        let mut m = crate::ui::initial_model(vec![]);
+        m.items = (1..=50)
+            .map(|i| crate::ui::ChooseItem {
+                kind: "cmd".to_string(),
+                label: format!("cmd{i}"),
+                forms: vec![format!("cmd{i}")],
+                flag_def: None,
+                cmd_def: None,
+                short: String::new(),
+                depth: 0,
+            })
+            .collect();
+        m.settings.scrollbar = true;
+        // 10 rows per page, 5 pages: a 2-row thumb
+        m.update(crate::ui::Msg::WindowSize { width: 30, height: 14 });
+        assert_eq!(m.per_page, 10);
+
+        for (page, filled) in [(0usize, [0usize, 1]), (2, [4, 5]), (4, [8, 9])] {
+            m.page = page;
+            let stripped = strip_ansi(&m.render_main_content());
+            let lines: Vec<&str> = stripped.lines().collect();
+            assert_eq!(lines.len(), 10);
+            for (row, line) in lines.iter().enumerate() {
+                assert_eq!(line.chars().count(), 30, "row {row}: `{line}`");
+                let glyph = line.chars().last().unwrap();
+                let expect = if filled.contains(&row) { '█' } else { '░' };
+                assert_eq!(glyph, expect, "page {page} row {row}");
+            }
+        }
+        let modeline = strip_ansi(&crate::ui::render_modeline_padded(&m));
+        assert!(!modeline.contains("Page "), "got: {modeline}");
+    }
 }
@@ -1,26 +1,45 @@
-use crate::ui::model::{ChooseItem, DEFAULT_WIDTH, Model};
-use crate::ui::render::styles::STYLE_MODELINE;
-use lipgloss::Color;
+use crate::ui::model::{ChooseItem, Model, TYPED_LABEL};
+use crate::ui::render::styles::{
+    STYLE_MODELINE, STYLE_MODELINE_ACE, STYLE_MODELINE_ERROR, STYLE_MODELINE_MODE, STYLE_MODELINE_TYPED, STYLE_MODELINE_WARNING, rgb,
+};
+use crate::ui::render::util::{first_line, truncate_visible};
 
-pub fn render_modeline(m: &Model, inner_max: usize, mode: &str, visible: &[ChooseItem]) -> String {
+// `typing`: `mode` shows the buffer being typed (after `TYPED_LABEL`), which is then styled
+pub fn render_modeline(m: &Model, inner_max: usize, mode: &str, typing: bool, visible: &[ChooseItem]) -> String {
     // Build styled pairs, compute plain widths, and fit pagination into available space.
-    let total = visible.len();
-    let per = if m.per_page == 0 { total } else { m.per_page };
-    let total_pages = if per > 0 { total.div_ceil(per) } else { 1 };
+    // A long mode (a deep subcommand, a long typed buffer) takes at most half of the line and
+    // ends in `…`, so the key hints and pagination keep room.
+    let mode_max = (inner_max / 2).max(1);
+    let truncated_mode;
+    let cut = mode.chars().count() > mode_max;
+    let mode = if cut {
+        truncated_mode = format!("{}…", mode.chars().take(mode_max - 1).collect::<String>());
+        truncated_mode.as_str()
+    } else {
+        mode
+    };
+    let total_pages = crate::ui::render::list::page_count(m, visible);
 
     // prepare inner styles without padding so spacing is under our control
     let inner_style = STYLE_MODELINE.clone().padding(0, 0, 0, 0);
     let key_style = STYLE_MODELINE
         .clone()
-        .foreground(Color::from_rgb(238, 0, 238))
+        .foreground(rgb(238, 0, 238))
         .bold(true)
         .padding(0, 0, 0, 0);
     let desc_style = STYLE_MODELINE.clone().padding(0, 0, 0, 0);
     let pag_style = STYLE_MODELINE.clone().faint(true).padding(0, 0, 0, 0);
 
-    // key/description pairs definitions
-    let pairs_def: Vec<(&str, &str)> =
-        vec![("␣", "arg"), ("⏎", "run"), ("⌫", "undo"), ("⎋", "quit")];
+    // key/description pairs from the keymap; Backspace leaves the root command for the command
+    // list ("back") but otherwise takes back the last argument ("undo")
+    let pairs_def: Vec<(&str, &str)> = crate::ui::keymap::keymap()
+        .iter()
+        .filter(|b| !b.glyph.is_empty())
+        .filter_map(|b| match b.key {
+            "Backspace" => m.backspace_hint().map(|hint| (b.glyph, hint)),
+            _ => Some((b.glyph, b.action)),
+        })
+        .collect();
 
     // Build rendered pairs and their plain widths in one pass
     let pairs: Vec<(String, usize)> = pairs_def
@@ -40,13 +59,30 @@ pub fn render_modeline(m: &Model, inner_max: usize, mode: &str, visible: &[Choos
     let pair_sep_rendered = inner_style.render("  ");
     let pair_sep_width = 2usize;
 
+    // visible/total candidates while a filter hides some (e.g. "12/84"), before the pagination,
+    // after the kind filter in effect (e.g. "flags only")
+    let count = m.candidate_count_for(visible);
+    let mut count_prefix = match m.kind_filter.label() {
+        Some(label) if !m.in_value_mode => format!("{label} "),
+        _ => String::new(),
+    };
+    if count.hidden_by_filter() > 0 && !m.in_value_mode {
+        count_prefix.push_str(&format!("{}/{} ", count.visible, count.total));
+    }
+
     // build pagination plain and styled
-    let mut pag_plain = String::new();
-    let mut pag_rendered = String::new();
-    if total_pages > 1 {
-        pag_plain = format!("Page {}/{} ↑/↓", m.page + 1, total_pages);
+    let mut pag_plain = count_prefix.trim_end().to_string();
+    let mut pag_rendered = if pag_plain.is_empty() {
+        String::new()
+    } else {
+        pag_style.render(&pag_plain)
+    };
+    // the scrollbar setting replaces the text indicator
+    let show_pages = total_pages > 1 && !m.settings.scrollbar;
+    if show_pages {
+        pag_plain = format!("{count_prefix}Page {}/{} ↑/↓", m.page + 1, total_pages);
         let arrows = format!("{}/{}", key_style.render("↑"), key_style.render("↓"));
-        let pag_unstyled = format!("Page {}/{} ", m.page + 1, total_pages);
+        let pag_unstyled = format!("{count_prefix}Page {}/{} ", m.page + 1, total_pages);
         pag_rendered = pag_style.render(&format!("{pag_unstyled}{arrows}"));
     }
     let mut pag_width = pag_plain.chars().count();
@@ -80,6 +116,58 @@ pub fn render_modeline(m: &Model, inner_max: usize, mode: &str, visible: &[Choos
         0
     };
 
+    // candidate counts summary sits left of the pagination and is the first thing dropped;
+    // the last error (red) or a conflicting-flags or command-length warning (yellow) takes its place, and a
+    // description of the finished command (when enabled) comes before the counts
+    let alert = if !m.err.is_empty() {
+        Some((format!("✗ {}", m.err.lines().next().unwrap_or_default()), STYLE_MODELINE_ERROR.clone()))
+    } else {
+        m.conflict_warning()
+            .or_else(|| m.command_length_warning())
+            .map(|w| (w, STYLE_MODELINE_WARNING.clone()))
+    };
+    let mut summary_plain = alert
+        .as_ref()
+        .map(|(text, _)| text.clone())
+        .or_else(|| m.command_description())
+        .or_else(|| m.counts_summary())
+        .unwrap_or_default();
+    let mut summary_w = if summary_plain.is_empty() {
+        0
+    } else {
+        summary_plain.chars().count() + 1
+    };
+    // an error or warning outranks the key hints: they make room for it first, and what still
+    // doesn't fit is cut short rather than hidden
+    if alert.is_some() && left_width + summary_w + pag_width > avail {
+        while pairs_count > 0 && left_width + summary_w + pag_width > avail {
+            pairs_count -= 1;
+            left_width = pairs.iter().take(pairs_count).map(|(_, w)| *w).sum::<usize>()
+                + pair_sep_width * pairs_count.saturating_sub(1);
+        }
+        left_joined_rendered = pairs
+            .iter()
+            .take(pairs_count)
+            .map(|(r, _)| r.clone())
+            .collect::<Vec<_>>()
+            .join(&pair_sep_rendered);
+        let room = avail.saturating_sub(left_width + pag_width + 1);
+        if summary_w > room + 1 && room > 1 {
+            summary_plain = format!("{}…", summary_plain.chars().take(room - 1).collect::<String>());
+            summary_w = room + 1;
+        }
+    }
+    if left_width + summary_w + pag_width > avail {
+        summary_w = 0;
+    }
+    let summary_rendered = if let Some((_, style)) = alert.filter(|_| summary_w > 0) {
+        style.render(&format!("{summary_plain} "))
+    } else if summary_w > 0 {
+        pag_style.render(&format!("{summary_plain} "))
+    } else {
+        String::new()
+    };
+
     // drop rightmost pairs until left + pag fits into avail
     while pairs_count > 0 && left_width + pag_width > avail {
         // remove last pair
@@ -104,15 +192,15 @@ pub fn render_modeline(m: &Model, inner_max: usize, mode: &str, visible: &[Choos
     }
 
     // if still doesn't fit and pagination exists, shorten pagination to just "Page X/Y"
-    if left_width + pag_width > avail && !pag_plain.is_empty() {
-        let short_pag = format!("Page {}/{}", m.page + 1, total_pages);
+    if left_width + pag_width > avail && show_pages {
+        let short_pag = format!("{count_prefix}Page {}/{}", m.page + 1, total_pages);
         pag_width = short_pag.chars().count();
         pag_rendered = pag_style.render(&short_pag);
     }
 
     // compute filler width (subtract 2 to keep spacing consistent)
-    let pad = if avail > left_width + pag_width + 2 {
-        avail - left_width - pag_width - 2
+    let pad = if avail > left_width + summary_w + pag_width + 2 {
+        avail - left_width - summary_w - pag_width - 2
     } else {
         0
     };
@@ -122,14 +210,27 @@ pub fn render_modeline(m: &Model, inner_max: usize, mode: &str, visible: &[Choos
         String::new()
     };
 
-    let footer_inner = format!("{left_joined_rendered}{filler}{pag_rendered}");
+    let footer_inner = format!("{left_joined_rendered}{filler}{summary_rendered}{pag_rendered}");
 
-    let mode_style = STYLE_MODELINE
-        .clone()
-        .background(Color::from_rgb(101, 101, 101))
-        .padding(0, 1, 0, 1)
-        .bold(true);
-    let mode_styled = mode_style.render(mode);
+    let mode_style = &*STYLE_MODELINE_MODE;
+    // while typing, style the buffer inside the mode block to show the disambiguation state;
+    // the plain width stays that of `mode`, so layout math above is unaffected
+    let mode_styled = if typing && !cut && !m.typed_raw.is_empty() {
+        let typed_styled = crate::ui::render::decorate_typed(
+            &m.typed_raw,
+            m.numeric_baseline.is_some(),
+            &STYLE_MODELINE_TYPED,
+            &STYLE_MODELINE_ACE,
+        );
+        format!(
+            "{}{}{}",
+            mode_style.clone().padding(0, 0, 0, 1).render(TYPED_LABEL),
+            typed_styled,
+            mode_style.clone().padding(0, 1, 0, 0).render("")
+        )
+    } else {
+        mode_style.render(mode)
+    };
 
     // Indicator: show a dim single-char marker at the far left to indicate
     // filtering mode. When numeric_baseline is present show '1', otherwise 'A'.
@@ -147,35 +248,24 @@ pub fn render_modeline(m: &Model, inner_max: usize, mode: &str, visible: &[Choos
 }
 
 pub fn render_modeline_padded(m: &Model) -> String {
-    // Compute total width and inner_max the same way render_full used to.
-    let total_width = if m.screen_width > 0 {
-        m.screen_width
-    } else {
-        DEFAULT_WIDTH
-    };
-    let inner_max = if total_width > 0 {
-        total_width.saturating_sub(2) - 1
-    } else {
-        DEFAULT_WIDTH
-    };
+    // leave room for the indicator and the trailing pad
+    let total_width = m.render_width();
+    let inner_max = total_width.saturating_sub(3);
     let visible = m.render_visible_items();
     let mode = m.mode();
-    let modeline = render_modeline(m, inner_max, &mode, &visible);
+    let modeline = render_modeline(m, inner_max, &mode, !m.typed.is_empty(), &visible);
     let modeline_single = modeline.replace('\n', " ");
-    STYLE_MODELINE
+    let padded = STYLE_MODELINE
         .clone()
         .width(total_width as i32)
-        .render(&modeline_single)
+        .render(&modeline_single);
+    // too narrow for even the indicator and mode block: cut the line at the terminal edge
+    truncate_visible(first_line(&padded), total_width)
 }
 
 #[cfg(test)]
 mod tests {
-    use regex::Regex;
-
-    fn strip_ansi(s: &str) -> String {
-        let re = Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]").unwrap();
-        re.replace_all(s, "").to_string()
-    }
+    use crate::ui::render::strip_ansi;
 
     #[test]
     fn modeline_is_last_line_and_exact_width_small() {
@@ -199,6 +289,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn backspace_hint_depends_on_depth() {
+        let mut m = crate::ui::initial_model(vec![("git".to_string(), String::new())]);
+        m.update(crate::ui::Msg::WindowSize { width: 120, height: 24 });
+        let line = |m: &crate::ui::Model| strip_ansi(&crate::ui::render_modeline_padded(m));
+        assert_eq!(m.depth(), 0);
+        assert!(!line(&m).contains('⌫'), "{}", line(&m));
+
+        m.ast = crate::ast::Segment::new_empty("git");
+        assert_eq!(m.depth(), 1);
+        assert!(line(&m).contains("back:⌫"), "{}", line(&m));
+
+        m.ast.push_subcommand("remote");
+        assert_eq!(m.depth(), 2);
+        let nested = line(&m);
+        assert!(nested.contains("undo:⌫") && !nested.contains("back:⌫"), "{nested}");
+
+        m.typed = "x".to_string();
+        assert!(line(&m).contains("erase:⌫"), "{}", line(&m));
+    }
+
     #[test]
     fn modeline_shows_numeric_indicator_when_numeric_baseline() {
         let (w, h) = (80usize, 24usize);
@@ -223,4 +334,149 @@ mod tests {
         let modeline_stripped = strip_ansi(&modeline);
         assert!(modeline_stripped.trim_start().starts_with('A'));
     }
+
+    #[test]
+    fn modeline_reports_shown_and_hidden_counts() {
+        use crate::ast::{CommandDef, FlagDef, Segment};
+        let flag = |name: &str, hidden: bool| FlagDef {
+            longhand: name.to_string(),
+            hidden,
+            ..Default::default()
+        };
+        let sub = |name: &str, hidden: bool| CommandDef {
+            name: name.to_string(),
+            hidden,
+            ..Default::default()
+        };
+        let def = CommandDef {
+            name: "tool".to_string(),
+            flags: vec![flag("all", false), flag("debug-internals", true), flag("long", false)],
+            subcommands: vec![sub("build", false), sub("__complete", true), sub("run", false), sub("test", false)],
+            ..Default::default()
+        };
+        let mut m = crate::ui::initial_model(vec![]);
+        m.update(crate::ui::Msg::WindowSize { width: 100, height: 24 });
+        m.ast = Segment::new_empty("tool");
+        m.def_cache.insert("tool".to_string(), def.clone());
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+
+        assert!(m.items.iter().all(|it| it.label != "__complete" && it.label != "--debug-internals"));
+        assert_eq!(m.counts_summary().as_deref(), Some("(2 flags, 3 subcommands; 2 hidden)"));
+        let stripped = strip_ansi(&crate::ui::render_modeline_padded(&m));
+        let first = stripped.lines().next().unwrap_or("");
+        assert!(first.contains("(2 flags, 3 subcommands; 2 hidden)"), "got: {first}");
+        assert!(first.chars().count() <= 100);
+
+        m.settings.show_counts = false;
+        assert_eq!(m.counts_summary(), None);
+        let stripped = strip_ansi(&crate::ui::render_modeline_padded(&m));
+        assert!(!stripped.contains("hidden"));
+    }
+
+    #[test]
+    fn modeline_warns_when_command_nears_the_length_limit() {
+        let mut m = crate::ui::initial_model(vec![]);
+        m.update(crate::ui::Msg::WindowSize { width: 100, height: 24 });
+        m.settings.max_command_len = 100;
+        m.ast = crate::ast::Segment::new_empty("echo");
+        m.ast.add_positional("short");
+        assert_eq!(m.command_length_warning(), None);
+        let first = |m: &crate::ui::Model| strip_ansi(&crate::ui::render_modeline_padded(m)).lines().next().unwrap_or("").to_string();
+        assert!(!first(&m).contains('⚠'));
+
+        m.ast.add_positional(&"x".repeat(90));
+        assert_eq!(m.command_length_warning().as_deref(), Some("⚠ 101/100 bytes"));
+        assert!(first(&m).contains("⚠ 101/100 bytes"), "got: {}", first(&m));
+        // the preview says so too, in the warning style
+        let preview = crate::ui::render_preview_block(&m).join("\n");
+        let warning = crate::ui::render::styles::STYLE_WARNING.render("⚠ 101/100 bytes");
+        assert!(preview.contains(&warning), "got: {preview:?}");
+    }
+
+    #[test]
+    fn modeline_describes_finished_command_from_each_level() {
+        let commit = crate::ast::CommandDef {
+            name: "commit".to_string(),
+            short: "Record changes".to_string(),
+            ..Default::default()
+        };
+        let remote = crate::ast::CommandDef { name: "remote".to_string(), ..Default::default() };
+        let git = crate::ast::CommandDef {
+            name: "git".to_string(),
+            short: "Distributed version control".to_string(),
+            subcommands: vec![commit.clone(), remote],
+            ..Default::default()
+        };
+        let mut m = crate::ui::initial_model(vec![]);
+        m.update(crate::ui::Msg::WindowSize { width: 120, height: 24 });
+        m.def_cache.insert("git".to_string(), git.clone());
+        m.ast = crate::ast::Segment::new_empty("git");
+        m.current = Some(git.clone());
+        m.build_items_from_command(&git);
+        m.settings.describe_command = true;
+        // subcommands left to choose: not finished yet
+        assert_eq!(m.command_description(), None);
+
+        m.ast.push_subcommand("commit");
+        m.current = Some(commit.clone());
+        m.build_items_from_command(&commit);
+        let want = "git: Distributed version control > commit: Record changes";
+        assert_eq!(m.command_description().as_deref(), Some(want));
+        let first = strip_ansi(&crate::ui::render_modeline_padded(&m));
+        assert!(first.contains(want), "got: {first}");
+
+        m.settings.describe_command = false;
+        assert_eq!(m.command_description(), None);
+    }
+
+    #[test]
+    fn modeline_shows_visible_over_total_while_filtered() {
+        let entries: Vec<(String, String)> = ["git", "gh", "go", "ls", "make"]
+            .iter()
+            .map(|n| (n.to_string(), String::new()))
+            .collect();
+        let mut m = crate::ui::initial_model(entries);
+        m.update(crate::ui::Msg::WindowSize { width: 100, height: 24 });
+        let count = m.candidate_count();
+        assert_eq!((count.total, count.visible, count.hidden_by_filter()), (5, 5, 0));
+        let unfiltered = strip_ansi(&crate::ui::render_modeline_padded(&m));
+        assert!(!unfiltered.contains("5/5"), "got: {unfiltered}");
+
+        m.update(crate::ui::Msg::Rune('g'));
+        let count = m.candidate_count();
+        assert_eq!((count.total, count.visible, count.hidden_by_filter()), (5, 3, 2));
+        assert_eq!(count.visible, m.render_visible_items().len());
+        let filtered = strip_ansi(&crate::ui::render_modeline_padded(&m));
+        assert!(filtered.contains("3/5"), "got: {filtered}");
+    }
+
+    #[test]
+    fn long_mode_is_cut_with_an_ellipsis_and_the_width_kept() {
+        let mut m = crate::ui::initial_model(vec![]);
+        m.update(crate::ui::Msg::WindowSize { width: 60, height: 20 });
+        m.ast = crate::ast::Segment::new_empty("tool");
+        let long = format!("subcommand-{}", "x".repeat(80));
+        m.ast.push_subcommand(&long);
+        let line = strip_ansi(&crate::ui::render_modeline_padded(&m));
+        assert_eq!(line.chars().count(), 60, "{line:?}");
+        assert!(line.contains("subcommand-xxx") && line.contains('…'), "{line:?}");
+        assert!(!line.contains(&long));
+        // the first key hint still fits
+        assert!(line.contains("arg:␣"), "{line:?}");
+    }
+
+    #[test]
+    fn typed_buffer_is_styled_in_the_mode_block() {
+        use crate::ui::render::styles::{STYLE_MODELINE_ACE, STYLE_MODELINE_TYPED};
+        let mut m = crate::ui::initial_model(vec![]);
+        m.update(crate::ui::Msg::WindowSize { width: 100, height: 20 });
+        m.typed = "gc".to_string();
+        m.typed_raw = "gc".to_string();
+        let visible = m.render_visible_items();
+        let line = crate::ui::render_modeline(&m, 97, &m.mode(), true, &visible);
+        assert!(line.contains(&STYLE_MODELINE_TYPED.render("g")), "{line:?}");
+        assert!(line.contains(&STYLE_MODELINE_ACE.render("c")), "{line:?}");
+        assert!(strip_ansi(&line).contains("Typed: gc"));
+    }
 }
@@ -1,26 +1,132 @@
-use crate::ui::model::{DEFAULT_WIDTH, Model, PREVIEW_BLOCK_LINES};
-use crate::ui::render::styles::{STYLE_PREVIEW, STYLE_PREVIEW_BOX};
+use crate::ui::model::{Model, PREVIEW_BLOCK_LINES};
+use crate::ui::render::styles::{STYLE_DESC, STYLE_PREVIEW, STYLE_PREVIEW_BOX, STYLE_WARNING};
+use crate::ui::render::util::{normalize_and_pad_into, one_line};
+
+// Narrowest terminal the bordered preview box fits in (two border and two padding columns
+// around at least one column of text); anything narrower gets the preview line unboxed.
+const MIN_BOX_WIDTH: usize = 5;
+
+// Shown faintly in place of the bare `> ` until a command is chosen
+const EMPTY_HINT: &str = "type or pick a command";
 
 pub fn render_preview(m: &Model) -> String {
-    STYLE_PREVIEW.render(&m.ast.render_preview())
+    STYLE_PREVIEW.render(&m.preview_text())
+}
+
+// The preview alone on one line, for prompts and status bars redrawing it continuously: no
+// box, list or modeline, and unstyled when NO_COLOR is set
+pub fn render_embed(m: &Model) -> String {
+    let preview = m.preview_text();
+    let line = one_line(&preview);
+    if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return line.into_owned();
+    }
+    STYLE_PREVIEW.render(&line)
+}
+
+// The preview line fitted to `room` columns: a command too long to show whole keeps its end,
+// the deepest subcommand and the arguments being added, behind `> …`
+fn preview_line(preview: &str, room: usize) -> String {
+    let len = preview.chars().count();
+    if len + 2 <= room || room < 4 {
+        return format!("> {preview}");
+    }
+    let tail: String = preview.chars().skip(len + 3 - room).collect();
+    format!("> …{tail}")
+}
+
+// The styled line for the preview area: the command fitted to `room`, or the hint (cut at
+// `room`) while nothing has been chosen. A command near the length limit ends in the warning,
+// when there is room for it beside the command.
+fn styled_preview_line(m: &Model, room: usize) -> String {
+    if m.is_empty_state() {
+        let hint: String = format!("> {EMPTY_HINT}").chars().take(room.max(2)).collect();
+        return STYLE_DESC.render(&hint);
+    }
+    if let Some(warning) = m.command_length_warning() {
+        let width = warning.chars().count() + 1;
+        if room >= width + 4 {
+            let line = preview_line(&m.preview_text(), room - width);
+            return format!("{} {}", STYLE_PREVIEW.render(&line), STYLE_WARNING.render(&warning));
+        }
+    }
+    STYLE_PREVIEW.render(&preview_line(&m.preview_text(), room))
 }
 
 pub fn render_preview_block(m: &Model) -> Vec<String> {
-    let preview = m.ast.render_preview();
-    let preview_line = format!("> {preview}");
-    let box_width = if m.screen_width >= 2 {
-        m.screen_width - 2
-    } else {
-        DEFAULT_WIDTH
-    };
+    let mut out = String::new();
+    render_preview_block_to(m, &mut out);
+    out.split('\n').map(str::to_string).collect()
+}
+
+// `render_preview_block` appended to `out`, its lines joined by newlines
+pub fn render_preview_block_to(m: &Model, out: &mut String) {
+    let total_width = m.render_width();
+    if total_width < MIN_BOX_WIDTH {
+        let line = styled_preview_line(m, total_width);
+        normalize_and_pad_into(std::iter::once(line.as_str()), total_width, PREVIEW_BLOCK_LINES, out);
+        return;
+    }
+    let box_width = total_width - 2;
     let w_i32: i32 = box_width.try_into().unwrap_or(i32::MAX);
-    let inner = STYLE_PREVIEW.render(&preview_line);
+    // inside the border and padding
+    let inner = styled_preview_line(m, box_width - 2);
     let preview_block = STYLE_PREVIEW_BOX.clone().width(w_i32).render(&inner);
-    let mut out: Vec<String> = preview_block.lines().map(|s| s.to_string()).collect();
     // Ensure the preview block occupies exactly PREVIEW_BLOCK_LINES lines by truncating or padding with empty lines.
-    out.truncate(PREVIEW_BLOCK_LINES);
-    while out.len() < PREVIEW_BLOCK_LINES {
-        out.push(String::new());
+    let mut lines = preview_block.lines();
+    for n in 0..PREVIEW_BLOCK_LINES {
+        if n > 0 {
+            out.push('\n');
+        }
+        out.push_str(lines.next().unwrap_or(""));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{CommandDef, Segment};
+    use crate::ui::model::{initial_model, PREVIEW_BLOCK_LINES};
+    use crate::ui::render::strip_ansi;
+
+    #[test]
+    fn embed_is_the_preview_on_a_single_line() {
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("git");
+        m.ast.push_subcommand("commit");
+        m.ast.add_flag("-m", "first line\nsecond line");
+        let embed = m.render_embed();
+        assert!(!embed.contains('\n'), "{embed:?}");
+        let plain = crate::ui::render::strip_ansi(&embed);
+        assert!(plain.starts_with("git commit -m "), "{plain}");
+        assert!(plain.contains("first line second line"), "{plain}");
+    }
+
+    #[test]
+    fn empty_state_hint_shows_until_a_command_is_chosen() {
+        let git = CommandDef { name: "git".to_string(), short: "Version control".to_string(), ..Default::default() };
+        let mut m = initial_model(vec![("git".to_string(), git.short.clone()), ("ls".to_string(), String::new())]);
+        m.source = crate::source::Source::new(crate::source::StaticSource::new(vec![git]));
+        m.update(crate::ui::Msg::WindowSize { width: 40, height: 12 });
+        let block = |m: &crate::ui::Model| strip_ansi(&m.render_preview_block().join("\n"));
+        let widths = |b: &str| b.lines().map(|l| l.chars().count()).collect::<Vec<_>>();
+
+        assert!(m.is_empty_state());
+        let empty = block(&m);
+        assert!(empty.contains("> type or pick a command"), "{empty}");
+        assert_eq!(empty.lines().count(), PREVIEW_BLOCK_LINES);
+
+        for r in m.keystroke_path_to("git").unwrap().chars() {
+            m.update(crate::ui::Msg::Rune(r));
+        }
+        assert!(!m.is_empty_state());
+        let chosen = block(&m);
+        assert!(!chosen.contains("type or pick"), "{chosen}");
+        assert!(chosen.contains("> git"), "{chosen}");
+        assert_eq!(widths(&chosen), widths(&empty));
+
+        // too narrow for the box, the hint is cut to the terminal
+        let mut m = initial_model(vec![("git".to_string(), String::new())]);
+        m.update(crate::ui::Msg::WindowSize { width: 4, height: 12 });
+        assert_eq!(block(&m).lines().next().unwrap().trim_end(), "> ty");
     }
-    out
 }
@@ -1,31 +1,209 @@
 use lipgloss::{Color, Style, rounded_border};
 use once_cell::sync::Lazy;
 
+// How many colors the terminal can show, from COLORTERM/TERM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+pub fn detect_color_depth(colorterm: &str, term: &str) -> ColorDepth {
+    let colorterm = colorterm.to_ascii_lowercase();
+    if colorterm == "truecolor" || colorterm == "24bit" || term.contains("direct") {
+        ColorDepth::TrueColor
+    } else if term.contains("256color") {
+        ColorDepth::Ansi256
+    } else if term.is_empty() {
+        // nothing to go on (not a terminal, or a test run): keep the colors as designed
+        ColorDepth::TrueColor
+    } else {
+        ColorDepth::Ansi16
+    }
+}
+
+static COLOR_DEPTH: Lazy<ColorDepth> = Lazy::new(|| {
+    let var = |k| std::env::var(k).unwrap_or_default();
+    detect_color_depth(&var("COLORTERM"), &var("TERM"))
+});
+
+// xterm's 6x6x6 cube levels
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// The xterm default 16 colors
+const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+// The RGB an xterm 256-color index stands for
+pub fn ansi256_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI16[index as usize],
+        16..=231 => {
+            let i = index - 16;
+            (CUBE[(i / 36) as usize], CUBE[(i / 6 % 6) as usize], CUBE[(i % 6) as usize])
+        }
+        _ => {
+            let v = 8 + 10 * (index - 232);
+            (v, v, v)
+        }
+    }
+}
+
+// The nearest 256-color index, from the color cube or the gray ramp (never the 16 colors, whose
+// RGB varies between terminals)
+pub fn ansi256_index(r: u8, g: u8, b: u8) -> u8 {
+    let level = |c: u8| (0..6).min_by_key(|&i| (CUBE[i] as i32 - c as i32).abs()).unwrap_or(0) as u8;
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray = 232 + (avg.saturating_sub(3) / 10).min(23) as u8;
+    [cube, gray]
+        .into_iter()
+        .min_by_key(|&i| distance((r, g, b), ansi256_rgb(i)))
+        .unwrap_or(cube)
+}
+
+pub fn ansi16_index(r: u8, g: u8, b: u8) -> u8 {
+    (0..16u8).min_by_key(|&i| distance((r, g, b), ANSI16[i as usize])).unwrap_or(0)
+}
+
+// A theme color as the terminal can show it: 24-bit where supported, else the nearest palette
+// entry as an indexed color (emitted as `38;5;n` or one of the 16 basic codes), so colors keep
+// their intended contrast instead of being approximated differently by each terminal
+pub fn palette_color(depth: ColorDepth, r: u8, g: u8, b: u8) -> Color {
+    match depth {
+        ColorDepth::TrueColor => Color::from_rgb(r, g, b),
+        ColorDepth::Ansi256 => Color::from(ansi256_index(r, g, b).to_string().as_str()),
+        ColorDepth::Ansi16 => Color::from(ansi16_index(r, g, b).to_string().as_str()),
+    }
+}
+
+// Theme colors go through here rather than Color::from_rgb
+pub fn rgb(r: u8, g: u8, b: u8) -> Color {
+    palette_color(*COLOR_DEPTH, r, g, b)
+}
+
 // Styles kept local to render module
 pub static STYLE_ACE: Lazy<Style> = Lazy::new(|| {
     Style::new()
-        .foreground(Color::from_rgb(238, 0, 238))
+        .foreground(rgb(238, 0, 238))
         .bold(true)
 });
 pub static STYLE_TYPED: Lazy<Style> = Lazy::new(|| {
     Style::new()
-        .foreground(Color::from_rgb(0, 0, 238))
+        .foreground(rgb(0, 0, 238))
         .bold(true)
 });
 pub static STYLE_PREVIEW: Lazy<Style> = Lazy::new(|| {
     Style::new()
-        .foreground(Color::from_rgb(0, 238, 238))
+        .foreground(rgb(0, 238, 238))
         .bold(true)
 });
+pub static STYLE_LEFT_UNIT: Lazy<Style> =
+    Lazy::new(|| Style::new().foreground(rgb(95, 135, 175)));
 pub static STYLE_LABEL: Lazy<Style> =
-    Lazy::new(|| Style::new().foreground(Color::from_rgb(200, 200, 200)));
+    Lazy::new(|| Style::new().foreground(rgb(200, 200, 200)));
+pub static STYLE_SELECTED: Lazy<Style> = Lazy::new(|| {
+    Style::new()
+        .foreground(rgb(0, 175, 0))
+        .bold(true)
+});
+// warnings outside the modeline: conflicting flags in the list, a command near the length limit
+// in the preview
+pub static STYLE_WARNING: Lazy<Style> = Lazy::new(|| {
+    Style::new()
+        .foreground(rgb(255, 215, 0))
+        .bold(true)
+});
 pub static STYLE_DESC: Lazy<Style> = Lazy::new(|| Style::new().faint(true));
 pub static STYLE_MODELINE: Lazy<Style> = Lazy::new(|| {
     Style::new()
-        .background(Color::from_rgb(95, 95, 95))
-        .foreground(Color::from_rgb(255, 255, 255))
+        .background(rgb(95, 95, 95))
+        .foreground(rgb(255, 255, 255))
         .padding(0, 1, 0, 1)
 });
+// the mode block at the modeline's left
+pub static STYLE_MODELINE_MODE: Lazy<Style> = Lazy::new(|| {
+    STYLE_MODELINE
+        .clone()
+        .background(rgb(101, 101, 101))
+        .padding(0, 1, 0, 1)
+        .bold(true)
+});
+// the typed buffer inside the mode block: the left unit, then the disambiguator runes
+pub static STYLE_MODELINE_TYPED: Lazy<Style> =
+    Lazy::new(|| STYLE_MODELINE_MODE.clone().padding(0, 0, 0, 0).foreground(rgb(135, 175, 255)));
+pub static STYLE_MODELINE_ACE: Lazy<Style> =
+    Lazy::new(|| STYLE_MODELINE_MODE.clone().padding(0, 0, 0, 0).foreground(rgb(238, 0, 238)));
+// a warning standing in for the modeline's counts, as STYLE_WARNING on the modeline background
+pub static STYLE_MODELINE_WARNING: Lazy<Style> = Lazy::new(|| {
+    STYLE_MODELINE
+        .clone()
+        .foreground(rgb(255, 215, 0))
+        .bold(true)
+        .padding(0, 0, 0, 0)
+});
+// the last error, standing in for the modeline's counts until the next key
+pub static STYLE_MODELINE_ERROR: Lazy<Style> = Lazy::new(|| {
+    STYLE_MODELINE
+        .clone()
+        .foreground(rgb(255, 95, 95))
+        .bold(true)
+        .padding(0, 0, 0, 0)
+});
 pub static STYLE_PREVIEW_BOX: Lazy<Style> =
     Lazy::new(|| Style::new().border(rounded_border()).padding(0, 1, 0, 1));
 pub static STYLE_LINENUM: Lazy<Style> = Lazy::new(|| Style::new().faint(true));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_downsample_to_the_nearest_palette_entry() {
+        assert_eq!(ansi256_index(238, 0, 238), 201);
+        assert_eq!(ansi256_index(95, 135, 175), 67);
+        assert_eq!(ansi256_index(128, 128, 128), 244);
+        assert_eq!(ansi256_rgb(201), (255, 0, 255));
+        assert_eq!(ansi16_index(255, 0, 0), 9);
+        assert_eq!(ansi16_index(0, 175, 0), 2);
+
+        assert_eq!(detect_color_depth("truecolor", "xterm-256color"), ColorDepth::TrueColor);
+        assert_eq!(detect_color_depth("", "xterm-256color"), ColorDepth::Ansi256);
+        assert_eq!(detect_color_depth("", "linux"), ColorDepth::Ansi16);
+    }
+
+    #[test]
+    fn limited_terminals_get_indexed_color_sequences() {
+        let fg = |depth| Style::new().foreground(palette_color(depth, 238, 0, 238)).render("x");
+        assert!(fg(ColorDepth::TrueColor).contains("38;2;238;0;238"), "{:?}", fg(ColorDepth::TrueColor));
+        assert_eq!(palette_color(ColorDepth::Ansi256, 238, 0, 238), Color::from("201"));
+        assert!(fg(ColorDepth::Ansi256).contains("38;5;201"), "{:?}", fg(ColorDepth::Ansi256));
+        // bright magenta, one of the 16 basic colors
+        assert_eq!(palette_color(ColorDepth::Ansi16, 238, 0, 238), Color::from("13"));
+        for depth in [ColorDepth::Ansi256, ColorDepth::Ansi16] {
+            assert!(!fg(depth).contains("38;2;"), "{:?}", fg(depth));
+        }
+    }
+}
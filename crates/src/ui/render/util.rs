@@ -1,14 +1,117 @@
 use lipgloss::Style;
 
 pub fn normalize_and_pad(lines: Vec<String>, total_width: usize, per: usize) -> String {
+    let mut out = String::new();
+    normalize_and_pad_into(lines.iter().map(String::as_str), total_width, per, &mut out);
+    out
+}
+
+// Same as `normalize_and_pad`, but borrows the lines and appends to `out`
+pub fn normalize_and_pad_into<'a>(lines: impl Iterator<Item = &'a str>, total_width: usize, per: usize, out: &mut String) {
     let line_style = Style::new().width(total_width as i32);
-    let mut normalized: Vec<String> = lines.into_iter().map(|l| line_style.render(&l)).collect();
-    if normalized.len() > per {
-        normalized.truncate(per);
-    } else {
-        while normalized.len() < per {
-            normalized.push(line_style.render(""));
+    let mut n = 0;
+    for l in lines.take(per) {
+        if n > 0 {
+            out.push('\n');
+        }
+        out.push_str(first_line(&line_style.render(l)));
+        n += 1;
+    }
+    if n < per {
+        let blank = line_style.render("");
+        while n < per {
+            if n > 0 {
+                out.push('\n');
+            }
+            out.push_str(&blank);
+            n += 1;
+        }
+    }
+}
+
+// A row wider than the terminal wraps when styled to its width; keep only the first physical
+// line so every row still occupies exactly one line.
+pub fn first_line(rendered: &str) -> &str {
+    rendered.split('\n').next().unwrap_or("")
+}
+
+// Descriptions from completion metadata may span lines (or carry tabs); a row shows them on
+// one line, each run of whitespace containing a line break or tab collapsed to a space.
+pub fn one_line(s: &str) -> std::borrow::Cow<'_, str> {
+    if !s.contains(['\n', '\r', '\t']) {
+        return std::borrow::Cow::Borrowed(s);
+    }
+    std::borrow::Cow::Owned(s.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+// Cut a styled line to its first `width` visible chars, keeping the escape sequences in it and
+// resetting the style after a cut so it can't bleed into what follows.
+pub fn truncate_visible(s: &str, width: usize) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut visible = 0;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            out.push(c);
+            out.push(chars.next().unwrap_or('['));
+            for c in chars.by_ref() {
+                out.push(c);
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+            continue;
         }
+        if visible == width {
+            out.push_str("\x1b[0m");
+            return out;
+        }
+        out.push(c);
+        visible += 1;
+    }
+    out
+}
+
+// Remove ANSI CSI sequences (ESC [ params, intermediates, final byte) leaving the visible text.
+// Public so embedders can assert on rendered output as this crate's own tests do.
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            chars.next();
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+// The lines of rendered output as they appear on screen (styling removed)
+pub fn visible_lines(s: &str) -> Vec<String> {
+    strip_ansi(s).lines().map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_ansi, visible_lines};
+
+    #[test]
+    fn strip_ansi_keeps_only_visible_text() {
+        let styled = "\x1b[1;38;2;238;0;238mgit\x1b[0m \x1b[2mcommit\x1b[0m\n\x1b[?25l--amend";
+        assert_eq!(strip_ansi(styled), "git commit\n--amend");
+        assert_eq!(visible_lines(styled), vec!["git commit", "--amend"]);
+        assert_eq!(strip_ansi("plain │ text"), "plain │ text");
+    }
+
+    #[test]
+    fn one_line_collapses_line_breaks_only_when_present() {
+        assert_eq!(super::one_line("two  spaces kept"), "two  spaces kept");
+        assert_eq!(super::one_line("first line\n  second\r\nthird\tcol"), "first line second third col");
     }
-    normalized.join("\n")
 }
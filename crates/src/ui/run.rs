@@ -1,13 +1,16 @@
 use crate::carapace;
+use crate::source::Source;
 use crate::ui::model::Model;
 use crate::ui::model::initial_model;
+use crate::ui::Settings;
 use bubbletea_rs::{
     Program, command::Cmd, event::KeyMsg, event::WindowSizeMsg, model::Model as TeaModel,
 };
 use crossterm::event::{KeyCode, KeyModifiers};
+use std::sync::Mutex;
 
 // helper to build forms for a FlagDef
-fn flag_forms(f: &crate::ast::FlagDef) -> Vec<String> {
+pub(crate) fn flag_forms(f: &crate::ast::FlagDef) -> Vec<String> {
     let mut forms = Vec::new();
     if !f.longhand.is_empty() {
         forms.push(format!("--{}", f.longhand));
@@ -18,86 +21,413 @@ fn flag_forms(f: &crate::ast::FlagDef) -> Vec<String> {
     forms
 }
 
-// Keep the interactive runner and the non-interactive parsing behavior here.
-pub fn run(initial_args: Vec<String>) -> Result<String, String> {
-    // preload carapace --list with descriptions
-    let entries = match carapace::list_with_desc() {
-        Ok(e) => e,
-        Err(err) => return Err(format!("carapace --list failed: {err}")),
-    };
-    let mut m = initial_model(entries);
+// ssh options taking a value, as ssh(1) lists them
+const SSH_VALUE_OPTIONS: &str = "BbcDEeFIiJLlmOopQRSWw";
 
-    if !initial_args.is_empty() {
-        // set root
-        let root = &initial_args[0];
-        match carapace::export(root) {
-            Ok(def) => {
-                m.ast.root = def.name.clone();
-                if !m.ast.stack.is_empty() {
-                    m.ast.stack[0].name = def.name.clone();
+// `ssh [options] <host>` at the start of `args`, split from the remote command after it
+fn split_remote_prefix(args: &[String]) -> Option<(&[String], &[String])> {
+    if args.first().map(String::as_str) != Some("ssh") {
+        return None;
+    }
+    let mut i = 1;
+    while let Some(opt) = args.get(i).and_then(|a| a.strip_prefix('-')) {
+        let takes_value = opt.len() == 1 && SSH_VALUE_OPTIONS.contains(opt);
+        i += if takes_value { 2 } else { 1 };
+    }
+    (i < args.len()).then(|| args.split_at(i + 1))
+}
+
+// Load argv (`<cmd> [args...]`) into the model: export the root, then match each token
+// against the current command's flags and subcommands, falling back to positionals.
+pub fn load_args(m: &mut Model, args: &[String]) -> Result<(), String> {
+    let args = match split_remote_prefix(args).filter(|_| m.settings.remote_commands) {
+        Some((prefix, remote)) => {
+            m.remote_prefix = prefix.to_vec();
+            remote
+        }
+        None => {
+            m.remote_prefix.clear();
+            args
+        }
+    };
+    if let Some(root) = args.first() {
+        match m.export_root(root) {
+            Ok((name, mut def)) => {
+                m.add_git_aliases(&mut def);
+                if m.ast.stack.is_empty() {
+                    m.ast = crate::ast::Segment::new_empty(&name);
+                } else {
+                    m.ast.root = name.clone();
+                    m.ast.stack[0].name = name.clone();
                 }
+                m.def_cache.insert(name, def.clone());
                 m.build_items_from_command(&def);
                 m.current = Some(def);
             }
-            Err(e) => return Err(format!("carapace {root} export failed: {e}")),
+            Err(e) if e.starts_with(carapace::NO_COMPLETION_DATA) => return Err(e),
+            Err(e) => return Err(format!("{} {root} export failed: {e}", m.source.name())),
         }
+        load_tokens(m, &args[1..]);
+    }
+    Ok(())
+}
 
-        // parse remaining tokens
-        let mut i = 1usize;
-        while i < initial_args.len() {
-            let tok = &initial_args[i];
-            if tok.starts_with('-') {
-                // flag form; find exact-form match among current.Flags
-                let mut matched = false;
-                if let Some(cur) = &m.current {
-                    for f in &cur.flags {
-                        for fm in flag_forms(f).iter() {
-                            if fm == tok {
-                                // add flag; if requires value and next arg exists and isn't a flag, consume it
-                                let mut val = String::new();
-                                if f.requires_value
-                                    && i + 1 < initial_args.len()
-                                    && !initial_args[i + 1].starts_with('-')
-                                {
-                                    val = initial_args[i + 1].clone();
-                                    i += 1;
-                                }
-                                m.ast.add_flag(fm, &val);
+// Whether `tok` names one of the current command's flags (`--long=value` included)
+fn is_known_flag(m: &Model, tok: &str) -> bool {
+    let form = tok.split_once('=').map_or(tok, |(f, _)| f);
+    m.current
+        .as_ref()
+        .is_some_and(|cur| cur.flags.iter().any(|f| flag_forms(f).iter().any(|fm| fm == form)))
+}
+
+// Split `-mhello` into a value-taking shorthand and its attached value
+fn split_attached_short<'a>(m: &Model, tok: &'a str) -> Option<(String, &'a str)> {
+    if tok.starts_with("--") {
+        return None;
+    }
+    let mut chars = tok.char_indices().skip(1);
+    let (_, short) = chars.next()?;
+    let (rest_at, _) = chars.next()?;
+    let cur = m.current.as_ref()?;
+    cur.flags
+        .iter()
+        .any(|f| f.requires_value && f.shorthand.chars().eq([short]))
+        .then(|| (format!("-{short}"), &tok[rest_at..]))
+}
+
+// Apply the tokens following the root to an already loaded model.
+pub(crate) fn load_tokens(m: &mut Model, args: &[String]) {
+    let mut i = 0usize;
+    while i < args.len() {
+        let tok = &args[i];
+        if tok.starts_with('-') {
+            // flag form; find exact-form match among current.Flags (`--long=value` and `-mvalue`
+            // included)
+            let (form, inline_val) = match tok.split_once('=') {
+                Some((f, v)) if tok.starts_with("--") => (f.to_string(), Some(v)),
+                _ if !is_known_flag(m, tok) => match split_attached_short(m, tok) {
+                    Some((f, v)) => (f, Some(v)),
+                    None => (tok.clone(), None),
+                },
+                _ => (tok.clone(), None),
+            };
+            let mut matched = false;
+            if let Some(cur) = &m.current {
+                for f in &cur.flags {
+                    for fm in flag_forms(f).iter() {
+                        if *fm == form {
+                            // add flag; a value-taking flag consumes the next arg as its value
+                            // unless that is another of the command's flags, so `-m -x` keeps
+                            // `-x` as the message
+                            let mut val = inline_val.unwrap_or_default().to_string();
+                            if inline_val.is_none() && f.variadic {
+                                // every word up to the next flag
+                                let values: Vec<String> =
+                                    args[i + 1..].iter().take_while(|a| !a.starts_with('-')).cloned().collect();
+                                i += values.len();
+                                let depth = m.ast.stack.len().saturating_sub(1);
+                                m.ast.add_flag_values_to_depth(depth, fm, &values);
                                 matched = true;
                                 break;
                             }
-                        }
-                        if matched {
+                            if inline_val.is_none()
+                                && f.requires_value
+                                && i + 1 < args.len()
+                                && !is_known_flag(m, &args[i + 1])
+                            {
+                                val = args[i + 1].clone();
+                                i += 1;
+                            }
+                            m.ast.add_flag(fm, &val);
+                            matched = true;
                             break;
                         }
                     }
-                }
-                if !matched {
-                    m.ast.add_positional(tok);
-                }
-                i += 1;
-                continue;
-            }
-            // not a flag: could be subcommand or positional
-            let mut found = false;
-            if let Some(cur) = m.current.clone() {
-                for sc in cur.subcommands.iter() {
-                    if sc.name == *tok || sc.aliases.iter().any(|a| a == tok) {
-                        m.ast.push_subcommand(&sc.name);
-                        m.current = Some(sc.clone());
-                        m.build_items_from_command(sc);
-                        found = true;
+                    if matched {
                         break;
                     }
                 }
             }
-            if !found {
+            if !matched {
                 m.ast.add_positional(tok);
             }
             i += 1;
+            continue;
+        }
+        // not a flag: could be subcommand or positional
+        if let Some(words) = m.git_alias_expansion(tok) {
+            load_tokens(m, &words);
+            i += 1;
+            continue;
+        }
+        let mut found = false;
+        if let Some(cur) = m.current.clone() {
+            for sc in cur.subcommands.iter() {
+                if sc.name == *tok || sc.aliases.iter().any(|a| a == tok) {
+                    m.ast.push_subcommand(&sc.name);
+                    let sc = m.forward_subcommand(sc).unwrap_or_else(|| sc.clone());
+                    m.current = Some(sc.clone());
+                    m.build_items_from_command(&sc);
+                    found = true;
+                    break;
+                }
+            }
+        }
+        if !found {
+            m.ast.add_positional(tok);
+        }
+        i += 1;
+    }
+}
+
+// Completion words (with descriptions) for `partial` in the model's current context: the
+// forms of the current candidates that start with it.
+pub fn completion_candidates(m: &Model, partial: &str) -> Vec<(String, String)> {
+    m.items
+        .iter()
+        .flat_map(|it| {
+            let desc = match &it.flag_def {
+                Some(fd) => fd.usage.clone(),
+                None => it.short.clone(),
+            };
+            it.forms.iter().map(move |f| (f.clone(), desc.clone()))
+        })
+        .filter(|(f, _)| f.starts_with(partial))
+        .collect()
+}
+
+// The word after a value-taking flag is that flag's value, not something to complete
+fn awaits_flag_value(m: &Model, prev: &str) -> bool {
+    m.current.as_ref().is_some_and(|cur| {
+        cur.flags
+            .iter()
+            .any(|f| f.requires_value && flag_forms(f).iter().any(|fm| fm == prev))
+    })
+}
+
+// The words before the cursor, the partial word under it and the quote left open in that word,
+// when invoked from bash completion
+fn comp_line_from_env() -> Option<(Vec<String>, String, Option<char>)> {
+    let line = std::env::var("COMP_LINE").ok()?;
+    let point = std::env::var("COMP_POINT").ok().and_then(|p| p.trim().parse::<usize>().ok());
+    let (words, partial) = crate::parse::split_at_cursor(&line, point);
+    let head: String = line.chars().take(point.unwrap_or(usize::MAX)).collect();
+    Some((words, partial, crate::parse::open_quote_at_end(&head)))
+}
+
+// The completion lines to print, one candidate each. Completions for a word typed after an
+// unmatched quote keep that quote, so the shell replaces `"co` with `"commit` rather than
+// dropping what the user typed. With a `descriptions` separator each line is the candidate and its
+// description joined by it: `candidate:description` for the zsh hook (`van --descriptions`, colons
+// in the candidate escaped as `\:`), `candidate<TAB>description` for fish (`--tab-descriptions`).
+pub fn completion_lines(cands: Vec<(String, String)>, quote: Option<char>, descriptions: Option<char>) -> String {
+    cands
+        .into_iter()
+        .map(|(word, desc)| {
+            let word = match quote {
+                Some(q) => format!("{q}{word}"),
+                None => word,
+            };
+            let desc = crate::ui::render::util::one_line(desc.trim());
+            match descriptions {
+                Some(_) if desc.is_empty() => word,
+                Some(':') => format!("{}:{desc}", word.replace(':', "\\:")),
+                Some(sep) => format!("{word}{sep}{desc}"),
+                None => word,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// `van --fzf [<cmd> [args...]]`: the candidates for the given command line as picker lines.
+// With `selection` (`van --fzf-select <line> ...`), apply that picked line instead and return the
+// resulting command line, ending in the chosen flag when it still needs a value.
+pub fn fzf(settings: Settings, args: &[String], selection: Option<&str>) -> Result<String, String> {
+    let mut m = listed_model(Source::default(), settings)?;
+    load_args(&mut m, args)?;
+    let Some(line) = selection else {
+        return Ok(m.flatten_for_fzf());
+    };
+    m.apply_fzf_line(line)?;
+    let mut out = m.preview_text();
+    if m.in_value_mode && !m.pending_form.is_empty() {
+        out.push(' ');
+        out.push_str(&m.pending_form);
+    }
+    Ok(out)
+}
+
+// The process that runs the finished command, per `settings.exec_mode`: `$SHELL -c <line>`, the
+// preview with every word quoted (`Model::shell_text`), or the command's words spawned directly
+// with a leading alias expanded. Pipelines and redirections always need the shell.
+pub fn exec_command(m: &Model) -> Option<std::process::Command> {
+    let needs_shell = !m.pipeline.is_empty() || !m.ast.redirections.is_empty();
+    let mut cmd = match m.settings.exec_mode {
+        crate::ui::ExecMode::Direct if !needs_shell => {
+            let mut words = m.ast.to_tokens();
+            if let Some(expansion) = words.first().and_then(|root| m.settings.aliases.get(root)) {
+                let expanded: Vec<String> = expansion.split_whitespace().map(String::from).collect();
+                words.splice(0..1, expanded);
+            }
+            // ssh runs what follows the host as one line in the remote shell
+            if !m.remote_prefix.is_empty() {
+                words = words.iter().map(|w| crate::parse::shell_word(w)).collect();
+            }
+            words.splice(0..0, m.remote_prefix.iter().cloned());
+            let (program, args) = words.split_first()?;
+            let mut cmd = std::process::Command::new(program);
+            cmd.args(args);
+            cmd
+        }
+        _ => {
+            let line = m.shell_text();
+            if line.is_empty() {
+                return None;
+            }
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+            let mut cmd = std::process::Command::new(shell);
+            cmd.arg("-c").arg(line);
+            cmd
+        }
+    };
+    cmd.stdin(std::process::Stdio::inherit())
+        .stdout(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::inherit());
+    Some(cmd)
+}
+
+// `van --exec <cmd> [args...]`: resolve the args as the non-interactive path does, then run the
+// command as Enter would instead of printing anything. A flag left without its value makes the
+// command incomplete, which is an error rather than something to run.
+pub fn exec(settings: Settings, args: &[String]) -> Result<std::process::ExitStatus, String> {
+    let args = command_line_args(args);
+    let mut m = listed_model(Source::default(), settings)?;
+    load_args(&mut m, &args)?;
+    for (depth, node) in m.ast.stack.iter().enumerate() {
+        let def = m.get_def_for_depth(depth);
+        let missing = node.flags.iter().find(|fi| {
+            fi.value.is_empty()
+                && def.as_ref().is_some_and(|d| {
+                    d.flags.iter().any(|f| f.requires_value && flag_forms(f).contains(&fi.form))
+                })
+        });
+        if let Some(fi) = missing {
+            return Err(format!("--exec: {} needs a value", fi.form));
+        }
+    }
+    let mut cmd = exec_command(&m).ok_or_else(|| "--exec: no command given".to_string())?;
+    cmd.status().map_err(|e| format!("failed to execute command: {e}"))
+}
+
+// The command line `args` resolve to, as the one-line embed (`van --embed`) shows it
+pub fn embed(settings: Settings, args: &[String]) -> Result<String, String> {
+    let args = command_line_args(args);
+    let mut m = listed_model(Source::default(), settings)?;
+    load_args(&mut m, &args)?;
+    Ok(m.render_embed())
+}
+
+// `van --replay <file>`: apply a recorded session to a fresh model and return the command it
+// built (what Enter finished, else the line as it was left)
+pub fn replay(settings: Settings, path: &std::path::Path) -> Result<String, String> {
+    let msgs = crate::ui::session::load(path)?;
+    let mut m = listed_model(Source::default(), settings)?;
+    crate::ui::session::replay(&mut m, &msgs);
+    if m.exit_preview.is_empty() {
+        return Ok(m.preview_text());
+    }
+    Ok(m.exit_preview.clone())
+}
+
+// A model with `settings`, showing the commands `source` lists
+fn listed_model(source: Source, settings: Settings) -> Result<Model, String> {
+    let entries = source
+        .list(settings.path_filter)
+        .map_err(|err| format!("{} --list failed: {err}", source.name()))?;
+    let mut m = initial_model(entries);
+    m.settings = settings;
+    m.source = source;
+    Ok(m)
+}
+
+// The words `args` stand for. A single argument holding a whole command line (`van "git commit
+// -m 'x y'"`, as prompts and editors pass it) is split the way a shell would; anything else is
+// taken word for word. A lone word that merely contains a space (a file name) is split too, so
+// callers meaning it literally pass it after the command.
+fn command_line_args(args: &[String]) -> Vec<String> {
+    match args {
+        [line] if line.contains(char::is_whitespace) => crate::parse::tokenize_command_line(line),
+        _ => args.to_vec(),
+    }
+}
+
+// The value command the last update asked for, run off the update path on the blocking pool so
+// a slow command doesn't freeze the UI; its output comes back as `Msg::ValueCandidates`
+pub fn value_command_task(m: &mut Model) -> Option<Cmd> {
+    let command = m.value_command_request.take()?;
+    Some(Box::pin(async move {
+        let run = command.clone();
+        let lines = tokio::task::spawn_blocking(move || {
+            crate::ui::model::value_command_lines(&run, crate::ui::model::VALUE_COMMAND_TIMEOUT)
+        })
+        .await
+        .unwrap_or_default();
+        Some(Box::new(crate::ui::Msg::ValueCandidates { command, lines }) as bubbletea_rs::event::Msg)
+    }))
+}
+
+// Source and settings for the interactive session's model, which bubbletea builds itself in
+// `init`
+static INTERACTIVE_SOURCE: Mutex<Option<(Source, Settings)>> = Mutex::new(None);
+
+// Keep the interactive runner and the non-interactive parsing behavior here.
+pub fn run(initial_args: Vec<String>) -> Result<String, String> {
+    run_with_source(Source::default(), Settings::from_env(), initial_args)
+}
+
+// `run` with command lists and definitions taken from `source` instead of carapace, and
+// `settings` instead of the environment's
+pub fn run_with_source(source: Source, settings: Settings, initial_args: Vec<String>) -> Result<String, String> {
+    let alt_screen = settings.alt_screen;
+    let mut m = listed_model(source.clone(), settings.clone())?;
+
+    // bash completion: COMP_LINE/COMP_POINT describe the raw line and cursor exactly, so they
+    // take precedence over the word list the hook passed along
+    if !initial_args.is_empty() {
+        if let Some((words, partial, quote)) = comp_line_from_env() {
+            if !words.is_empty() {
+                load_args(&mut m, &words)?;
+            }
+            let cands = completion_candidates(&m, &partial);
+            return Ok(completion_lines(cands, quote, m.settings.completion_descriptions));
         }
     }
 
+    let initial_args = command_line_args(&initial_args);
+    // a partial last word (`git comm`) completes to the matching candidates rather than being
+    // recorded as a positional
+    if let Some((last, head)) = initial_args.split_last().filter(|(_, head)| !head.is_empty()) {
+        load_args(&mut m, head)?;
+        let prev = head.last().map(String::as_str).unwrap_or_default();
+        // a hook passing the raw word (`"co`) leaves its opening quote on; match on the text
+        // inside and put the quote back on the completions
+        let quote = crate::parse::open_quote_at_end(last);
+        let last = match quote {
+            Some(_) => crate::parse::tokenize_command_line(last).concat(),
+            None => last.clone(),
+        };
+        if last.starts_with('-') || !awaits_flag_value(&m, prev) {
+            let cands = completion_candidates(&m, &last);
+            if !cands.is_empty() && !cands.iter().any(|(w, _)| *w == last) {
+                return Ok(completion_lines(cands, quote, m.settings.completion_descriptions));
+            }
+        }
+        load_tokens(&mut m, std::slice::from_ref(&last));
+    } else {
+        load_args(&mut m, &initial_args)?;
+    }
+
     // If initial_args were provided we are non-interactive: return the recorded preview (may be empty)
     if !initial_args.is_empty() {
         return Ok(m.exit_preview.clone());
@@ -111,8 +441,23 @@ pub fn run(initial_args: Vec<String>) -> Result<String, String> {
     impl TeaModel for TeaAdapter {
         fn init() -> (Self, Option<Cmd>) {
             // Preload entries for interactive session (best-effort)
-            let entries = carapace::list_with_desc().unwrap_or_default();
-            let model = initial_model(entries);
+            let (source, settings) = INTERACTIVE_SOURCE
+                .lock()
+                .ok()
+                .and_then(|mut s| s.take())
+                .unwrap_or_else(|| (Source::default(), Settings::from_env()));
+            let entries = source.list(settings.path_filter).unwrap_or_default();
+            let mut model = initial_model(entries);
+            model.settings = settings;
+            model.source = source;
+            model.value_history = crate::history::ValueHistory::load_default();
+            model.command_usage = crate::history::CommandUsage::load_default();
+            model.arrange_top_level();
+            if model.settings.remember_window_size {
+                if let Some(path) = crate::history::window_size_path() {
+                    model.restore_window_size(&path);
+                }
+            }
             (TeaAdapter { inner: model }, None)
         }
 
@@ -159,7 +504,22 @@ pub fn run(initial_args: Vec<String>) -> Result<String, String> {
                     KeyCode::Backspace => {
                         self.inner.update(crate::ui::Msg::KeyBackspace);
                     }
+                    KeyCode::Delete => {
+                        self.inner.update(crate::ui::Msg::ClearTyped);
+                    }
+                    KeyCode::Tab => {
+                        self.inner.update(crate::ui::Msg::KeyTab);
+                    }
+                    KeyCode::BackTab => {
+                        self.inner.update(crate::ui::Msg::BackTab);
+                    }
                     KeyCode::Esc => { /* handled above */ }
+                    KeyCode::Up if km.modifiers.contains(KeyModifiers::ALT) => {
+                        self.inner.update(crate::ui::Msg::MoveArgUp);
+                    }
+                    KeyCode::Down if km.modifiers.contains(KeyModifiers::ALT) => {
+                        self.inner.update(crate::ui::Msg::MoveArgDown);
+                    }
                     KeyCode::Up => {
                         self.inner.update(crate::ui::Msg::KeyUp);
                     }
@@ -168,14 +528,8 @@ pub fn run(initial_args: Vec<String>) -> Result<String, String> {
                     }
                     KeyCode::Char(ch) => {
                         if km.modifiers.contains(KeyModifiers::CONTROL) {
-                            match ch {
-                                'n' | 'N' => {
-                                    self.inner.update(crate::ui::Msg::KeyDown);
-                                }
-                                'p' | 'P' => {
-                                    self.inner.update(crate::ui::Msg::KeyUp);
-                                }
-                                _ => {}
+                            if let Some(msg) = crate::ui::keymap::ctrl_msg(*ch) {
+                                self.inner.update(msg);
                             }
                         } else if *ch == ' ' {
                             self.inner.update(crate::ui::Msg::KeySpace);
@@ -185,6 +539,10 @@ pub fn run(initial_args: Vec<String>) -> Result<String, String> {
                     }
                     _ => {}
                 }
+                return value_command_task(&mut self.inner);
+            }
+            if let Some(msg) = msg.downcast_ref::<crate::ui::Msg>() {
+                self.inner.update(msg.clone());
                 return None;
             }
             if let Some(ws) = msg.downcast_ref::<WindowSizeMsg>() {
@@ -192,6 +550,7 @@ pub fn run(initial_args: Vec<String>) -> Result<String, String> {
                     width: ws.width as usize,
                     height: ws.height as usize,
                 });
+                self.inner.remember_window_size(ws.width as usize, ws.height as usize);
                 return None;
             }
             None
@@ -202,17 +561,304 @@ pub fn run(initial_args: Vec<String>) -> Result<String, String> {
         }
     }
 
+    if let Ok(mut s) = INTERACTIVE_SOURCE.lock() {
+        *s = Some((source, settings));
+    }
     let builder = Program::<TeaAdapter>::builder()
-        .alt_screen(true)
+        .alt_screen(alt_screen)
         .signal_handler(true);
     let program = match builder.build() {
         Ok(p) => p,
         Err(e) => return Err(format!("failed to build program: {e:?}")),
     };
-    let final_adapter = match futures::executor::block_on(program.run()) {
+    let result = futures::executor::block_on(program.run());
+    // drawn inline, the cursor is left where the picker hid it
+    if !alt_screen {
+        let _ = crossterm::execute!(std::io::stderr(), crossterm::cursor::Show);
+    }
+    let final_adapter = match result {
         Ok(fa) => fa,
         Err(e) => return Err(format!("program error: {e:?}")),
     };
+    final_adapter.inner.save_window_size();
 
     Ok(final_adapter.inner.exit_preview.clone())
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::carapace::fake::{with_fake_carapace, EnvVars};
+    use crate::source::Source;
+    use crate::ui::Settings;
+
+    const GIT_SCRIPT: &str = r#"case "$1" in
+  --list) echo "git  Distributed version control" ;;
+  git) echo '{"Name":"git","Commands":[{"Name":"commit","Short":"Record changes","LocalFlags":[{"Longhand":"amend","Usage":"amend previous"},{"Longhand":"message","Shorthand":"m","Type":"string"}]},{"Name":"config"},{"Name":"clone"},{"Name":"push"}]}' ;;
+  *) exit 1 ;;
+esac"#;
+
+    fn with_comp_env<R>(line: &str, point: usize, f: impl FnOnce() -> R) -> R {
+        with_fake_carapace(GIT_SCRIPT, || {
+            let point = point.to_string();
+            let _env = EnvVars::set(&[("COMP_LINE", line), ("COMP_POINT", point.as_str()), ("VAN_NO_PATH_FILTER", "1")]);
+            f()
+        })
+    }
+
+    #[test]
+    fn comp_line_partial_word_drives_completion() {
+        let line = "git co";
+        let out = with_comp_env(line, line.len(), || super::run(vec!["git".into(), "co".into()])).unwrap();
+        let words: Vec<&str> = out.lines().collect();
+        assert_eq!(words, vec!["commit", "config"]);
+    }
+
+    #[test]
+    fn comp_point_mid_line_completes_the_word_under_the_cursor() {
+        // cursor after "git commit --a" while more text follows; the hook's args are stale
+        let line = "git commit --a -m msg";
+        let out = with_comp_env(line, 14, || super::run(vec!["git".into(), "commit".into(), "-m".into(), "msg".into()])).unwrap();
+        assert_eq!(out, "--amend");
+    }
+
+    #[test]
+    fn aliased_root_completes_like_the_real_command() {
+        let out = with_comp_env("g co", 4, || {
+            let _env = EnvVars::set(&[("VAN_ALIASES", "g=git")]);
+            super::run(vec!["g".into(), "co".into()])
+        })
+        .unwrap();
+        assert_eq!(out, "commit\nconfig");
+
+        with_fake_carapace(GIT_SCRIPT, || {
+            let mut m = crate::ui::initial_model(vec![]);
+            m.settings.aliases.insert("g".to_string(), "git".to_string());
+            super::load_args(&mut m, &["g".to_string(), "commit".to_string(), "--amend".to_string()]).unwrap();
+            assert_eq!(m.ast.render_preview(), "g commit --amend");
+        });
+    }
+
+    #[test]
+    fn ssh_host_prefix_completes_the_remote_command_locally() {
+        let words = |line: &str| line.split_whitespace().map(String::from).collect::<Vec<_>>();
+        with_fake_carapace(GIT_SCRIPT, || {
+            let mut m = crate::ui::initial_model(vec![]);
+            m.settings.remote_commands = true;
+            super::load_args(&mut m, &words("ssh box git")).unwrap();
+            let subs: Vec<String> = super::completion_candidates(&m, "co").into_iter().map(|(w, _)| w).collect();
+            assert_eq!(subs, vec!["commit", "config"]);
+            super::load_args(&mut m, &words("ssh -p 2222 box git commit --amend")).unwrap();
+            assert_eq!(m.preview_text(), "ssh -p 2222 box git commit --amend");
+
+            // a value with a space stays one word through the local and the remote shell
+            let mut m = crate::ui::initial_model(vec![]);
+            m.settings.remote_commands = true;
+            let mut args = words("ssh box git commit -m");
+            args.push("fix it".to_string());
+            super::load_args(&mut m, &args).unwrap();
+            let line = m.preview_text();
+            assert_eq!(line, "ssh box git commit -m \"'fix it'\"");
+            let local = crate::parse::tokenize_command_line(&line);
+            assert_eq!(crate::parse::tokenize_command_line(&local[2..].join(" ")), words("git commit -m").into_iter().chain(["fix it".to_string()]).collect::<Vec<_>>());
+            m.settings.exec_mode = crate::ui::ExecMode::Direct;
+            let direct: Vec<String> = super::exec_command(&m).unwrap().get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+            assert_eq!(direct, vec!["box", "git", "commit", "-m", "'fix it'"]);
+
+            // the prefix goes with the args that had it
+            super::load_args(&mut m, &words("git commit")).unwrap();
+            assert!(m.remote_prefix.is_empty());
+
+            let _env = EnvVars::set(&[("VAN_REMOTE_COMMANDS", "1")]);
+            assert_eq!(super::run(words("ssh box git co")).unwrap(), "commit\nconfig");
+        });
+    }
+
+    #[test]
+    fn partial_last_word_prints_matching_candidates() {
+        with_fake_carapace(GIT_SCRIPT, || {
+            assert_eq!(super::run(vec!["git".into(), "comm".into()]).unwrap(), "commit");
+            assert_eq!(super::run(vec!["git".into(), "co".into()]).unwrap(), "commit\nconfig");
+            assert_eq!(super::run(vec!["git".into(), "commit".into(), "--am".into()]).unwrap(), "--amend");
+            // complete words and flag values are loaded as before
+            assert_eq!(super::run(vec!["git".into(), "commit".into()]).unwrap(), "");
+            assert_eq!(super::run(vec!["git".into(), "commit".into(), "-m".into(), "co".into()]).unwrap(), "");
+        });
+    }
+
+    #[test]
+    fn a_lone_argument_with_spaces_is_read_as_a_command_line() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(super::command_line_args(&args(&["git commit -m 'x y'"])), args(&["git", "commit", "-m", "x y"]));
+        assert_eq!(super::command_line_args(&args(&["git"])), args(&["git"]));
+        assert_eq!(super::command_line_args(&args(&["cat", "a b"])), args(&["cat", "a b"]));
+        with_fake_carapace(GIT_SCRIPT, || {
+            assert_eq!(super::run(args(&["git co"])).unwrap(), "commit\nconfig");
+            let embed = |a: &[&str]| super::embed(Settings::from_env(), &args(a)).unwrap();
+            assert_eq!(embed(&["git commit -m 'x y'"]), embed(&["git", "commit", "-m", "x y"]));
+        });
+    }
+
+    #[test]
+    fn word_after_an_unmatched_quote_matches_on_its_unquoted_text() {
+        with_fake_carapace(GIT_SCRIPT, || {
+            assert_eq!(super::run(vec!["git".into(), "\"co".into()]).unwrap(), "\"commit\n\"config");
+            assert_eq!(super::run(vec!["git".into(), "'comm".into()]).unwrap(), "'commit");
+            assert_eq!(super::run(vec!["git".into(), "commit".into(), "\"--am".into()]).unwrap(), "\"--amend");
+        });
+        let line = "git \"co";
+        let out = with_comp_env(line, line.len(), || super::run(vec!["git".into(), "\"co".into()])).unwrap();
+        assert_eq!(out, "\"commit\n\"config");
+    }
+
+    #[test]
+    fn empty_export_is_a_distinct_error() {
+        let args = vec!["blank".to_string(), "x".to_string()];
+        let err = with_fake_carapace("echo '{}'", || {
+            let mut m = crate::ui::initial_model(vec![]);
+            super::load_args(&mut m, &args).unwrap_err()
+        });
+        assert_eq!(err, "no completion data for blank");
+    }
+
+    #[test]
+    fn typed_casing_kept_for_values_and_optionally_for_the_root() {
+        let args: Vec<String> = ["GIT", "commit", "-m", "Fix The Bug", "ReadMe.MD"].iter().map(|s| s.to_string()).collect();
+        with_fake_carapace(GIT_SCRIPT, || {
+            for preserve in [false, true] {
+                let mut m = crate::ui::initial_model(vec![("git".to_string(), String::new())]);
+                m.settings.preserve_root_case = preserve;
+                super::load_args(&mut m, &args).unwrap();
+                let top = m.ast.top().unwrap();
+                assert_eq!(top.name, "commit");
+                assert_eq!(top.flags[0].value, "Fix The Bug");
+                assert_eq!(top.positionals, vec!["ReadMe.MD".to_string()]);
+                assert_eq!(m.ast.root, if preserve { "GIT" } else { "git" });
+            }
+        });
+    }
+
+    #[test]
+    fn comp_line_first_word_completes_top_level_commands() {
+        let out = with_comp_env("gi", 2, || super::run(vec!["gi".into()])).unwrap();
+        assert_eq!(out, "git");
+    }
+
+    #[test]
+    fn static_source_drives_the_model_without_carapace() {
+        use crate::ast::{CommandDef, FlagDef};
+        use crate::source::{Source, StaticSource};
+        let commit = CommandDef {
+            name: "commit".to_string(),
+            short: "Record changes".to_string(),
+            flags: vec![FlagDef { longhand: "amend".to_string(), ..Default::default() }],
+            ..Default::default()
+        };
+        let source = Source::new(StaticSource::new(vec![CommandDef {
+            name: "git".to_string(),
+            subcommands: vec![commit],
+            ..Default::default()
+        }]));
+        // any carapace call would fail
+        with_fake_carapace("exit 1", || {
+            let mut m = crate::ui::initial_model(source.list(true).unwrap());
+            m.source = source.clone();
+            for r in "gc-a".chars() {
+                m.update(crate::ui::Msg::Rune(r));
+            }
+            assert_eq!(m.err, "");
+            assert_eq!(m.ast.render_preview(), "git commit --amend");
+
+            let out = super::run_with_source(source.clone(), Settings::from_env(), vec!["git".into(), "co".into()]).unwrap();
+            assert_eq!(out, "commit");
+            let err = super::run_with_source(source, Settings::from_env(), vec!["nope".into(), "x".into()]).unwrap_err();
+            assert_eq!(err, "no completion data for nope");
+        });
+    }
+
+    #[test]
+    fn list_all_includes_commands_not_on_path() {
+        let script = r#"echo "sh            POSIX shell"
+echo "van-offpath-tool-xyz  not installed here""#;
+        let complete_first_word = |settings| {
+            let _env = EnvVars::set(&[("COMP_LINE", "van-off"), ("COMP_POINT", "7")]);
+            super::run_with_source(Source::default(), settings, vec!["van-off".into()]).unwrap()
+        };
+        with_fake_carapace(script, || {
+            assert_eq!(complete_first_word(Settings::from_env()), "");
+            let all = Settings::from_env().with_flags(crate::ui::CliFlags { list_all: true, ..Default::default() });
+            assert_eq!(complete_first_word(all), "van-offpath-tool-xyz");
+        });
+    }
+
+    #[test]
+    fn the_shell_runs_the_fully_quoted_line_not_the_preview() {
+        let mut m = crate::ui::initial_model(vec![]);
+        m.ast = crate::ast::Segment::new_empty("git");
+        m.ast.push_subcommand("show");
+        m.ast.add_positional("HEAD@{1}");
+        assert_eq!(m.preview_text(), "git show HEAD@{1}");
+        let cmd = super::exec_command(&m).unwrap();
+        let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+        assert_eq!(args, vec!["-c", "git show 'HEAD@{1}'"]);
+    }
+
+    #[test]
+    fn exec_runs_the_resolved_command_through_the_chosen_mechanism() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("van-exec-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // the shim records its arguments, one per line, next to itself
+        let shim = dir.join("shim");
+        std::fs::write(&shim, "#!/bin/sh\nprintf '%s\\n' \"$@\" > \"$0.out\"\n").unwrap();
+        std::fs::set_permissions(&shim, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let shim = shim.to_string_lossy().to_string();
+        let recorded = || std::fs::read_to_string(format!("{shim}.out")).unwrap();
+        // carapace knows every command as one with a value-taking --name
+        let script = r#"echo "{\"Name\":\"$1\",\"LocalFlags\":[{\"Longhand\":\"name\",\"Type\":\"string\"}]}""#;
+        with_fake_carapace(script, || {
+            {
+                let _env = EnvVars::set(&[("VAN_EXEC_MODE", "direct")]);
+                let status = super::exec(Settings::from_env(), &[shim.clone(), "--name".into(), "a b".into(), "x".into()]).unwrap();
+                assert!(status.success());
+                assert_eq!(recorded(), "--name\na b\nx\n");
+                // the whole line as one argument is split as a shell would
+                let status = super::exec(Settings::from_env(), &[format!("{shim} --name 'c d'")]).unwrap();
+                assert!(status.success());
+                assert_eq!(recorded(), "--name\nc d\n");
+                let err = super::exec(Settings::from_env(), &[shim.clone(), "--name".into()]).unwrap_err();
+                assert_eq!(err, "--exec: --name needs a value");
+            }
+
+            // shell mode hands the previewed line to $SHELL -c; the shim stands in for the shell
+            let _env = EnvVars::set(&[("VAN_EXEC_MODE", "shell"), ("SHELL", shim.as_str())]);
+            let status = super::exec(Settings::from_env(), &["echo".into(), "--name".into(), "a b".into()]);
+            assert!(status.unwrap().success());
+            assert_eq!(recorded(), "-c\necho --name 'a b'\n");
+        });
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn descriptions_pair_each_candidate_with_its_short_or_usage() {
+        let run = |sep: char, args: &[&str]| {
+            let settings = Settings { completion_descriptions: Some(sep), ..Settings::from_env() };
+            super::run_with_source(Source::default(), settings, args.iter().map(|a| a.to_string()).collect())
+        };
+        with_fake_carapace(GIT_SCRIPT, || {
+            let subs = run(':', &["git", "co"]);
+            let flags = run(':', &["git", "commit", "--am"]);
+            let quoted = run(':', &["git", "\"comm"]);
+            let fish = run('\t', &["git", "co"]);
+            // config has no short help, so it is printed bare
+            assert_eq!(subs.unwrap(), "commit:Record changes\nconfig");
+            assert_eq!(flags.unwrap(), "--amend:amend previous");
+            assert_eq!(quoted.unwrap(), "\"commit:Record changes");
+            // fish's format: candidate, tab, description
+            assert_eq!(fish.unwrap(), "commit\tRecord changes\nconfig");
+        });
+        let cands = vec![("a:b".to_string(), "two\nlines".to_string())];
+        assert_eq!(super::completion_lines(cands.clone(), None, Some(':')), "a\\:b:two lines");
+        assert_eq!(super::completion_lines(cands.clone(), None, Some('\t')), "a:b\ttwo lines");
+        assert_eq!(super::completion_lines(cands, None, None), "a:b");
+    }
+}
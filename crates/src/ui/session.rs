@@ -0,0 +1,82 @@
+// A session as the messages its model was given: recorded while `VAN_RECORD=<file>` is set and
+// written there on exit, then applied to a fresh model by `van --replay <file>`. Gives bug
+// reports the exact keystrokes and makes demos scriptable.
+use crate::ui::Msg;
+use crate::ui::model::Model;
+use std::path::Path;
+
+pub fn to_json(msgs: &[Msg]) -> String {
+    serde_json::to_string_pretty(msgs).unwrap_or_default()
+}
+
+pub fn from_json(s: &str) -> Result<Vec<Msg>, String> {
+    serde_json::from_str(s).map_err(|e| format!("failed to parse session: {e}"))
+}
+
+pub fn load(path: &Path) -> Result<Vec<Msg>, String> {
+    let s = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    from_json(&s)
+}
+
+// Write what `m` recorded to `settings.record`; nothing when it wasn't recording
+pub fn save(m: &Model) -> Result<(), String> {
+    let (Some(msgs), Some(path)) = (&m.recording, &m.settings.record) else {
+        return Ok(());
+    };
+    std::fs::write(path, to_json(msgs)).map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
+
+pub fn replay(m: &mut Model, msgs: &[Msg]) {
+    for msg in msgs {
+        m.update(msg.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{CommandDef, FlagDef};
+    use crate::source::{Source, StaticSource};
+    use crate::ui::model::initial_model;
+
+    #[test]
+    fn a_recorded_session_replays_to_the_same_command() {
+        let message = FlagDef {
+            longhand: "message".to_string(),
+            shorthand: "m".to_string(),
+            requires_value: true,
+            ..Default::default()
+        };
+        let commit = CommandDef { name: "commit".to_string(), flags: vec![message], ..Default::default() };
+        let git = CommandDef { name: "git".to_string(), subcommands: vec![commit], ..Default::default() };
+        let source = Source::new(StaticSource::new(vec![git]));
+        let fresh = || {
+            let mut m = initial_model(source.list(false).unwrap());
+            m.source = source.clone();
+            m
+        };
+
+        let mut m = fresh();
+        m.recording = Some(vec![]);
+        m.update(Msg::WindowSize { width: 80, height: 24 });
+        for c in "gc-m".chars() {
+            m.update(Msg::Rune(c));
+        }
+        assert!(m.in_value_mode);
+        "fix it".chars().for_each(|c| m.update(if c == ' ' { Msg::KeySpace } else { Msg::Rune(c) }));
+        m.update(Msg::KeyEnter);
+        m.update(Msg::KeyEnter);
+        assert_eq!(m.exit_preview, "git commit -m 'fix it'");
+
+        let json = to_json(m.recording.as_ref().unwrap());
+        let msgs = from_json(&json).unwrap();
+        assert_eq!(msgs.len(), 13);
+        let mut replayed = fresh();
+        replay(&mut replayed, &msgs);
+        assert_eq!(replayed.exit_preview, m.exit_preview);
+        assert_eq!(replayed.preview_text(), m.preview_text());
+        assert!(replayed.recording.is_none());
+
+        assert!(from_json("[\"NoSuchKey\"]").unwrap_err().starts_with("failed to parse session"));
+    }
+}
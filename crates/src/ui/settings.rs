@@ -0,0 +1,458 @@
+// User-tunable UI behavior. Defaults keep the historical behavior; the binary reads
+// overrides from `VAN_*` environment variables via `Settings::from_env`.
+
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+// van's own flags, given ahead of the command in any order (`van --all git co`). They override
+// the matching settings for that invocation (`Settings::with_flags`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CliFlags {
+    // `--all`: list top-level commands that are not on PATH, whatever VAN_NO_PATH_FILTER says
+    pub list_all: bool,
+    // `--descriptions` / `--tab-descriptions`: print completions as `candidate:description`
+    // or `candidate<TAB>description`, as fish reads them (see `completion_lines`)
+    pub completion_descriptions: Option<char>,
+    // `--no-alt-screen`: draw the interactive picker inline, below the prompt
+    pub no_alt_screen: bool,
+}
+
+impl CliFlags {
+    // Take the flags off the front of `args`, up to the first word that isn't one (the
+    // command, whose own flags may share these names)
+    pub fn take_leading(args: &mut Vec<String>) -> Self {
+        let mut flags = CliFlags::default();
+        let n = args.iter().take_while(|a| flags.set(a)).count();
+        args.drain(..n);
+        flags
+    }
+
+    fn set(&mut self, arg: &str) -> bool {
+        match arg {
+            "--all" => self.list_all = true,
+            "--descriptions" => self.completion_descriptions = Some(':'),
+            "--tab-descriptions" => self.completion_descriptions = Some('\t'),
+            "--no-alt-screen" => self.no_alt_screen = true,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Where van's configuration belongs: $VAN_CONFIG_DIR, else $XDG_CONFIG_HOME/van, else
+// ~/.config/van. Settings come from the environment; `van --doctor` checks it is usable.
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(d) = env::var_os("VAN_CONFIG_DIR").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(d));
+    }
+    if let Some(d) = env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(d).join("van"));
+    }
+    env::var_os("HOME")
+        .filter(|d| !d.is_empty())
+        .map(|h| PathBuf::from(h).join(".config").join("van"))
+}
+
+// Whether to draw on the alternate screen: not when asked not to, nor on terminals known to
+// lack it (no TERM, `dumb`, Emacs' `eterm`), where switching corrupts the scrollback
+pub fn use_alt_screen(term: Option<&str>, disabled: bool) -> bool {
+    if disabled {
+        return false;
+    }
+    match term.map(str::trim) {
+        None | Some("") | Some("dumb") | Some("unknown") => false,
+        Some(t) => !t.starts_with("eterm"),
+    }
+}
+
+// Which kinds of candidate are committed as soon as typing narrows the list down to them.
+// Others stay highlighted until Enter, e.g. to read a subcommand's description first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoSelect {
+    Always,
+    FlagsOnly,
+    Never,
+}
+
+impl AutoSelect {
+    pub fn allows(self, kind: &str) -> bool {
+        match self {
+            AutoSelect::Always => true,
+            AutoSelect::FlagsOnly => kind == "flag",
+            AutoSelect::Never => false,
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "always" | "all" | "1" | "true" | "on" => Some(AutoSelect::Always),
+            "flags" | "flags-only" => Some(AutoSelect::FlagsOnly),
+            "never" | "none" | "0" | "false" | "off" => Some(AutoSelect::Never),
+            _ => None,
+        }
+    }
+}
+
+// How a finished command is run: handed to `$SHELL -c` as previewed, or spawned directly from
+// its words without a shell (no expansion, aliases resolved by van)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecMode {
+    Shell,
+    Direct,
+}
+
+impl ExecMode {
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "shell" | "sh" => Some(ExecMode::Shell),
+            "direct" | "exec" => Some(ExecMode::Direct),
+            _ => None,
+        }
+    }
+}
+
+// Where the top-level list puts the commands most often run from van: nowhere special, first,
+// or alone (the whole list still comes back when typing matches none of them)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrequentCommands {
+    Off,
+    First,
+    Only,
+}
+
+impl FrequentCommands {
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" | "0" | "false" | "no" => Some(FrequentCommands::Off),
+            "first" | "1" | "true" | "yes" => Some(FrequentCommands::First),
+            "only" => Some(FrequentCommands::Only),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    // show a "(N flags, M subcommands; K hidden)" summary in the modeline
+    pub show_counts: bool,
+    // only list top-level commands resolvable on PATH
+    pub path_filter: bool,
+    pub auto_select: AutoSelect,
+    // group flags under a header per originating command when inside subcommands
+    pub group_flags: bool,
+    // offer a --help flag on commands whose export lacks one
+    pub synthesize_help: bool,
+    // show pagination as a scrollbar column beside the list instead of "Page X/Y" text
+    pub scrollbar: bool,
+    // alias -> command used for roots that carapace cannot export itself (e.g. `g` -> `git`)
+    pub aliases: HashMap<String, String>,
+    // after choosing a command that only takes positionals, start argument input right away
+    pub auto_positional: bool,
+    // render the shared left-unit of each form (first rune, or `--`) in its own color
+    pub style_left_unit: bool,
+    // prefix each candidate with the keys that select it, e.g. `[--m]`
+    pub show_keystrokes: bool,
+    // follow the value being typed with its length, e.g. `[23]`
+    pub value_counter: bool,
+    // show those keys in their own column left of the numbers instead (`s │ 1 │ serve`)
+    pub key_column: bool,
+    // number candidates from 1 on every page; by default numbers continue across pages (page 2
+    // of a 10-row list starts at 11), so the same number always picks the same candidate
+    pub per_page_numbers: bool,
+    // with a key set here, digits select by number only after it (as after Ctrl-T) and are
+    // otherwise typed like letters; unset, a leading digit starts numeric selection by itself
+    pub numeric_prefix: Option<char>,
+    // describe the finished command in the modeline from each level's short help
+    pub describe_command: bool,
+    // when typing matches no candidate's name, list those whose description contains it
+    pub match_descriptions: bool,
+    // name the whole subcommand path in the modeline (`git › remote › add`), keeping at most
+    // this many levels; 0 shows just the innermost one
+    pub breadcrumb_depth: usize,
+    // save the terminal size in the state dir on exit and start the next session at it
+    pub remember_window_size: bool,
+    // pair each printed completion with its description after this separator, for shells that
+    // can show them: ':' for zsh, a tab for fish
+    pub completion_descriptions: Option<char>,
+    pub exec_mode: ExecMode,
+    pub frequent_commands: FrequentCommands,
+    // draw the interactive picker on the alternate screen (see `use_alt_screen`)
+    pub alt_screen: bool,
+    // flags taking `key=value` pairs, as "<command> <form>" or a bare form for any command
+    pub kv_flags: Vec<String>,
+    // flags whose values are offered from a shell command's output lines, as ("<command> <form>"
+    // or a bare form, command); empty by default since each one spawns a process
+    pub value_commands: Vec<(String, String)>,
+    // warn in the modeline once the built command nears this many bytes (Linux caps a single
+    // argument string at 128 KiB; ARG_MAX for the whole command line is larger)
+    pub max_command_len: usize,
+    // keep the root as typed (`GIT`) when it matches a command only case-insensitively;
+    // subcommands and flags always use canonical casing, positionals and values are verbatim
+    pub preserve_root_case: bool,
+    // complete a metadata-less subcommand from its `<root>-<sub>` binary (`cargo-foo`) if on PATH
+    pub forward_subcommands: bool,
+    // offer the aliases in git's configuration as `git` subcommands (runs `git config`)
+    pub git_aliases: bool,
+    // positionals matching any of these go into the preview unquoted (see DEFAULT_VERBATIM_WORDS)
+    pub verbatim_words: Vec<regex::Regex>,
+    // treat `ssh <host>` like a wrapper: complete the remote command after it from the local
+    // definitions, best-effort, keeping `ssh <host>` in the preview
+    pub remote_commands: bool,
+    // record the interactive session's messages and write them here on exit (`van --replay`)
+    pub record: Option<std::path::PathBuf>,
+}
+
+// git revision syntax that quoting would obscure: reflog/upstream suffixes (`HEAD@{2}`,
+// `@{u}`). Only words whose braces are their sole shell syntax are left bare (see
+// `Segment::render_preview_keeping`).
+pub const DEFAULT_VERBATIM_WORDS: &[&str] = &[r"^\S*@\{[^}]*\}\S*$"];
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            show_counts: true,
+            path_filter: true,
+            auto_select: AutoSelect::Always,
+            group_flags: false,
+            synthesize_help: false,
+            scrollbar: false,
+            aliases: HashMap::new(),
+            auto_positional: false,
+            style_left_unit: false,
+            show_keystrokes: false,
+            value_counter: true,
+            key_column: false,
+            per_page_numbers: false,
+            numeric_prefix: None,
+            describe_command: false,
+            match_descriptions: false,
+            breadcrumb_depth: 0,
+            remember_window_size: false,
+            completion_descriptions: None,
+            exec_mode: ExecMode::Shell,
+            frequent_commands: FrequentCommands::Off,
+            alt_screen: true,
+            kv_flags: vec!["git -c".to_string()],
+            value_commands: vec![],
+            max_command_len: 128 * 1024,
+            preserve_root_case: false,
+            forward_subcommands: false,
+            git_aliases: false,
+            verbatim_words: DEFAULT_VERBATIM_WORDS
+                .iter()
+                .filter_map(|p| regex::Regex::new(p).ok())
+                .collect(),
+            remote_commands: false,
+            record: None,
+        }
+    }
+}
+
+impl Settings {
+    // These settings with the invocation's `flags` applied over them
+    pub fn with_flags(mut self, flags: CliFlags) -> Self {
+        if flags.list_all {
+            self.path_filter = false;
+        }
+        if flags.completion_descriptions.is_some() {
+            self.completion_descriptions = flags.completion_descriptions;
+        }
+        if flags.no_alt_screen {
+            self.alt_screen = false;
+        }
+        self
+    }
+
+    pub fn from_env() -> Self {
+        let mut s = Settings::default();
+        if let Some(v) = env_bool("VAN_SHOW_COUNTS") {
+            s.show_counts = v;
+        }
+        if let Some(v) = env_bool("VAN_NO_PATH_FILTER") {
+            s.path_filter = !v;
+        }
+        let no_alt_screen = env_bool("VAN_NO_ALT_SCREEN").unwrap_or(false);
+        s.alt_screen = use_alt_screen(env::var("TERM").ok().as_deref(), no_alt_screen);
+        if let Some(v) = env_bool("VAN_GROUP_FLAGS") {
+            s.group_flags = v;
+        }
+        if let Some(v) = env_bool("VAN_SYNTHESIZE_HELP") {
+            s.synthesize_help = v;
+        }
+        if let Some(v) = env_bool("VAN_SCROLLBAR") {
+            s.scrollbar = v;
+        }
+        if let Some(v) = env_bool("VAN_AUTO_POSITIONAL") {
+            s.auto_positional = v;
+        }
+        if let Some(v) = env_bool("VAN_STYLE_LEFT_UNIT") {
+            s.style_left_unit = v;
+        }
+        if let Some(v) = env_bool("VAN_SHOW_KEYSTROKES") {
+            s.show_keystrokes = v;
+        }
+        if let Some(v) = env_bool("VAN_VALUE_COUNTER") {
+            s.value_counter = v;
+        }
+        if let Some(v) = env_bool("VAN_KEY_COLUMN") {
+            s.key_column = v;
+        }
+        if let Some(v) = env_bool("VAN_PER_PAGE_NUMBERS") {
+            s.per_page_numbers = v;
+        }
+        if let Ok(v) = env::var("VAN_NUMERIC_PREFIX") {
+            s.numeric_prefix = v.trim().chars().next();
+        }
+        if let Some(v) = env_bool("VAN_DESCRIBE_COMMAND") {
+            s.describe_command = v;
+        }
+        if let Some(v) = env_bool("VAN_MATCH_DESCRIPTIONS") {
+            s.match_descriptions = v;
+        }
+        if let Some(v) = env::var("VAN_BREADCRUMB_DEPTH").ok().and_then(|v| v.trim().parse().ok()) {
+            s.breadcrumb_depth = v;
+        }
+        if let Some(v) = env_bool("VAN_REMEMBER_WINDOW_SIZE") {
+            s.remember_window_size = v;
+        }
+        if let Ok(v) = env::var("VAN_KV_FLAGS") {
+            s.kv_flags = v.split(',').map(|e| e.trim().to_string()).filter(|e| !e.is_empty()).collect();
+        }
+        // `<command> <form>=<shell command>` entries separated by `;`
+        if let Ok(v) = env::var("VAN_VALUE_COMMANDS") {
+            s.value_commands = v
+                .split(';')
+                .filter_map(|e| e.split_once('='))
+                .map(|(flag, cmd)| (flag.trim().to_string(), cmd.trim().to_string()))
+                .filter(|(flag, cmd)| !flag.is_empty() && !cmd.is_empty())
+                .collect();
+        }
+        if let Some(v) = env::var("VAN_MAX_COMMAND_LEN").ok().and_then(|v| v.trim().parse().ok()) {
+            s.max_command_len = v;
+        }
+        if let Some(v) = env_bool("VAN_PRESERVE_ROOT_CASE") {
+            s.preserve_root_case = v;
+        }
+        if let Some(v) = env_bool("VAN_FORWARD_SUBCOMMANDS") {
+            s.forward_subcommands = v;
+        }
+        if let Some(v) = env_bool("VAN_GIT_ALIASES") {
+            s.git_aliases = v;
+        }
+        // a single regex (combine patterns with `|`) replacing the defaults; empty keeps none
+        if let Ok(v) = env::var("VAN_VERBATIM_WORDS") {
+            if v.trim().is_empty() {
+                s.verbatim_words.clear();
+            } else if let Ok(re) = regex::Regex::new(v.trim()) {
+                s.verbatim_words = vec![re];
+            }
+        }
+        if let Some(v) = env_bool("VAN_REMOTE_COMMANDS") {
+            s.remote_commands = v;
+        }
+        if let Some(v) = env::var_os("VAN_RECORD").filter(|v| !v.is_empty()) {
+            s.record = Some(v.into());
+        }
+        if let Ok(v) = env::var("VAN_ALIASES") {
+            s.aliases = parse_aliases(&v);
+        }
+        if let Some(v) = env::var("VAN_AUTO_SELECT").ok().and_then(|v| AutoSelect::parse(&v)) {
+            s.auto_select = v;
+        }
+        if let Some(v) = env::var("VAN_EXEC_MODE").ok().and_then(|v| ExecMode::parse(&v)) {
+            s.exec_mode = v;
+        }
+        if let Some(v) = env::var("VAN_FREQUENT_COMMANDS").ok().and_then(|v| FrequentCommands::parse(&v)) {
+            s.frequent_commands = v;
+        }
+        s
+    }
+
+    // The command an alias stands for: the first word of its expansion
+    pub fn resolve_alias(&self, name: &str) -> Option<&str> {
+        self.aliases
+            .get(name)
+            .and_then(|v| v.split_whitespace().next())
+    }
+}
+
+// Parse `g=git,k=kubectl`; newline-separated `alias g='git'` lines (the shell's `alias`
+// output) are accepted as well.
+pub fn parse_aliases(s: &str) -> HashMap<String, String> {
+    s.split([',', '\n'])
+        .filter_map(|entry| {
+            let entry = entry.trim();
+            let entry = entry.strip_prefix("alias ").unwrap_or(entry).trim();
+            let (name, value) = entry.split_once('=')?;
+            let value = value.trim().trim_matches(|c| c == '\'' || c == '"').trim();
+            if name.trim().is_empty() || value.is_empty() {
+                return None;
+            }
+            Some((name.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+// parse a boolean-ish environment variable; unset or unrecognized values yield None
+fn env_bool(name: &str) -> Option<bool> {
+    let v = env::var(name).ok()?;
+    match v.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_aliases;
+
+    #[test]
+    fn parse_aliases_accepts_pairs_and_shell_alias_output() {
+        let a = parse_aliases("g=git, k=kubectl");
+        assert_eq!(a["g"], "git");
+        assert_eq!(a["k"], "kubectl");
+
+        let a = parse_aliases("alias g='git'\nalias gl='git log --oneline'\nbogus\n");
+        assert_eq!(a.len(), 2);
+        assert_eq!(a["gl"], "git log --oneline");
+
+        let s = super::Settings { aliases: a, ..Default::default() };
+        assert_eq!(s.resolve_alias("gl"), Some("git"));
+        assert_eq!(s.resolve_alias("git"), None);
+    }
+
+    #[test]
+    fn leading_flags_are_taken_up_to_the_command() {
+        use super::CliFlags;
+        let words = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+        let mut args = words("--descriptions --no-alt-screen --all git log --all");
+        let flags = CliFlags::take_leading(&mut args);
+        assert!(flags.list_all);
+        assert_eq!(args, words("git log --all"));
+        let settings = super::Settings::default().with_flags(flags);
+        assert!(!settings.path_filter);
+        assert_eq!(settings.completion_descriptions, Some(':'));
+        assert!(!settings.alt_screen);
+
+        let mut args = words("--tab-descriptions git");
+        assert_eq!(CliFlags::take_leading(&mut args).completion_descriptions, Some('\t'));
+        assert_eq!(args, words("git"));
+
+        let mut args = words("git --all");
+        assert_eq!(CliFlags::take_leading(&mut args), CliFlags::default());
+        assert_eq!(args, words("git --all"));
+    }
+
+    #[test]
+    fn alt_screen_is_skipped_when_disabled_or_unsupported() {
+        use super::use_alt_screen;
+        assert!(use_alt_screen(Some("xterm-256color"), false));
+        assert!(use_alt_screen(Some("screen"), false));
+        assert!(!use_alt_screen(Some("xterm-256color"), true));
+        for term in [None, Some(""), Some("dumb"), Some("unknown"), Some("eterm-color")] {
+            assert!(!use_alt_screen(term, false), "{term:?}");
+        }
+    }
+}
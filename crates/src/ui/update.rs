@@ -1,63 +1,146 @@
 use crate::acekey::assign_ace_keys;
-use crate::carapace;
 use crate::ui::model::ChooseItem;
 use crate::ui::model::Model;
+use crate::ui::model::ValueSource;
 use bubbletea_widgets::Viewport;
 use std::collections::HashMap;
 
 pub fn handle_update(m: &mut Model, msg: crate::ui::Msg) {
+    if let Some(recording) = &mut m.recording {
+        recording.push(msg.clone());
+    }
+    // an error is shown until the user's next action
+    if msg.is_user_action() {
+        m.err.clear();
+    }
     match msg {
         crate::ui::Msg::WindowSize { width, height } => handle_window_size(m, width, height),
         crate::ui::Msg::KeyBackspace => handle_key_backspace(m),
         crate::ui::Msg::KeyEnter => handle_key_enter(m),
         crate::ui::Msg::KeySpace => handle_key_space(m),
+        crate::ui::Msg::KeyTab => handle_key_tab(m),
+        crate::ui::Msg::BackTab => handle_back_tab(m),
+        crate::ui::Msg::ToggleNumeric => handle_toggle_numeric(m),
         crate::ui::Msg::KeyEsc => handle_key_esc(m),
         crate::ui::Msg::KeyDown => handle_key_down(m),
         crate::ui::Msg::KeyUp => handle_key_up(m),
+        crate::ui::Msg::MoveArgUp => {
+            m.ast.move_focused(false);
+        }
+        crate::ui::Msg::MoveArgDown => {
+            m.ast.move_focused(true);
+        }
+        crate::ui::Msg::ClearTyped => handle_clear_typed(m),
+        crate::ui::Msg::CycleKindFilter => {
+            if m.in_value_mode {
+                return;
+            }
+            m.kind_filter = m.kind_filter.next();
+            if let Some(cur) = m.current.clone() {
+                m.build_items_from_command(&cur);
+            }
+            // numbers and typed keys referred to the old list
+            handle_clear_typed(m);
+        }
+        crate::ui::Msg::ClearNode => {
+            if !m.in_value_mode && m.ast.clear_top() {
+                update_viewport_after_typed(m);
+            }
+        }
+        crate::ui::Msg::ToggleLongUsage => {
+            m.long_usage = !m.long_usage;
+            update_viewport_after_typed(m);
+        }
+        crate::ui::Msg::ToggleExplain => handle_toggle_explain(m),
         crate::ui::Msg::Rune(r) => handle_rune(m, r),
+        crate::ui::Msg::ValueCandidates { command, lines } => m.receive_value_candidates(&command, lines),
+    }
+}
+
+// The explain panel's lines come out of the list's, so the frame keeps its height; it stays
+// closed when that would leave the list no row (or the height isn't known yet)
+fn handle_toggle_explain(m: &mut Model) {
+    if !m.explain && m.per_page <= crate::ui::model::EXPLAIN_LINES {
+        return;
+    }
+    let height = m.per_page + m.reserved_lines;
+    m.explain = !m.explain;
+    if m.explain {
+        m.reserved_lines += crate::ui::model::EXPLAIN_LINES;
+    } else {
+        m.reserved_lines -= crate::ui::model::EXPLAIN_LINES;
     }
+    handle_window_size(m, m.screen_width, height);
 }
 
 fn handle_window_size(m: &mut Model, width: usize, height: usize) {
     m.screen_width = width;
-    m.per_page = height.saturating_sub(crate::ui::model::RESERVED_LINES);
-    m.vp = Viewport::new(m.per_page, m.screen_width);
+    m.per_page = height.saturating_sub(m.reserved_lines);
+    m.vp = Viewport::new(m.screen_width, m.per_page);
     let visible = m.render_visible_items();
     let total_pages = if visible.is_empty() {
         1
     } else {
-        visible.len().div_ceil(m.per_page)
+        m.list_row_count(&visible).div_ceil(m.per_page)
     };
     if m.page >= total_pages {
         m.page = 0;
     }
     let list_content = m.render_list_content(&visible);
-    m.vp.set_content(&list_content);
+    m.sync_viewport(&list_content);
     if !m.typed.is_empty() {
         m.vp.goto_top();
     }
 }
 
 fn handle_key_backspace(m: &mut Model) {
+    if m.in_value_mode {
+        // back from the value of a key=value flag into its key
+        if m.pending_value.is_empty() {
+            if let Some(key) = m.pending_kv_key.take() {
+                m.pending_value = key;
+                m.suggestion_idx = 0;
+                return;
+            }
+        }
+        m.pending_value.pop();
+        m.suggestion_idx = 0;
+        return;
+    }
     if !m.typed.is_empty() {
         m.typed.pop();
         m.typed_raw.pop();
         // If typed_raw becomes empty, clear numeric_baseline since numeric mode ended
-        if m.typed_raw.is_empty() {
+        // (unless it was entered explicitly, then it lasts until toggled off)
+        if m.typed_raw.is_empty() && !m.numeric_locked {
             m.numeric_baseline = None;
         }
         return;
     }
+    // back into the command before the last `|`, `&&` or `||`
+    if m.ast.root.is_empty() {
+        if let Some((seg, _)) = m.pipeline.pop() {
+            m.ast = seg;
+            restore_current_after_pop(m);
+            return;
+        }
+    }
+    // a redirection made after the last flag or positional goes first, and one made before them
+    // waits its turn
+    if m.ast.undoes_redirection() {
+        m.ast.remove_last();
+        return;
+    }
 
     if let Some(top) = m.ast.top() {
         if !m.ast.root.is_empty()
-            && m.ast.stack.len() == 1
+            && m.depth() == 1
             && top.flags.is_empty()
             && top.positionals.is_empty()
         {
-            match carapace::list_with_desc() {
+            match m.source.list(m.settings.path_filter) {
                 Ok(entries) => {
-                    set_items_from_carapace_entries(m, entries);
+                    set_items_from_entries(m, entries);
                     return;
                 }
                 Err(e) => {
@@ -68,9 +151,9 @@ fn handle_key_backspace(m: &mut Model) {
         }
     }
 
-    let before = m.ast.stack.len();
+    let before = m.depth();
     m.ast.remove_last();
-    let after = m.ast.stack.len();
+    let after = m.depth();
     if after < before {
         restore_current_after_pop(m);
     }
@@ -78,6 +161,16 @@ fn handle_key_backspace(m: &mut Model) {
 
 fn handle_key_enter(m: &mut Model) {
     if m.in_value_mode {
+        if let Some(mut redirect) = m.pending_redirect.take() {
+            if !m.pending_value.is_empty() {
+                let (crate::ast::Redirection::Input(file) | crate::ast::Redirection::Output { file, .. }) =
+                    &mut redirect;
+                *file = std::mem::take(&mut m.pending_value);
+                m.ast.add_redirection(redirect);
+            }
+            m.in_value_mode = false;
+            return;
+        }
         if m.pending_pos {
             if !m.pending_value.is_empty() {
                 m.ast.add_positional(&m.pending_value);
@@ -88,41 +181,313 @@ fn handle_key_enter(m: &mut Model) {
             return;
         }
         if let Some(_fd) = &m.pending_flag {
-            m.ast
-                .add_flag_to_depth(m.pending_depth, &m.pending_form, &m.pending_value);
+            // key=value flags take the key first, unless the whole pair was typed at once
+            if m.pending_is_kv() {
+                match m.pending_kv_key.take() {
+                    Some(key) => m.pending_value = format!("{key}={}", m.pending_value),
+                    None if m.pending_value.contains('=') => {}
+                    None => {
+                        if !m.pending_value.is_empty() {
+                            m.pending_kv_key = Some(std::mem::take(&mut m.pending_value));
+                            m.suggestion_idx = 0;
+                        }
+                        return;
+                    }
+                }
+            }
+            // a variadic flag takes each (shell-quoted) word typed as a value of its own
+            if m.pending_flag.as_ref().is_some_and(|fd| fd.variadic) {
+                let values = crate::parse::tokenize_command_line(&m.pending_value);
+                m.ast.add_flag_values_to_depth(m.pending_depth, &m.pending_form, &values);
+            } else {
+                m.ast
+                    .add_flag_to_depth(m.pending_depth, &m.pending_form, &m.pending_value);
+            }
+            if let Some(key) = m.pending_value_key() {
+                if let Err(e) = m.value_history.record(&key, &m.pending_value) {
+                    m.err = e;
+                }
+            }
             m.in_value_mode = false;
             m.pending_flag = None;
+            m.value_candidates.clear();
             m.pending_form.clear();
             m.pending_value.clear();
             return;
         }
     }
-    let preview = m.ast.render_preview();
+    if select_held_candidate(m) {
+        return;
+    }
+    // found by its description rather than its name: the first one listed
+    if let Some(it) = m.description_matches().into_iter().next() {
+        let form = it.forms.first().cloned().unwrap_or_default();
+        select_item(m, &it, &form);
+        return;
+    }
+    if typed_root_is_unlisted(m) {
+        force_export_typed_root(m);
+        return;
+    }
+    // conflicting flags are resolved before anything runs
+    if let Some((_, forms)) = m.conflicting_flags().into_iter().next() {
+        m.err = format!("{} can't be used together; drop all but one", forms.join(", "));
+        return;
+    }
+    // a line ending in `|`, `&&` or `||` still needs its next command
+    if m.ast.root.is_empty() && !m.pipeline.is_empty() {
+        return;
+    }
+    let preview = m.preview_text();
     if preview.is_empty() {
         return;
     }
     m.exit_preview = preview.clone();
+    // every command of the line counts as run, for `settings.frequent_commands` (when on)
+    if m.settings.frequent_commands == crate::ui::FrequentCommands::Off {
+        return;
+    }
+    let roots: Vec<String> = m.pipeline.iter().map(|(seg, _)| seg.root.clone()).chain([m.ast.root.clone()]).collect();
+    for root in roots {
+        if let Err(e) = m.command_usage.record(&root) {
+            m.err = e;
+        }
+    }
+}
+
+// With auto-select disabled for its kind, the last candidate left by typing waits for Enter
+fn select_held_candidate(m: &mut Model) -> bool {
+    if m.typed_raw.is_empty() || m.numeric_baseline.is_some() {
+        return false;
+    }
+    let visible = m.render_visible_items();
+    if visible.len() != 1 || m.settings.auto_select.allows(&visible[0].kind) {
+        return false;
+    }
+    let it = visible[0].clone();
+    let chosen_form = it.forms.first().cloned().unwrap_or_default();
+    select_item(m, &it, &chosen_form)
+}
+
+// At the top-level list, a typed name that no listed command starts with may still be known
+// to the completion source (e.g. filtered out because it is not on PATH here).
+fn typed_root_is_unlisted(m: &Model) -> bool {
+    if m.current.is_some() || !m.ast.root.is_empty() || m.typed_raw.is_empty() {
+        return false;
+    }
+    if m.numeric_baseline.is_some() {
+        return false;
+    }
+    let typed_lower = m.typed_raw.to_lowercase();
+    !m.items
+        .iter()
+        .any(|it| it.forms.iter().any(|f| f.to_lowercase().starts_with(&typed_lower)))
+}
+
+fn force_export_typed_root(m: &mut Model) {
+    let name = m.typed_raw.clone();
+    match m.export_root(&name) {
+        Ok((root, def)) => apply_loaded_root(m, &root, def),
+        Err(e) => m.err = e,
+    }
 }
 
 fn handle_key_space(m: &mut Model) {
+    if m.in_value_mode {
+        push_value_char(m, ' ');
+        return;
+    }
+    // a positional needs a command to belong to
+    if m.depth() == 0 {
+        return;
+    }
     m.in_value_mode = true;
     m.pending_pos = true;
 }
 
+// Tab completes like readline: extend the typed buffer to the longest common prefix of the
+// forms that start with it, or select the candidate outright when only one remains.
+fn handle_key_tab(m: &mut Model) {
+    if m.in_value_mode {
+        let before = m.pending_value.clone();
+        accept_value_suggestion(m);
+        if m.pending_value != before {
+            m.tab_undo.push((before, m.pending_value.clone()));
+        }
+        return;
+    }
+    if m.numeric_baseline.is_some() {
+        return;
+    }
+    let typed_lower = m.typed_raw.to_lowercase();
+    let matches: Vec<(usize, String)> = m
+        .items
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, it)| it.forms.iter().map(move |f| (idx, f.clone())))
+        .filter(|(_, f)| f.to_lowercase().starts_with(&typed_lower))
+        .collect();
+    if matches.is_empty() {
+        return;
+    }
+
+    let first_item = matches[0].0;
+    if matches.iter().all(|(idx, _)| *idx == first_item) {
+        let it = m.items[first_item].clone();
+        let chosen_form = matches[0].1.clone();
+        select_item(m, &it, &chosen_form);
+        return;
+    }
+
+    let forms: Vec<String> = matches.into_iter().map(|(_, f)| f).collect();
+    let common = common_prefix_len(&forms);
+    let typed_len = m.typed_raw.chars().count();
+    if common <= typed_len {
+        return;
+    }
+    let extension: String = forms[0].chars().skip(typed_len).take(common - typed_len).collect();
+    m.tab_undo.push((m.typed_raw.clone(), format!("{}{extension}", m.typed_raw)));
+    m.typed_raw.push_str(&extension);
+    m.typed.push_str(&extension.to_lowercase());
+    m.page = 0;
+    update_viewport_after_typed(m);
+}
+
+// Shift+Tab reverses Tab: it takes back the last completion Tab made to the buffer being edited
+// (as long as it hasn't been edited since), otherwise steps backward through the candidates --
+// the previous value suggestion, or the previous page of the list.
+fn handle_back_tab(m: &mut Model) {
+    let buffer = if m.in_value_mode { &m.pending_value } else { &m.typed_raw };
+    if m.tab_undo.last().is_some_and(|(_, after)| after == buffer) {
+        let (before, _) = m.tab_undo.pop().unwrap_or_default();
+        if m.in_value_mode {
+            m.pending_value = before;
+        } else {
+            m.typed = before.to_lowercase();
+            m.typed_raw = before;
+            m.page = 0;
+            update_viewport_after_typed(m);
+        }
+        return;
+    }
+    m.tab_undo.clear();
+    if m.in_value_mode {
+        move_value_suggestion(m, false);
+    } else {
+        handle_key_up(m);
+    }
+}
+
+// Length (in chars) of the case-insensitive common prefix shared by all forms
+fn common_prefix_len(forms: &[String]) -> usize {
+    let Some(first) = forms.first() else {
+        return 0;
+    };
+    let first: Vec<char> = first.to_lowercase().chars().collect();
+    forms.iter().skip(1).fold(first.len(), |acc, f| {
+        f.to_lowercase()
+            .chars()
+            .zip(first.iter())
+            .take(acc)
+            .take_while(|(a, b)| a == *b)
+            .count()
+    })
+}
+
+// Explicitly enter or leave numeric selection, independent of the digit heuristics
+fn handle_toggle_numeric(m: &mut Model) {
+    if m.in_value_mode {
+        return;
+    }
+    if m.numeric_locked || m.numeric_baseline.is_some() {
+        m.numeric_locked = false;
+        m.numeric_baseline = None;
+    } else {
+        m.numeric_locked = true;
+        m.numeric_baseline = Some(visible_baseline_indices(m));
+    }
+    clear_typed(m);
+    m.page = 0;
+    update_viewport_after_typed(m);
+}
+
+// Empty the typed buffer (the value being typed, in value mode) and leave numeric filtering,
+// so the full candidate list shows again; the AST is untouched
+fn handle_clear_typed(m: &mut Model) {
+    if m.in_value_mode {
+        m.pending_value.clear();
+        m.suggestion_idx = 0;
+        return;
+    }
+    clear_typed(m);
+    m.numeric_baseline = None;
+    m.numeric_locked = false;
+    m.page = 0;
+    update_viewport_after_typed(m);
+}
+
+// Value input: free text, with remembered values for the flag as suggestions
+fn push_value_char(m: &mut Model, ch: char) {
+    m.suggestion_idx = 0;
+    // `=` ends the key of a key=value flag
+    if ch == '=' && m.pending_kv_key.is_none() && !m.pending_value.is_empty() && m.pending_is_kv() {
+        m.pending_kv_key = Some(std::mem::take(&mut m.pending_value));
+        return;
+    }
+    m.pending_value.push(ch);
+}
+
+fn accept_value_suggestion(m: &mut Model) {
+    let groups = m.value_suggestion_groups();
+    let from_env = groups.first().is_some_and(|(s, _)| *s == ValueSource::EnvVars);
+    let suggestions: Vec<String> = groups.into_iter().flat_map(|(_, values)| values).collect();
+    if let Some(v) = suggestions.get(m.suggestion_idx).or(suggestions.first()) {
+        // a completed variable reference is meant for the shell to expand
+        if from_env {
+            let name = &v[v.rfind('$').map_or(0, |i| i + 1)..];
+            if !m.ast.live_vars.iter().any(|n| n == name) {
+                m.ast.live_vars.push(name.to_string());
+            }
+        }
+        m.pending_value = v.clone();
+        m.suggestion_idx = 0;
+    }
+}
+
+fn move_value_suggestion(m: &mut Model, down: bool) {
+    let n = m.value_suggestions().len();
+    if n == 0 {
+        return;
+    }
+    m.suggestion_idx = if down {
+        (m.suggestion_idx + 1) % n
+    } else {
+        (m.suggestion_idx + n - 1) % n
+    };
+}
+
 fn handle_key_esc(m: &mut Model) {
     if m.in_value_mode {
         m.in_value_mode = false;
         m.pending_flag = None;
+        m.value_candidates.clear();
         m.pending_form.clear();
         m.pending_pos = false;
         m.pending_depth = 0;
         m.pending_value.clear();
+        m.pending_kv_key = None;
+        m.pending_redirect = None;
+        m.suggestion_idx = 0;
     }
 }
 
 fn handle_key_down(m: &mut Model) {
+    if m.in_value_mode {
+        move_value_suggestion(m, true);
+        return;
+    }
     let visible = m.render_visible_items();
-    let total = visible.len();
+    let total = m.list_row_count(&visible);
     let per = if m.per_page == 0 { total } else { m.per_page };
     if per == 0 {
         return;
@@ -136,17 +501,21 @@ fn handle_key_down(m: &mut Model) {
         m.page += 1;
     }
     let list_content = m.render_list_content(&visible);
-    m.vp.set_content(&list_content);
+    m.sync_viewport(&list_content);
     m.vp.goto_top();
 }
 
 fn handle_key_up(m: &mut Model) {
+    if m.in_value_mode {
+        move_value_suggestion(m, false);
+        return;
+    }
     if m.page > 0 {
         m.page -= 1;
     }
     let visible = m.render_visible_items();
     let list_content = m.render_list_content(&visible);
-    m.vp.set_content(&list_content);
+    m.sync_viewport(&list_content);
 }
 
 fn clear_typed(m: &mut Model) {
@@ -154,6 +523,33 @@ fn clear_typed(m: &mut Model) {
     m.typed_raw.clear();
 }
 
+// With `auto_positional`, a command offering nothing but positional arguments goes straight
+// to argument input, as if Space had been pressed.
+fn prompt_positional_if_only_args(m: &mut Model) {
+    if !m.settings.auto_positional {
+        return;
+    }
+    let only_args = m.current.as_ref().is_some_and(|def| {
+        def.takes_args && def.subcommands.iter().all(|sc| sc.hidden) && def.flags.iter().all(|f| f.hidden)
+    });
+    if only_args {
+        m.in_value_mode = true;
+        m.pending_pos = true;
+    }
+}
+
+// Choosing the subcommand that was just entered again (a stale candidate from a repeated key)
+// must not push it twice (`git commit commit`); a command with a same-named child still descends.
+fn is_repeated_selection(m: &Model, cmd_name: &str) -> bool {
+    let Some(current) = &m.current else {
+        return false;
+    };
+    m.depth() > 1
+        && m.ast.top().is_some_and(|top| top.name == cmd_name && top.flags.is_empty() && top.positionals.is_empty())
+        && current.name == cmd_name
+        && !current.subcommands.iter().any(|sc| sc.name == cmd_name)
+}
+
 fn handle_command_choice(m: &mut Model, it: &ChooseItem, chosen_form: &str) -> bool {
     let cmd_name = if let Some(cd) = &it.cmd_def {
         cd.name.clone()
@@ -162,9 +558,11 @@ fn handle_command_choice(m: &mut Model, it: &ChooseItem, chosen_form: &str) -> b
     };
 
     if m.current.is_none() && m.ast.root.is_empty() {
-        match carapace::export(&cmd_name) {
-            Ok(def) => {
-                apply_loaded_command(m, def);
+        match m.source.export(&cmd_name) {
+            Ok(mut def) => {
+                m.add_git_aliases(&mut def);
+                let root = m.root_display(&m.typed_raw, &def);
+                apply_loaded_root(m, &root, def);
                 return true;
             }
             Err(e) => {
@@ -174,21 +572,34 @@ fn handle_command_choice(m: &mut Model, it: &ChooseItem, chosen_form: &str) -> b
         }
     }
 
+    if is_repeated_selection(m, &cmd_name) {
+        clear_typed(m);
+        return true;
+    }
+    if let Some(words) = m.git_alias_expansion(&cmd_name) {
+        crate::ui::run::load_tokens(m, &words);
+        clear_typed(m);
+        prompt_positional_if_only_args(m);
+        return true;
+    }
     m.ast.push_subcommand(&cmd_name);
 
     if let Some(subdef) = &it.cmd_def {
+        let subdef = m.forward_subcommand(subdef).unwrap_or_else(|| subdef.clone());
         m.current = Some(subdef.clone());
-        m.build_items_from_command(subdef);
+        m.build_items_from_command(&subdef);
         clear_typed(m);
+        prompt_positional_if_only_args(m);
         return true;
     }
 
-    match carapace::export(chosen_form) {
+    match m.source.export(chosen_form) {
         Ok(def) => {
             m.def_cache.insert(def.name.clone(), def.clone());
             m.current = Some(def.clone());
             m.build_items_from_command(&def);
             clear_typed(m);
+            prompt_positional_if_only_args(m);
             true
         }
         Err(e) => {
@@ -204,6 +615,23 @@ fn handle_flag_choice(
     chosen_form: &str,
     depth: usize,
 ) -> bool {
+    // a `--color`/`--no-color` pair cycles unset → `--color` → `--no-color` → unset
+    if !fd.negation.is_empty() {
+        let neg = format!("--{}", fd.negation);
+        match m.ast.switch_state(depth, &crate::ui::run::flag_forms(fd), &neg) {
+            crate::ast::SwitchState::Unset => {}
+            crate::ast::SwitchState::On(from) => {
+                m.ast.replace_flag_form(depth, &from, &neg);
+                clear_typed(m);
+                return true;
+            }
+            crate::ast::SwitchState::Off => {
+                m.ast.remove_flag_from_depth(&neg, depth);
+                clear_typed(m);
+                return true;
+            }
+        }
+    }
     if m.ast.remove_flag_from_depth(chosen_form, depth) {
         clear_typed(m);
         return true;
@@ -213,6 +641,7 @@ fn handle_flag_choice(
         m.pending_flag = Some(fd.clone());
         m.pending_form = chosen_form.to_string();
         m.pending_depth = depth;
+        m.request_value_candidates();
         clear_typed(m);
         return true;
     }
@@ -222,6 +651,17 @@ fn handle_flag_choice(
     true
 }
 
+// Select an item through the handler matching its kind
+pub(crate) fn select_item(m: &mut Model, it: &ChooseItem, chosen_form: &str) -> bool {
+    if it.kind == "cmd" {
+        return handle_command_choice(m, it, chosen_form);
+    }
+    if let Some(fd) = &it.flag_def {
+        return handle_flag_choice(m, fd, chosen_form, it.depth);
+    }
+    false
+}
+
 fn update_typed_for_rune(m: &mut Model, r: char, was_numeric: bool) {
     // Handles all non-initial-numeric-capture typed updates
     if r.is_ascii_digit() && was_numeric {
@@ -282,28 +722,61 @@ fn simulate_alpha_treatment(m: &Model, r: char, was_numeric: bool) -> bool {
 }
 
 fn handle_rune(m: &mut Model, r: char) {
+    if m.in_value_mode {
+        // `>>`: the second `>` makes the pending redirection append
+        if r == '>' && m.pending_value.is_empty() {
+            if let Some(crate::ast::Redirection::Output { append, .. }) = &mut m.pending_redirect {
+                *append = true;
+                return;
+            }
+        }
+        push_value_char(m, r);
+        return;
+    }
+    if handle_shell_operator(m, r) {
+        return;
+    }
+    if m.settings.numeric_prefix == Some(r) {
+        handle_toggle_numeric(m);
+        return;
+    }
     let s = r.to_string();
     if !crate::acekey::is_single_ace_rune(&s) {
         return;
     }
 
+    if m.numeric_locked {
+        handle_locked_numeric_rune(m, r);
+        return;
+    }
+
     let was_numeric = m.typed_raw.chars().all(|c| c.is_ascii_digit()) && !m.typed_raw.is_empty();
 
     // If incoming rune is a digit starting a potential numeric mode, treat it as numeric
-    // only when simulate_alpha_treatment returns false. Flattened for readability.
-    if r.is_ascii_digit() && !was_numeric && !simulate_alpha_treatment(m, r, was_numeric) {
+    // only when simulate_alpha_treatment returns false (and no prefix key is required).
+    let implicit_numeric = m.settings.numeric_prefix.is_none();
+    if implicit_numeric && r.is_ascii_digit() && !was_numeric && !simulate_alpha_treatment(m, r, was_numeric) {
         capture_numeric_baseline(m, r);
     } else {
         update_typed_for_rune(m, r, was_numeric);
     }
 
-    let (forms, form_map) = forms_and_form_map(m);
-    let assignments = assign_ace_keys(&forms, &m.typed_raw);
+    let (mut forms, mut form_map) = forms_and_form_map(m);
+    let mut assignments = assign_ace_keys(&forms, &m.typed_raw);
 
-    if process_numeric_selection(m) {
+    if implicit_numeric && process_numeric_selection(m) {
         return;
     }
 
+    if assignments.is_none() && m.numeric_baseline.is_none() && fall_back_to_top_level(m) {
+        (forms, form_map) = forms_and_form_map(m);
+        assignments = assign_ace_keys(&forms, &m.typed_raw);
+    }
+    if assignments.is_none() && m.numeric_baseline.is_none() && show_whole_top_level(m) {
+        (forms, form_map) = forms_and_form_map(m);
+        assignments = assign_ace_keys(&forms, &m.typed_raw);
+    }
+
     if let Some(asg) = assignments {
         if try_immediate_assignment_selection(m, asg, &forms, &form_map) {
             return;
@@ -313,8 +786,87 @@ fn handle_rune(m: &mut Model, r: char) {
     update_viewport_after_typed(m);
 }
 
+// Shell operators typed after a chosen command: `|`, `&&` and `||` finish it and go back to
+// the top-level list for the next one; `>`, `>>` and `<` prompt for the file to redirect to
+// or from. Returns false for any other rune.
+fn handle_shell_operator(m: &mut Model, r: char) -> bool {
+    use crate::ast::{BinaryOp, Redirection};
+    if !matches!(r, '|' | '&' | '>' | '<') {
+        return false;
+    }
+    if m.ast.root.is_empty() {
+        // the second character of `||` (`&&` is what a lone `&` already gives)
+        if r == '|' && m.typed_raw.is_empty() {
+            if let Some((_, op @ BinaryOp::Pipe)) = m.pipeline.last_mut() {
+                *op = BinaryOp::Or;
+            }
+        }
+        return true;
+    }
+    clear_typed(m);
+    m.numeric_baseline = None;
+    m.numeric_locked = false;
+    m.page = 0;
+    match r {
+        '>' | '<' => {
+            m.pending_redirect = Some(if r == '>' {
+                Redirection::Output { file: String::new(), append: false }
+            } else {
+                Redirection::Input(String::new())
+            });
+            m.in_value_mode = true;
+            m.pending_value.clear();
+        }
+        _ => {
+            let op = if r == '|' { BinaryOp::Pipe } else { BinaryOp::And };
+            m.pipeline.push((std::mem::take(&mut m.ast), op));
+            let entries = if m.top_level.is_empty() {
+                m.source.list(m.settings.path_filter).unwrap_or_else(|e| {
+                    m.err = e;
+                    vec![]
+                })
+            } else {
+                m.top_level.clone()
+            };
+            set_items_from_entries(m, entries);
+        }
+    }
+    true
+}
+
+// In explicit numeric mode only digits count; the selected item ends the mode
+fn handle_locked_numeric_rune(m: &mut Model, r: char) {
+    if !r.is_ascii_digit() {
+        return;
+    }
+    m.typed_raw.push(r);
+    m.typed.push(r);
+    m.page = 0;
+    if process_numeric_selection(m) {
+        m.numeric_locked = false;
+        return;
+    }
+    update_viewport_after_typed(m);
+}
+
 fn capture_numeric_baseline(m: &mut Model, r: char) {
-    let visible_snapshot = m.render_visible_items();
+    let baseline_indices = visible_baseline_indices(m);
+    m.numeric_baseline = Some(baseline_indices);
+    m.typed_raw.clear();
+    m.typed.clear();
+    m.typed_raw.push(r);
+    m.typed.push(r.to_ascii_lowercase());
+    m.page = 0;
+}
+
+// Indices into `items` of the currently visible candidates (all items when none match); with
+// per-page numbers only those on the current page, which the numbers then count from 1
+fn visible_baseline_indices(m: &Model) -> Vec<usize> {
+    let mut visible_snapshot = m.render_visible_items();
+    if m.settings.per_page_numbers {
+        let on_page = crate::ui::render::list::page_item_range(m, &visible_snapshot);
+        visible_snapshot = visible_snapshot.drain(on_page).collect();
+    }
     let mut baseline_indices: Vec<usize> = visible_snapshot
         .iter()
         .filter_map(|vis| {
@@ -327,16 +879,41 @@ fn capture_numeric_baseline(m: &mut Model, r: char) {
     if baseline_indices.is_empty() {
         baseline_indices = (0..m.items.len()).collect();
     }
+    baseline_indices
+}
 
-    m.numeric_baseline = Some(baseline_indices);
-    m.typed_raw.clear();
-    m.typed.clear();
-    m.typed_raw.push(r);
-    m.typed.push(r.to_ascii_lowercase());
-    m.page = 0;
+// At a root nothing has been added to yet, text matching none of its candidates may be the
+// start of another top-level command: the list goes back to those (as Backspace would),
+// keeping what was typed, so the usual selection picks the new root
+fn fall_back_to_top_level(m: &mut Model) -> bool {
+    let pristine = m.depth() == 1
+        && !m.ast.root.is_empty()
+        && m.ast.top().is_some_and(|n| n.flags.is_empty() && n.positionals.is_empty());
+    let typed = m.typed_raw.to_lowercase();
+    if !pristine || typed.is_empty() || !m.top_level.iter().any(|(name, _)| name.to_lowercase().starts_with(&typed)) {
+        return false;
+    }
+    let (typed_raw, typed) = (m.typed_raw.clone(), m.typed.clone());
+    set_items_from_entries(m, m.top_level.clone());
+    m.typed_raw = typed_raw;
+    m.typed = typed;
+    true
 }
 
-fn set_items_from_carapace_entries(m: &mut Model, entries: Vec<(String, String)>) {
+// With `FrequentCommands::Only`, text matching none of the frequent commands brings back the
+// whole top-level list, keeping what was typed
+fn show_whole_top_level(m: &mut Model) -> bool {
+    if !m.ast.root.is_empty() || m.typed_raw.is_empty() || m.items.len() >= m.top_level.len() {
+        return false;
+    }
+    m.items = top_level_items(m.top_level.clone());
+    let visible = m.render_visible_items();
+    let list_content = m.render_list_content(&visible);
+    m.sync_viewport(&list_content);
+    true
+}
+
+fn top_level_items(entries: Vec<(String, String)>) -> Vec<ChooseItem> {
     let items: Vec<ChooseItem> = entries
         .into_iter()
         .map(|(name, short)| ChooseItem {
@@ -349,15 +926,21 @@ fn set_items_from_carapace_entries(m: &mut Model, entries: Vec<(String, String)>
             depth: 0,
         })
         .collect();
-    m.items = crate::ui::model::sort_items(items);
+    crate::ui::model::sort_items(items)
+}
+
+fn set_items_from_entries(m: &mut Model, entries: Vec<(String, String)>) {
+    m.top_level = entries.clone();
+    m.items = top_level_items(entries);
     m.current = None;
     m.ast.root.clear();
     if let Some(n) = m.ast.stack.get_mut(0) {
         n.name.clear();
     }
+    m.arrange_top_level();
     let visible = m.render_visible_items();
     let list_content = m.render_list_content(&visible);
-    m.vp.set_content(&list_content);
+    m.sync_viewport(&list_content);
 }
 
 fn restore_current_after_pop(m: &mut Model) {
@@ -383,16 +966,16 @@ fn restore_current_after_pop(m: &mut Model) {
             m.build_items_from_command(&cur);
             let visible = m.render_visible_items();
             let list_content = m.render_list_content(&visible);
-            m.vp.set_content(&list_content);
+            m.sync_viewport(&list_content);
         } else {
             m.current = None;
             m.items.clear();
-            m.vp.set_content("");
+            m.sync_viewport("");
         }
     } else {
         m.current = None;
         m.items.clear();
-        m.vp.set_content("");
+        m.sync_viewport("");
     }
 }
 
@@ -402,8 +985,9 @@ fn process_numeric_selection(m: &mut Model) -> bool {
     if let Some(baseline) = &m.numeric_baseline {
         let matches: Vec<usize> = baseline
             .iter()
-            .filter_map(|&orig_idx| {
-                let num = (orig_idx + 1).to_string();
+            .enumerate()
+            .filter_map(|(pos, &orig_idx)| {
+                let num = crate::ui::render::list::candidate_number(m, pos, orig_idx).to_string();
                 if num.starts_with(&m.typed_raw) {
                     Some(orig_idx)
                 } else {
@@ -473,6 +1057,10 @@ fn try_immediate_assignment_selection(m: &mut Model, assignments: Vec<crate::ace
                 let chosen_form = forms[idx].clone();
                 if let Some(item_idx) = form_map.get(&chosen_form) {
                     let it = m.items[*item_idx].clone();
+                    if !m.settings.auto_select.allows(&it.kind) {
+                        // leave it highlighted; Enter commits it
+                        return false;
+                    }
                     if it.kind == "cmd" {
                         handle_command_choice(m, &it, &chosen_form);
                         return true;
@@ -493,32 +1081,35 @@ fn try_immediate_assignment_selection(m: &mut Model, assignments: Vec<crate::ace
 fn update_viewport_after_typed(m: &mut Model) {
     let visible_now = m.render_visible_items();
     let list_content = m.render_list_content(&visible_now);
-    m.vp.set_content(&list_content);
+    m.sync_viewport(&list_content);
     if !m.typed.is_empty() {
         m.vp.goto_top();
     }
 }
 
-fn apply_loaded_command(m: &mut Model, def: crate::ast::CommandDef) {
-    m.def_cache.insert(def.name.clone(), def.clone());
-    m.ast.root = def.name.clone();
+// Load `def` as the root command shown as `root` (differs from `def.name` for aliases)
+fn apply_loaded_root(m: &mut Model, root: &str, def: crate::ast::CommandDef) {
+    m.def_cache.insert(root.to_string(), def.clone());
+    m.ast.root = root.to_string();
     if m.ast.stack.is_empty() {
-        m.ast = crate::ast::Segment::new_empty(&def.name);
+        m.ast = crate::ast::Segment::new_empty(root);
     } else {
-        m.ast.stack[0].name = def.name.clone();
+        m.ast.stack[0].name = root.to_string();
     }
     m.current = Some(def.clone());
     m.build_items_from_command(&def);
     // update viewport content so the interactive UI shows the newly loaded command items
     let visible = m.render_visible_items();
     let list_content = m.render_list_content(&visible);
-    m.vp.set_content(&list_content);
+    m.sync_viewport(&list_content);
     m.typed.clear();
     m.typed_raw.clear();
+    prompt_positional_if_only_args(m);
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::ast::build::{command, flag, sub, switch};
     use crate::ast::{Segment, CommandDef, FlagDef};
     use crate::ui::model::initial_model;
 
@@ -534,6 +1125,7 @@ mod tests {
             aliases: vec![],
             flags: vec![],
             subcommands: vec![],
+            ..Default::default()
         };
         let def = CommandDef {
             name: "ls".to_string(),
@@ -544,12 +1136,14 @@ mod tests {
                 shorthand: "a".to_string(),
                 usage: "show all".to_string(),
                 requires_value: false,
+                ..Default::default()
             }],
             subcommands: vec![sub.clone()],
+            ..Default::default()
         };
 
         // call the private helper as the interactive path would
-        super::apply_loaded_command(&mut m, def.clone());
+        super::apply_loaded_root(&mut m, "ls", def.clone());
 
         // AST stack should have a root node named `ls`
         assert!(
@@ -590,12 +1184,756 @@ mod tests {
             "expected rendered list content to be non-empty"
         );
     }
-}
 
-#[cfg(test)]
-mod numeric_mode_tests {
-    use crate::ui::model::{initial_model, ChooseItem};
-    use crate::ast::{Segment, FlagDef, CommandDef};
+    #[test]
+    fn space_starts_a_positional_only_once_a_command_is_chosen() {
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::default();
+        m.update(crate::ui::Msg::KeySpace);
+        assert!(!m.in_value_mode && !m.pending_pos);
+
+        let def = CommandDef { name: "ls".to_string(), takes_args: true, ..Default::default() };
+        super::apply_loaded_root(&mut m, "ls", def);
+        m.update(crate::ui::Msg::KeySpace);
+        assert!(m.in_value_mode && m.pending_pos);
+    }
+
+    #[test]
+    fn letter_picks_the_subcommand_and_dash_the_flag_sharing_its_prefix() {
+        let def = CommandDef {
+            name: "tool".to_string(),
+            flags: vec![FlagDef { longhand: "all".to_string(), shorthand: "a".to_string(), ..Default::default() }],
+            subcommands: vec![CommandDef { name: "add".to_string(), ..Default::default() }],
+            ..Default::default()
+        };
+        let typed_preview = |typed: &str| {
+            let mut m = initial_model(vec![]);
+            m.ast = Segment::default();
+            super::apply_loaded_root(&mut m, "tool", def.clone());
+            for ch in typed.chars() {
+                m.update(crate::ui::Msg::Rune(ch));
+            }
+            m.ast.render_preview()
+        };
+        // flags start with `-`, so a bare letter only ever matches commands
+        assert_eq!(typed_preview("a"), "tool add");
+        assert_eq!(typed_preview("-a"), "tool -a");
+        // the only long form is settled by `--` alone
+        assert_eq!(typed_preview("--"), "tool --all");
+    }
+
+    #[test]
+    fn typing_a_description_keyword_finds_the_command_it_describes() {
+        let sub = |name: &str, short: &str| CommandDef { name: name.to_string(), short: short.to_string(), ..Default::default() };
+        let def = CommandDef {
+            name: "git".to_string(),
+            subcommands: vec![
+                sub("commit", "Record changes to the repository"),
+                sub("push", "Update remote refs along with associated objects"),
+                sub("status", "Show the working tree status"),
+            ],
+            ..Default::default()
+        };
+        let fresh = |match_descriptions, typed: &str| {
+            let mut m = initial_model(vec![]);
+            m.settings.match_descriptions = match_descriptions;
+            super::apply_loaded_root(&mut m, "git", def.clone());
+            for c in typed.chars() {
+                m.update(crate::ui::Msg::Rune(c));
+            }
+            m
+        };
+        let labels = |m: &crate::ui::model::Model| m.render_visible_items().iter().map(|it| it.label.clone()).collect::<Vec<_>>();
+
+        assert!(labels(&fresh(false, "record")).is_empty());
+        let mut m = fresh(true, "record");
+        assert_eq!(labels(&m), vec!["commit"]);
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "git commit");
+        assert!(m.exit_preview.is_empty());
+
+        // a name match still wins: `p` picks push although commit's description has a p
+        assert_eq!(fresh(true, "p").ast.render_preview(), "git push");
+    }
+
+    #[test]
+    fn typing_another_command_at_an_untouched_root_switches_to_it() {
+        use crate::source::{Source, StaticSource};
+        let git = command("git", vec![], vec![sub("commit"), sub("push")]);
+        let docker = command("docker", vec![], vec![sub("ps")]);
+        let source = Source::new(StaticSource::new(vec![git.clone(), docker]));
+        let fresh = || {
+            let mut m = initial_model(source.list(false).unwrap());
+            m.source = source.clone();
+            m.update(crate::ui::Msg::Rune('g'));
+            assert_eq!(m.ast.render_preview(), "git");
+            m
+        };
+
+        // `d` matches no git subcommand, so it starts `docker`
+        let mut m = fresh();
+        m.update(crate::ui::Msg::Rune('d'));
+        assert_eq!(m.ast.render_preview(), "docker");
+        assert_eq!(m.current.as_ref().map(|c| c.name.as_str()), Some("docker"));
+        assert_eq!(m.depth(), 1);
+
+        // git's own subcommands still come first
+        let mut m = fresh();
+        m.update(crate::ui::Msg::Rune('p'));
+        assert_eq!(m.ast.render_preview(), "git push");
+
+        // once something is added the root stays
+        let mut m = fresh();
+        m.ast.add_flag("--bare", "");
+        m.update(crate::ui::Msg::Rune('d'));
+        assert_eq!(m.ast.render_preview(), "git --bare");
+    }
+
+    #[test]
+    fn frequently_run_commands_lead_the_top_level_list() {
+        use crate::source::{Source, StaticSource};
+        use crate::ui::{FrequentCommands, Msg};
+        let source = Source::new(StaticSource::new(vec![sub("awk"), sub("cat"), sub("git"), sub("ls")]));
+        let fresh = |mode, usage: &crate::history::CommandUsage| {
+            let mut m = initial_model(source.list(false).unwrap());
+            m.source = source.clone();
+            m.settings.frequent_commands = mode;
+            m.command_usage = usage.clone();
+            m.arrange_top_level();
+            m
+        };
+        let labels = |m: &crate::ui::model::Model| m.items.iter().map(|it| it.label.clone()).collect::<Vec<_>>();
+
+        // finishing a command counts a run of it, unless frequent commands are off
+        let mut m = fresh(FrequentCommands::Off, &crate::history::CommandUsage::default());
+        m.update(Msg::Rune('g'));
+        m.update(Msg::KeyEnter);
+        assert_eq!(m.command_usage.count("git"), 0);
+        let mut usage = crate::history::CommandUsage::default();
+        for (key, name) in [('g', "git"), ('l', "ls"), ('g', "git")] {
+            let mut m = fresh(FrequentCommands::First, &usage);
+            m.update(Msg::Rune(key));
+            m.update(Msg::KeyEnter);
+            assert_eq!(m.exit_preview, name);
+            usage = m.command_usage;
+        }
+        assert_eq!((usage.count("git"), usage.count("ls"), usage.count("awk")), (2, 1, 0));
+
+        assert_eq!(labels(&fresh(FrequentCommands::Off, &usage)), vec!["ls", "awk", "cat", "git"]);
+        assert_eq!(labels(&fresh(FrequentCommands::First, &usage)), vec!["git", "ls", "awk", "cat"]);
+
+        // only the frequent ones, until typing matches none of them
+        let mut m = fresh(FrequentCommands::Only, &usage);
+        assert_eq!(labels(&m), vec!["git", "ls"]);
+        m.update(Msg::Rune('c'));
+        assert_eq!(m.ast.root, "cat");
+        m.update(Msg::KeyBackspace);
+        assert_eq!(labels(&m), vec!["git", "ls"]);
+    }
+
+    #[test]
+    fn candidate_numbers_continue_across_pages_or_restart_per_page() {
+        use crate::ui::Msg;
+        use crate::ui::render::strip_ansi;
+        let sub = |name: String| CommandDef { name, ..Default::default() };
+        let tool = CommandDef {
+            name: "tool".to_string(),
+            subcommands: ('a'..='n').map(|c| sub(format!("s{c}"))).collect(),
+            ..Default::default()
+        };
+        for (per_page_numbers, first_on_page_two, keys) in [(false, "11 │", "12"), (true, "1 │", "2")] {
+            let mut m = initial_model(vec![]);
+            m.settings.per_page_numbers = per_page_numbers;
+            super::apply_loaded_root(&mut m, "tool", tool.clone());
+            m.per_page = 10;
+            m.page = 1;
+            let visible = m.render_visible_items();
+            let content = strip_ansi(&m.render_list_content(&visible));
+            let first = content.lines().next().unwrap_or_default().trim_start().to_string();
+            assert!(first.starts_with(first_on_page_two), "{first:?}");
+
+            // the number shown is the one that selects: the second candidate on page two
+            let second = m.items[11].label.clone();
+            for k in keys.chars() {
+                m.update(Msg::Rune(k));
+            }
+            assert_eq!(m.ast.render_preview(), format!("tool {second}"), "per_page_numbers={per_page_numbers}");
+        }
+    }
+
+    #[test]
+    fn with_a_numeric_prefix_key_digits_are_typed_like_letters() {
+        use crate::ui::Msg;
+        let tool = command("tool", vec![], vec![sub("build"), sub("p2p"), sub("push")]);
+        let fresh = |prefix| {
+            let mut m = initial_model(vec![]);
+            m.settings.numeric_prefix = prefix;
+            super::apply_loaded_root(&mut m, "tool", tool.clone());
+            m
+        };
+        let second = fresh(None).items[1].label.clone();
+
+        // implicitly, a digit selects by number
+        let mut m = fresh(None);
+        m.update(Msg::Rune('2'));
+        assert_eq!(m.ast.render_preview(), format!("tool {second}"));
+
+        // with the prefix key, it is just typed
+        let mut m = fresh(Some('#'));
+        m.update(Msg::Rune('2'));
+        assert_eq!((m.ast.render_preview().as_str(), m.typed_raw.as_str()), ("tool", "2"));
+        assert!(m.numeric_baseline.is_none());
+        m.update(Msg::ClearTyped);
+        for r in ['p', '2'] {
+            m.update(Msg::Rune(r));
+        }
+        assert_eq!(m.ast.render_preview(), "tool p2p");
+
+        // until the prefix key starts numeric selection
+        let mut m = fresh(Some('#'));
+        m.update(Msg::Rune('#'));
+        m.update(Msg::Rune('2'));
+        assert_eq!(m.ast.render_preview(), format!("tool {second}"));
+    }
+
+    #[test]
+    fn typed_shell_operators_start_pipes_and_redirections() {
+        use crate::source::{Source, StaticSource};
+        use crate::ui::Msg;
+        let git = command("git", vec![], vec![sub("log"), sub("push")]);
+        let grep = CommandDef { name: "grep".to_string(), takes_args: true, ..Default::default() };
+        let source = Source::new(StaticSource::new(vec![git, grep]));
+        let mut m = initial_model(source.list(false).unwrap());
+        m.source = source.clone();
+        let typed = |m: &mut crate::ui::model::Model, text: &str| text.chars().for_each(|c| m.update(Msg::Rune(c)));
+
+        // `|` finishes `git log` and lists the top-level commands again
+        typed(&mut m, "gil");
+        assert_eq!(m.preview_text(), "git log");
+        typed(&mut m, "|");
+        assert!(m.current.is_none());
+        assert_eq!(m.items.len(), 2);
+        assert_eq!(m.preview_text(), "git log |");
+        // nothing runs while the pipe has no command after it
+        m.update(Msg::KeyEnter);
+        assert!(m.exit_preview.is_empty());
+        typed(&mut m, "gr");
+        assert_eq!(m.preview_text(), "git log | grep");
+
+        // `>` asks for the file; a second `>` appends
+        typed(&mut m, ">");
+        assert!(m.in_value_mode);
+        let main = crate::ui::render::render_main_content(&m);
+        assert!(main.contains("Write to (>):"), "{main}");
+        typed(&mut m, ">out.txt");
+        m.update(Msg::KeyEnter);
+        assert!(!m.in_value_mode);
+        assert_eq!(m.preview_text(), "git log | grep >> out.txt");
+
+        // Backspace takes the redirection back, then returns to `git log`
+        m.update(Msg::KeyBackspace);
+        assert_eq!(m.preview_text(), "git log | grep");
+        m.update(Msg::KeyBackspace);
+        m.update(Msg::KeyBackspace);
+        assert_eq!(m.preview_text(), "git log");
+        assert_eq!(m.current.as_ref().map(|c| c.name.as_str()), Some("log"));
+
+        // `||` and `&&` join lists; `<` reads a file
+        typed(&mut m, "||gr<in.txt");
+        m.update(Msg::KeyEnter);
+        typed(&mut m, "&&gip");
+        assert_eq!(m.preview_text(), "git log || grep < in.txt && git push");
+        m.update(Msg::KeyEnter);
+        assert_eq!(m.exit_preview, "git log || grep < in.txt && git push");
+    }
+
+    #[test]
+    fn single_char_shorthands_are_each_selectable_by_their_char() {
+        let short = |c: &str| FlagDef { shorthand: c.to_string(), ..Default::default() };
+        let def = CommandDef {
+            name: "tool".to_string(),
+            flags: vec![short("a"), short("b"), short("c")],
+            ..Default::default()
+        };
+        for c in ["a", "b", "c"] {
+            let mut m = initial_model(vec![]);
+            m.ast = Segment::default();
+            super::apply_loaded_root(&mut m, "tool", def.clone());
+            m.update(crate::ui::Msg::Rune('-'));
+            m.update(crate::ui::Msg::Rune(c.chars().next().unwrap()));
+            assert_eq!(m.ast.render_preview(), format!("tool -{c}"));
+        }
+    }
+
+    #[test]
+    fn apply_loaded_flags_only_command_lists_and_selects_flags() {
+        let def = CommandDef {
+            takes_args: true,
+            ..command("ls", vec![flag("all", "a"), flag("long", "l"), flag("human-readable", "h"), flag("recursive", "R")], vec![])
+        };
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::default();
+        super::apply_loaded_root(&mut m, "ls", def);
+
+        assert_eq!(m.ast.render_preview(), "ls");
+        assert_eq!(m.items.len(), 4);
+        assert!(m.items.iter().all(|it| it.kind == "flag"));
+        let list = crate::ui::render::strip_ansi(&m.render_list_content(&m.render_visible_items()));
+        assert!(list.contains("--long") && list.contains("--recursive"), "{list}");
+
+        // typing narrows to a single flag and adds it
+        for r in "--l".chars() {
+            m.update(crate::ui::Msg::Rune(r));
+        }
+        assert_eq!(m.ast.render_preview(), "ls --long");
+
+        // the gutter number of --recursive adds it too
+        let n = m.items.iter().position(|it| it.forms.contains(&"--recursive".to_string())).unwrap() + 1;
+        m.update(crate::ui::Msg::ToggleNumeric);
+        for r in n.to_string().chars() {
+            m.update(crate::ui::Msg::Rune(r));
+        }
+        assert_eq!(m.ast.render_preview(), "ls --long --recursive");
+        assert_eq!(m.depth(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn enter_exports_typed_root_missing_from_the_list() {
+        let script = r#"case "$1" in
+  kubectl) echo '{"Name":"kubectl","Short":"k8s","LocalFlags":[{"Longhand":"namespace","Shorthand":"n","Type":"string"}],"Commands":[{"Name":"get"}]}' ;;
+  *) exit 1 ;;
+esac"#;
+        crate::carapace::fake::with_fake_carapace(script, || {
+            // kubectl is not in the (PATH-filtered) top-level list
+            let mut m = initial_model(vec![("ls".to_string(), "list".to_string())]);
+            m.ast = Segment::new_empty("");
+            m.typed_raw = "kubectl".to_string();
+            m.typed = "kubectl".to_string();
+            m.update(crate::ui::Msg::KeyEnter);
+
+            assert!(m.err.is_empty(), "unexpected error: {}", m.err);
+            assert_eq!(m.ast.root, "kubectl");
+            assert_eq!(m.current.as_ref().unwrap().name, "kubectl");
+            assert!(m.items.iter().any(|it| it.label == "--namespace, -n"));
+            assert!(m.items.iter().any(|it| it.label == "get"));
+            assert!(m.typed_raw.is_empty());
+            assert!(m.exit_preview.is_empty(), "Enter must load the command, not exit");
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn empty_export_explains_itself_in_the_modeline() {
+        crate::carapace::fake::with_fake_carapace("echo '{}'", || {
+            let mut m = initial_model(vec![]);
+            m.update(crate::ui::Msg::WindowSize { width: 100, height: 12 });
+            m.ast = Segment::new_empty("");
+            m.typed_raw = "blank".to_string();
+            m.typed = "blank".to_string();
+            m.update(crate::ui::Msg::KeyEnter);
+            assert_eq!(m.err, "no completion data for blank");
+            let modeline = crate::ui::render::strip_ansi(&crate::ui::render_modeline_padded(&m));
+            assert!(modeline.contains("✗ no completion data for blank"), "got: {modeline}");
+
+            // a resize keeps it; the next key dismisses it
+            m.update(crate::ui::Msg::WindowSize { width: 90, height: 12 });
+            assert_eq!(m.err, "no completion data for blank");
+            m.update(crate::ui::Msg::KeyBackspace);
+            assert!(m.err.is_empty());
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_export_of_a_chosen_command_shows_why_until_the_next_key() {
+        let script = r#"case "$1" in
+  --list) echo "broken  A tool"; echo "bare  Another" ;;
+  *) echo "export exploded" >&2; exit 3 ;;
+esac"#;
+        crate::carapace::fake::with_fake_carapace(script, || {
+            let mut m = initial_model(crate::carapace::list_entries(false).unwrap());
+            m.update(crate::ui::Msg::WindowSize { width: 80, height: 12 });
+            for ch in "br".chars() {
+                m.update(crate::ui::Msg::Rune(ch));
+            }
+            assert!(m.err.contains("export exploded"), "err: {:?}", m.err);
+            assert!(m.current.is_none(), "nothing was loaded");
+            let full = crate::ui::render::strip_ansi(&m.render_full());
+            assert!(full.contains("✗ carapace") && full.contains("export exploded"), "got: {full}");
+            // on a narrow screen the key hints give way and the message is cut short
+            m.update(crate::ui::Msg::WindowSize { width: 40, height: 12 });
+            let modeline = crate::ui::render::strip_ansi(&crate::ui::render_modeline_padded(&m));
+            assert_eq!(modeline.chars().count(), 40);
+            assert!(modeline.contains("✗ carapace") && modeline.contains('…'), "got: {modeline}");
+
+            m.update(crate::ui::Msg::KeyBackspace);
+            assert!(!crate::ui::render::strip_ansi(&m.render_full()).contains('✗'));
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn enter_resolves_configured_alias_but_keeps_it_as_root() {
+        let script = r#"case "$1" in
+  git) echo '{"Name":"git","Commands":[{"Name":"commit","LocalFlags":[{"Longhand":"amend"}]},{"Name":"push"}]}' ;;
+  *) exit 1 ;;
+esac"#;
+        crate::carapace::fake::with_fake_carapace(script, || {
+            let mut m = initial_model(vec![]);
+            m.settings.aliases.insert("g".to_string(), "git".to_string());
+            m.ast = Segment::new_empty("");
+            m.typed_raw = "g".to_string();
+            m.typed = "g".to_string();
+            m.update(crate::ui::Msg::KeyEnter);
+
+            assert!(m.err.is_empty(), "unexpected error: {}", m.err);
+            assert_eq!(m.ast.root, "g");
+            assert_eq!(m.current.as_ref().unwrap().name, "git");
+            assert!(m.items.iter().any(|it| it.label == "commit"));
+            assert!(m.items.iter().any(|it| it.label == "push"));
+
+            // descending keeps resolving through the alias
+            m.update(crate::ui::Msg::Rune('c'));
+            assert!(m.items.iter().any(|it| it.label == "--amend"));
+            assert_eq!(m.ast.render_preview(), "g commit");
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn choosing_bare_subcommand_forwards_to_root_dash_sub_binary() {
+        use std::os::unix::fs::PermissionsExt;
+        let script = r#"case "$1" in
+  cargo-foo) echo '{"Name":"cargo-foo","Short":"foo things","LocalFlags":[{"Longhand":"bar","Usage":"bar it"}],"Commands":[{"Name":"baz"}]}' ;;
+  *) exit 1 ;;
+esac"#;
+        let cargo = CommandDef {
+            name: "cargo".to_string(),
+            subcommands: vec![
+                CommandDef { name: "foo".to_string(), ..Default::default() },
+                CommandDef { name: "build".to_string(), ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let bin_dir = std::env::temp_dir().join(format!("van-forward-{}", std::process::id()));
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        let shim = bin_dir.join("cargo-foo");
+        std::fs::write(&shim, "#!/bin/sh
+").unwrap();
+        std::fs::set_permissions(&shim, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        crate::carapace::fake::with_fake_carapace(script, || {
+            let old_path = std::env::var_os("PATH").unwrap_or_default();
+            let mut paths = vec![bin_dir.clone()];
+            paths.extend(std::env::split_paths(&old_path));
+            let _env = crate::carapace::fake::EnvVars::set(&[("PATH", std::env::join_paths(paths).unwrap())]);
+
+            for forward in [false, true] {
+                let mut m = initial_model(vec![]);
+                m.settings.forward_subcommands = forward;
+                m.ast = Segment::new_empty("");
+                super::apply_loaded_root(&mut m, "cargo", cargo.clone());
+                m.update(crate::ui::Msg::Rune('f'));
+
+                assert_eq!(m.ast.render_preview(), "cargo foo");
+                let has_bar = m.items.iter().any(|it| it.label == "--bar");
+                assert_eq!(has_bar, forward);
+                assert_eq!(m.items.iter().any(|it| it.label == "baz"), forward);
+                if forward {
+                    // the grafted definition survives rebuilding from the cache
+                    assert_eq!(m.get_def_for_depth(1).unwrap().flags[0].longhand, "bar");
+                }
+            }
+        });
+        let _ = std::fs::remove_dir_all(&bin_dir);
+    }
+
+    #[test]
+    fn alt_arrows_reorder_positionals_and_backspace_undoes() {
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("mv");
+        for arg in ["a.txt", "b.txt"] {
+            m.update(crate::ui::Msg::KeySpace);
+            for ch in arg.chars() {
+                m.update(crate::ui::Msg::Rune(ch));
+            }
+            m.update(crate::ui::Msg::KeyEnter);
+        }
+        assert_eq!(m.ast.render_preview(), "mv a.txt b.txt");
+        m.update(crate::ui::Msg::MoveArgUp);
+        assert_eq!(m.ast.render_preview(), "mv b.txt a.txt");
+        m.update(crate::ui::Msg::KeyBackspace);
+        assert_eq!(m.ast.render_preview(), "mv a.txt b.txt");
+    }
+
+    #[test]
+    fn clear_node_keeps_the_subcommand_and_undo_restores_its_args() {
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("git");
+        m.ast.add_flag("-C", "repo");
+        m.ast.push_subcommand("commit");
+        m.ast.add_flag("--amend", "");
+        m.ast.add_flag("-m", "wip");
+        m.ast.add_positional("file.txt");
+        assert_eq!(m.ast.render_preview(), "git -C repo commit --amend -m wip file.txt");
+
+        m.update(crate::ui::Msg::ClearNode);
+        assert_eq!(m.ast.render_preview(), "git -C repo commit");
+        assert_eq!(m.depth(), 2);
+        // nothing left to clear: no extra history entry
+        let ops = m.ast.history.len();
+        m.update(crate::ui::Msg::ClearNode);
+        assert_eq!(m.ast.history.len(), ops);
+
+        m.update(crate::ui::Msg::KeyBackspace);
+        assert_eq!(m.ast.render_preview(), "git -C repo commit --amend -m wip file.txt");
+        m.update(crate::ui::Msg::KeyBackspace);
+        assert_eq!(m.ast.render_preview(), "git -C repo commit --amend -m wip");
+    }
+
+    #[test]
+    fn negatable_switch_pair_is_one_item_cycling_through_three_states() {
+        let def = command("ls", vec![switch("color"), switch("no-color"), switch("all")], vec![]);
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("ls");
+        m.current = Some(def.clone());
+        m.def_cache.insert("ls".to_string(), def.clone());
+        m.build_items_from_command(&def);
+        let labels: Vec<&str> = m.items.iter().map(|it| it.label.as_str()).collect();
+        assert_eq!(labels.len(), 2, "{labels:?}");
+        let toggle = m.items.iter().find(|it| it.label.contains("--no-color")).cloned().unwrap();
+        assert_eq!(toggle.forms, vec!["--color".to_string(), "--no-color".to_string()]);
+
+        // typed or chosen again, the item goes on, negated, off and on again
+        let mut previews = vec![];
+        for _ in 0..4 {
+            for c in "--c".chars() {
+                m.update(crate::ui::Msg::Rune(c));
+            }
+            previews.push(m.ast.render_preview());
+        }
+        assert_eq!(previews, vec!["ls --color", "ls --no-color", "ls", "ls --color"]);
+        // flipping keeps the single history entry, so one undo removes it
+        assert!(super::select_item(&mut m, &toggle, "--color"));
+        assert_eq!(m.ast.render_preview(), "ls --no-color");
+        m.update(crate::ui::Msg::KeyBackspace);
+        assert_eq!(m.ast.render_preview(), "ls");
+
+        // the negative side picked first goes back to unset
+        m.ast = Segment::new_empty("ls");
+        assert!(super::select_item(&mut m, &toggle, "--no-color"));
+        assert_eq!(m.ast.render_preview(), "ls --no-color");
+        assert!(super::select_item(&mut m, &toggle, "--no-color"));
+        assert_eq!(m.ast.render_preview(), "ls");
+    }
+
+    #[test]
+    fn positional_and_flag_values_keep_typed_casing() {
+        let def = CommandDef {
+            name: "tool".to_string(),
+            flags: vec![FlagDef {
+                longhand: "name".to_string(),
+                requires_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("tool");
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+
+        m.update(crate::ui::Msg::KeySpace);
+        for ch in "Foo.TXT".chars() {
+            m.update(crate::ui::Msg::Rune(ch));
+        }
+        m.update(crate::ui::Msg::KeyEnter);
+        m.update(crate::ui::Msg::KeyTab);
+        for ch in "MiXeD".chars() {
+            m.update(crate::ui::Msg::Rune(ch));
+        }
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "tool --name MiXeD Foo.TXT");
+    }
+
+    #[test]
+    fn lowercase_typing_selects_mixed_case_subcommands_with_their_own_casing() {
+        let def = command("kubectl", vec![], vec![sub("ApplySet"), sub("describe"), sub("getPods"), sub("getNodes")]);
+        let select = |typed: &str, tab: bool| {
+            let mut m = initial_model(vec![]);
+            m.ast = Segment::default();
+            super::apply_loaded_root(&mut m, "kubectl", def.clone());
+            if tab {
+                // held until Tab completes it
+                m.settings.auto_select = crate::ui::AutoSelect::FlagsOnly;
+            }
+            for ch in typed.chars() {
+                m.update(crate::ui::Msg::Rune(ch));
+            }
+            if tab {
+                assert_eq!(m.ast.render_preview(), "kubectl");
+                m.update(crate::ui::Msg::KeyTab);
+            }
+            m.ast.render_preview()
+        };
+        assert_eq!(select("a", false), "kubectl ApplySet");
+        assert_eq!(select("getp", false), "kubectl getPods");
+        assert_eq!(select("getn", true), "kubectl getNodes");
+    }
+
+    #[test]
+    fn variadic_flag_takes_several_values_typed_or_loaded() {
+        let def = CommandDef {
+            name: "tool".to_string(),
+            flags: vec![
+                FlagDef { longhand: "files".to_string(), requires_value: true, variadic: true, ..Default::default() },
+                FlagDef { longhand: "verbose".to_string(), ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("tool");
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+        for ch in "--f".chars() {
+            m.update(crate::ui::Msg::Rune(ch));
+        }
+        assert!(m.in_value_mode);
+        for ch in "a.txt b.txt 'c d.txt'".chars() {
+            m.update(if ch == ' ' { crate::ui::Msg::KeySpace } else { crate::ui::Msg::Rune(ch) });
+        }
+        m.update(crate::ui::Msg::KeyEnter);
+        let flag = &m.ast.top().unwrap().flags[0];
+        assert_eq!((flag.value.as_str(), flag.more_values.clone()), ("a.txt", vec!["b.txt".to_string(), "c d.txt".to_string()]));
+        assert_eq!(m.ast.render_preview(), "tool --files a.txt b.txt 'c d.txt'");
+
+        // the non-interactive parser reads values up to the next flag
+        let mut m = initial_model(vec![]);
+        m.source = crate::source::Source::new(crate::source::StaticSource::new(vec![def]));
+        let args: Vec<String> = ["tool", "--files", "a", "b", "--verbose", "--files", "c"].iter().map(|s| s.to_string()).collect();
+        crate::ui::run::load_args(&mut m, &args).unwrap();
+        assert_eq!(m.ast.render_preview(), "tool --files a b --verbose --files c");
+        assert_eq!(m.ast.top().unwrap().flags[0].more_values, vec!["b".to_string()]);
+        assert!(m.ast.top().unwrap().positionals.is_empty());
+    }
+
+    #[test]
+    fn choosing_positional_only_command_enters_argument_input_when_enabled() {
+        let show = CommandDef {
+            name: "show".to_string(),
+            takes_args: true,
+            ..Default::default()
+        };
+        let root = CommandDef {
+            name: "tool".to_string(),
+            subcommands: vec![show],
+            ..Default::default()
+        };
+        let setup = |auto: bool| {
+            let mut m = initial_model(vec![]);
+            m.settings.auto_positional = auto;
+            m.ast = Segment::new_empty("tool");
+            m.def_cache.insert("tool".to_string(), root.clone());
+            m.current = Some(root.clone());
+            m.build_items_from_command(&root);
+            m.update(crate::ui::Msg::Rune('s'));
+            m
+        };
+
+        let m = setup(false);
+        assert_eq!(m.ast.top().unwrap().name, "show");
+        assert!(!m.in_value_mode);
+
+        let mut m = setup(true);
+        assert_eq!(m.ast.top().unwrap().name, "show");
+        assert!(m.in_value_mode && m.pending_pos);
+        for ch in "a.txt".chars() {
+            m.update(crate::ui::Msg::Rune(ch));
+        }
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "tool show a.txt");
+    }
+
+    #[test]
+    fn choosing_positional_only_root_enters_argument_input_when_enabled() {
+        let cat = CommandDef { name: "cat".to_string(), takes_args: true, ..Default::default() };
+        for auto in [false, true] {
+            let mut m = initial_model(vec![]);
+            m.settings.auto_positional = auto;
+            m.ast = Segment::new_empty("");
+            super::apply_loaded_root(&mut m, "cat", cat.clone());
+            assert_eq!(m.in_value_mode && m.pending_pos, auto);
+        }
+
+        // picked from the top-level list
+        let mut m = initial_model(vec![("cat".to_string(), String::new()), ("ls".to_string(), String::new())]);
+        m.source = crate::source::Source::new(crate::source::StaticSource::new(vec![cat]));
+        m.settings.auto_positional = true;
+        m.update(crate::ui::Msg::Rune('c'));
+        assert!(m.in_value_mode && m.pending_pos);
+        for ch in "a.txt".chars() {
+            m.update(crate::ui::Msg::Rune(ch));
+        }
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "cat a.txt");
+    }
+}
+
+#[cfg(test)]
+mod repeated_selection_tests {
+    use crate::ast::{CommandDef, Segment};
+    use crate::ui::model::{initial_model, ChooseItem, Model};
+
+    fn git_with(commit: CommandDef) -> Model {
+        let git = CommandDef {
+            name: "git".to_string(),
+            subcommands: vec![commit],
+            ..Default::default()
+        };
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("git");
+        m.def_cache.insert("git".to_string(), git.clone());
+        m.current = Some(git.clone());
+        m.build_items_from_command(&git);
+        m
+    }
+
+    fn commit_item(m: &Model) -> ChooseItem {
+        m.items.iter().find(|it| it.label == "commit").cloned().unwrap()
+    }
+
+    #[test]
+    fn selecting_the_same_subcommand_twice_pushes_it_once() {
+        let mut m = git_with(CommandDef { name: "commit".to_string(), ..Default::default() });
+        let stale = commit_item(&m);
+        assert!(super::select_item(&mut m, &stale, "commit"));
+        assert!(super::select_item(&mut m, &stale, "commit"));
+        assert_eq!(m.ast.render_preview(), "git commit");
+        assert_eq!(m.ast.stack.len(), 2);
+    }
+
+    #[test]
+    fn a_same_named_child_is_still_entered() {
+        let inner = CommandDef { name: "commit".to_string(), ..Default::default() };
+        let mut m = git_with(CommandDef {
+            name: "commit".to_string(),
+            subcommands: vec![inner],
+            ..Default::default()
+        });
+        let outer = commit_item(&m);
+        super::select_item(&mut m, &outer, "commit");
+        let inner = commit_item(&m);
+        super::select_item(&mut m, &inner, "commit");
+        assert_eq!(m.ast.render_preview(), "git commit commit");
+    }
+}
+
+#[cfg(test)]
+mod numeric_mode_tests {
+    use crate::ast::build::{command, sub, switch};
+    use crate::ui::model::{initial_model, ChooseItem};
+    use crate::ast::{Segment, FlagDef, CommandDef};
 
     #[test]
     fn test_digit_switch_from_alpha_to_numeric_and_selects_unique_index() {
@@ -616,6 +1954,7 @@ mod numeric_mode_tests {
                     shorthand: "f".to_string(),
                     usage: String::new(),
                     requires_value: false,
+                    ..Default::default()
                 }),
                 cmd_def: None,
                 short: String::new(),
@@ -630,6 +1969,7 @@ mod numeric_mode_tests {
                     shorthand: "g".to_string(),
                     usage: String::new(),
                     requires_value: false,
+                    ..Default::default()
                 }),
                 cmd_def: None,
                 short: String::new(),
@@ -644,6 +1984,7 @@ mod numeric_mode_tests {
                     shorthand: "h".to_string(),
                     usage: String::new(),
                     requires_value: false,
+                    ..Default::default()
                 }),
                 cmd_def: None,
                 short: String::new(),
@@ -713,20 +2054,16 @@ mod numeric_mode_tests {
 
     #[test]
     fn test_w_wc_who_alpha_list_and_numeric_selects_who() {
-        use regex::Regex;
-        fn strip_ansi(s: &str) -> String {
-            let re = Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]").unwrap();
-            re.replace_all(s, "").to_string()
-        }
+        use crate::ui::render::strip_ansi;
 
         let mut m = initial_model(vec![]);
         m.ast = Segment::new_empty("root");
         m.ast.root = "root".to_string();
         m.ast.stack[0].name = "root".to_string();
 
-        let wdef = CommandDef { name: "w".to_string(), short: "w".to_string(), aliases: vec![], flags: vec![], subcommands: vec![] };
-        let wcdef = CommandDef { name: "wc".to_string(), short: "wc".to_string(), aliases: vec![], flags: vec![], subcommands: vec![] };
-        let whodef = CommandDef { name: "who".to_string(), short: "who".to_string(), aliases: vec![], flags: vec![], subcommands: vec![] };
+        let wdef = CommandDef { name: "w".to_string(), short: "w".to_string(), aliases: vec![], flags: vec![], subcommands: vec![], ..Default::default() };
+        let wcdef = CommandDef { name: "wc".to_string(), short: "wc".to_string(), aliases: vec![], flags: vec![], subcommands: vec![], ..Default::default() };
+        let whodef = CommandDef { name: "who".to_string(), short: "who".to_string(), aliases: vec![], flags: vec![], subcommands: vec![], ..Default::default() };
 
         m.items = vec![
             ChooseItem {
@@ -779,6 +2116,79 @@ mod numeric_mode_tests {
         assert!(m.ast.top().is_some(), "expected a subcommand selected");
         assert_eq!(m.ast.top().unwrap().name, "who");
     }
+
+    #[test]
+    fn numeric_selection_reaches_items_on_other_pages() {
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("root");
+        // letter-only names so digits always mean gutter numbers
+        let letters: Vec<char> = ('a'..='z').collect();
+        m.items = (0..30)
+            .map(|i| {
+                let name = format!("--opt-{}{}", letters[i / 26], letters[i % 26]);
+                ChooseItem {
+                    kind: "flag".to_string(),
+                    label: name.clone(),
+                    forms: vec![name.clone()],
+                    flag_def: Some(FlagDef {
+                        longhand: name.trim_start_matches('-').to_string(),
+                        ..Default::default()
+                    }),
+                    cmd_def: None,
+                    short: String::new(),
+                    depth: 0,
+                }
+            })
+            .collect();
+        m.per_page = 10;
+        m.update(crate::ui::Msg::KeyDown);
+        assert_eq!(m.page, 1);
+
+        // "2" still matches 2 and 20..=29: the filtered list starts over on its first page
+        m.update(crate::ui::Msg::Rune('2'));
+        assert_eq!(m.page, 0);
+        let visible = m.render_visible_items();
+        assert_eq!(visible.len(), 11);
+        let list = crate::ui::render::strip_ansi(&m.render_list_content(&visible));
+        assert!(list.contains(" 25 │ --opt-ay"), "{list}");
+
+        m.update(crate::ui::Msg::Rune('5'));
+        assert!(m.ast.stack[0].flags.iter().any(|f| f.form == "--opt-ay"), "expected item 25 selected");
+        assert!(m.numeric_baseline.is_none());
+    }
+
+    #[test]
+    fn flags_only_filter_limits_listing_and_numbers_to_flags() {
+        let def = command("git", vec![switch("bare"), switch("paginate")], vec![sub("add"), sub("branch"), sub("push")]);
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("git");
+        m.current = Some(def.clone());
+        m.def_cache.insert("git".to_string(), def.clone());
+        m.build_items_from_command(&def);
+        assert_eq!(m.items.len(), 5);
+
+        m.update(crate::ui::Msg::CycleKindFilter);
+        assert_eq!(m.kind_filter, crate::ui::KindFilter::FlagsOnly);
+        let kinds: Vec<String> = m.render_visible_items().into_iter().map(|it| it.kind).collect();
+        assert_eq!(kinds, vec!["flag", "flag"]);
+        assert!(crate::ui::render::strip_ansi(&crate::ui::render_modeline_padded(&m)).contains("flags only"));
+
+        // "-" alone would still leave several candidates; "--b" narrows to --bare
+        for r in "--b".chars() {
+            m.update(crate::ui::Msg::Rune(r));
+        }
+        assert_eq!(m.ast.render_preview(), "git --bare");
+        // numbers cover the flags alone: 2 is --paginate, not a subcommand
+        m.update(crate::ui::Msg::ToggleNumeric);
+        m.update(crate::ui::Msg::Rune('2'));
+        assert_eq!(m.ast.render_preview(), "git --bare --paginate");
+        assert_eq!(m.depth(), 1);
+
+        m.update(crate::ui::Msg::CycleKindFilter);
+        assert!(m.items.iter().all(|it| it.kind == "cmd"));
+        m.update(crate::ui::Msg::CycleKindFilter);
+        assert_eq!(m.kind_filter, crate::ui::KindFilter::All);
+    }
 }
 
 #[cfg(test)]
@@ -814,3 +2224,600 @@ mod digit_vs_numeric_tests {
         assert_eq!(m.typed_raw, "2");
     }
 }
+
+#[cfg(test)]
+mod tab_completion_tests {
+    use crate::ast::{CommandDef, Segment};
+    use crate::ui::model::{initial_model, ChooseItem, Model};
+
+    fn model_with_subcommands(names: &[&str]) -> Model {
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("root");
+        m.ast.root = "root".to_string();
+        m.ast.stack[0].name = "root".to_string();
+        m.items = names
+            .iter()
+            .map(|n| ChooseItem {
+                kind: "cmd".to_string(),
+                label: n.to_string(),
+                forms: vec![n.to_string()],
+                flag_def: None,
+                cmd_def: Some(CommandDef {
+                    name: n.to_string(),
+                    short: String::new(),
+                    aliases: vec![],
+                    flags: vec![],
+                    subcommands: vec![],
+                    ..Default::default()
+                }),
+                short: String::new(),
+                depth: 0,
+            })
+            .collect();
+        m
+    }
+
+    #[test]
+    fn tab_with_single_prefix_match_completes_and_selects() {
+        let mut m = model_with_subcommands(&["chmod", "chown", "chgrp"]);
+        m.typed_raw = "chm".to_string();
+        m.typed = "chm".to_string();
+        m.update(crate::ui::Msg::KeyTab);
+        assert_eq!(m.ast.top().unwrap().name, "chmod");
+        assert_eq!(m.ast.render_preview(), "root chmod");
+        assert!(m.typed_raw.is_empty(), "typed buffer should be cleared after selection");
+    }
+
+    #[test]
+    fn tab_with_ambiguous_prefix_extends_to_common_prefix() {
+        let mut m = model_with_subcommands(&["chcpu", "chcon", "df"]);
+        m.typed_raw = "ch".to_string();
+        m.typed = "ch".to_string();
+        m.update(crate::ui::Msg::KeyTab);
+        assert_eq!(m.typed_raw, "chc");
+        assert_eq!(m.typed, "chc");
+        assert_eq!(m.ast.stack.len(), 1, "ambiguous Tab must not select");
+
+        // nothing further in common: Tab is a no-op
+        m.update(crate::ui::Msg::KeyTab);
+        assert_eq!(m.typed_raw, "chc");
+    }
+
+    #[test]
+    fn clear_typed_restores_full_list_and_keeps_the_preview() {
+        let mut m = model_with_subcommands(&["chcpu", "chcon", "df"]);
+        m.ast.add_flag("--verbose", "");
+        let preview = m.ast.render_preview();
+        m.typed_raw = "ch".to_string();
+        m.typed = "ch".to_string();
+        assert_eq!(m.render_visible_items().len(), 2);
+
+        m.update(crate::ui::Msg::ClearTyped);
+        assert!(m.typed_raw.is_empty() && m.typed.is_empty());
+        assert!(m.numeric_baseline.is_none());
+        assert_eq!(m.render_visible_items().len(), 3);
+        assert_eq!(m.ast.render_preview(), preview);
+
+        // numeric filtering is left as well
+        m.update(crate::ui::Msg::ToggleNumeric);
+        assert!(m.numeric_baseline.is_some());
+        m.update(crate::ui::Msg::ClearTyped);
+        assert!(m.numeric_baseline.is_none() && !m.numeric_locked);
+        assert_eq!(m.render_visible_items().len(), 3);
+    }
+
+    #[test]
+    fn back_tab_takes_back_common_prefix_extension() {
+        let mut m = model_with_subcommands(&["chcpu", "chcon", "df"]);
+        m.typed_raw = "c".to_string();
+        m.typed = "c".to_string();
+        m.update(crate::ui::Msg::KeyTab);
+        assert_eq!(m.typed_raw, "chc");
+        m.update(crate::ui::Msg::BackTab);
+        assert_eq!(m.typed_raw, "c");
+        assert_eq!(m.typed, "c");
+        assert_eq!(m.ast.stack.len(), 1);
+    }
+
+    #[test]
+    fn back_tab_after_editing_pages_backward_instead() {
+        let names: Vec<String> = (0..30).map(|i| format!("cmd{i:02}")).collect();
+        let refs: Vec<&str> = names.iter().map(String::as_str).collect();
+        let mut m = model_with_subcommands(&refs);
+        m.update(crate::ui::Msg::WindowSize { width: 80, height: 12 });
+        m.update(crate::ui::Msg::KeyTab);
+        assert_eq!(m.typed_raw, "cmd");
+        // editing after Tab leaves nothing to take back
+        for _ in 0..3 {
+            m.update(crate::ui::Msg::KeyBackspace);
+        }
+        m.update(crate::ui::Msg::KeyDown);
+        assert_eq!(m.page, 1);
+        m.update(crate::ui::Msg::BackTab);
+        assert_eq!(m.page, 0);
+        assert!(m.typed_raw.is_empty());
+    }
+
+    #[test]
+    fn back_tab_in_value_mode_reverses_tab_then_cycles_backward() {
+        let mut m = model_with_subcommands(&["x"]);
+        m.in_value_mode = true;
+        m.pending_flag = Some(crate::ast::FlagDef {
+            longhand: "out".to_string(),
+            requires_value: true,
+            ..Default::default()
+        });
+        m.pending_form = "--out".to_string();
+        let key = m.pending_value_key().unwrap();
+        m.value_history.record(&key, "b.txt").unwrap();
+        m.value_history.record(&key, "a.txt").unwrap();
+        let suggestions = m.value_suggestions();
+        assert_eq!(suggestions.len(), 2);
+
+        m.update(crate::ui::Msg::KeyTab);
+        assert_eq!(m.pending_value, suggestions[0]);
+        m.update(crate::ui::Msg::BackTab);
+        assert!(m.pending_value.is_empty());
+
+        // nothing to take back: step to the previous suggestion (wrapping), as Up does
+        m.update(crate::ui::Msg::BackTab);
+        assert_eq!(m.suggestion_idx, suggestions.len() - 1);
+    }
+}
+
+#[cfg(test)]
+mod auto_select_tests {
+    use crate::ast::{CommandDef, Segment};
+    use crate::ui::model::{initial_model, ChooseItem, Model};
+    use crate::ui::AutoSelect;
+
+    fn model_with_build_and_run() -> Model {
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("tool");
+        m.items = ["build", "run"]
+            .iter()
+            .map(|n| ChooseItem {
+                kind: "cmd".to_string(),
+                label: n.to_string(),
+                forms: vec![n.to_string()],
+                flag_def: None,
+                cmd_def: Some(CommandDef {
+                    name: n.to_string(),
+                    ..Default::default()
+                }),
+                short: String::new(),
+                depth: 0,
+            })
+            .collect();
+        m
+    }
+
+    #[test]
+    fn single_remaining_command_is_auto_selected_by_default() {
+        let mut m = model_with_build_and_run();
+        assert_eq!(m.settings.auto_select, AutoSelect::Always);
+        m.update(crate::ui::Msg::Rune('b'));
+        assert_eq!(m.ast.render_preview(), "tool build");
+        assert!(m.typed_raw.is_empty());
+    }
+
+    #[test]
+    fn single_remaining_command_waits_for_enter_when_auto_select_off() {
+        let mut m = model_with_build_and_run();
+        m.settings.auto_select = AutoSelect::FlagsOnly;
+        m.update(crate::ui::Msg::Rune('b'));
+        assert_eq!(m.ast.render_preview(), "tool", "must not descend before Enter");
+        assert_eq!(m.typed_raw, "b");
+        assert_eq!(m.render_visible_items().len(), 1);
+
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "tool build");
+        assert!(m.exit_preview.is_empty(), "Enter selects the held candidate instead of exiting");
+    }
+}
+
+#[cfg(test)]
+mod numeric_toggle_tests {
+    use crate::ast::{CommandDef, Segment};
+    use crate::ui::model::{initial_model, ChooseItem, Model};
+
+    // subcommand names full of digits, where the implicit heuristics would treat digits as alpha
+    fn model_with_digit_names() -> Model {
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty("tool");
+        m.items = ["s3", "v1", "http2"]
+            .iter()
+            .map(|n| ChooseItem {
+                kind: "cmd".to_string(),
+                label: n.to_string(),
+                forms: vec![n.to_string()],
+                flag_def: None,
+                cmd_def: Some(CommandDef {
+                    name: n.to_string(),
+                    ..Default::default()
+                }),
+                short: String::new(),
+                depth: 0,
+            })
+            .collect();
+        m
+    }
+
+    #[test]
+    fn toggle_enters_and_exits_numeric_mode() {
+        let mut m = model_with_digit_names();
+        m.update(crate::ui::Msg::ToggleNumeric);
+        assert!(m.numeric_locked);
+        assert_eq!(m.numeric_baseline, Some(vec![0, 1, 2]));
+        assert!(m.typed_raw.is_empty());
+
+        // letters are ignored while numeric mode is locked
+        m.update(crate::ui::Msg::Rune('v'));
+        assert!(m.typed_raw.is_empty());
+        assert!(m.numeric_locked);
+
+        m.update(crate::ui::Msg::ToggleNumeric);
+        assert!(!m.numeric_locked);
+        assert!(m.numeric_baseline.is_none());
+        assert!(m.typed_raw.is_empty());
+    }
+
+    #[test]
+    fn toggled_numeric_mode_selects_by_index_despite_digits_in_forms() {
+        let mut m = model_with_digit_names();
+        m.update(crate::ui::Msg::ToggleNumeric);
+        m.update(crate::ui::Msg::Rune('3'));
+        assert_eq!(m.ast.render_preview(), "tool http2");
+        assert!(!m.numeric_locked, "selection ends numeric mode");
+        assert!(m.numeric_baseline.is_none());
+    }
+
+    #[test]
+    fn backspace_keeps_toggled_numeric_mode() {
+        let mut m = model_with_digit_names();
+        let extra: Vec<ChooseItem> = (4..=12)
+            .map(|i| {
+                let mut it = m.items[0].clone();
+                it.label = format!("r{i}");
+                it.forms = vec![it.label.clone()];
+                it
+            })
+            .collect();
+        m.items.extend(extra);
+        m.update(crate::ui::Msg::ToggleNumeric);
+        // "1" is ambiguous between 1, 10, 11 and 12
+        m.update(crate::ui::Msg::Rune('1'));
+        assert_eq!(m.typed_raw, "1");
+        m.update(crate::ui::Msg::KeyBackspace);
+        assert!(m.typed_raw.is_empty());
+        assert!(m.numeric_locked);
+        assert!(m.numeric_baseline.is_some());
+        assert_eq!(m.ast.render_preview(), "tool");
+    }
+}
+
+#[cfg(test)]
+mod value_history_tests {
+    use crate::ast::{CommandDef, FlagDef, Segment};
+    use crate::history::ValueHistory;
+    use crate::ui::model::{initial_model, Model};
+
+    // Run the value command the last key asked for the way the event loop does, and deliver its
+    // output
+    #[cfg(unix)]
+    fn finish_value_command(m: &mut Model) {
+        let Some(task) = crate::ui::run::value_command_task(m) else {
+            return;
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        if let Some(msg) = runtime.block_on(task) {
+            m.update(*msg.downcast::<crate::ui::Msg>().unwrap());
+        }
+    }
+
+    fn checkout_session(history: &std::path::Path) -> Model {
+        let checkout = CommandDef {
+            name: "checkout".to_string(),
+            flags: vec![FlagDef {
+                longhand: "branch".to_string(),
+                shorthand: "b".to_string(),
+                requires_value: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let git = CommandDef {
+            name: "git".to_string(),
+            subcommands: vec![checkout.clone()],
+            ..Default::default()
+        };
+        let mut m = initial_model(vec![]);
+        m.value_history = ValueHistory::load(history.to_path_buf());
+        m.ast = Segment::new_empty("git");
+        m.ast.stack[0].name = "git".to_string();
+        m.def_cache.insert("git".to_string(), git);
+        m.ast.push_subcommand("checkout");
+        m.current = Some(checkout.clone());
+        m.build_items_from_command(&checkout);
+        m
+    }
+
+    #[test]
+    fn entered_flag_value_is_suggested_in_the_next_session() {
+        let dir = std::env::temp_dir().join(format!("van-value-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("values.json");
+
+        // first session: pick --branch (the only candidate) and type a value
+        let mut m = checkout_session(&path);
+        m.update(crate::ui::Msg::KeyTab);
+        assert!(m.in_value_mode);
+        assert!(m.value_suggestions().is_empty());
+        for ch in "feature/x y".chars() {
+            if ch == ' ' {
+                m.update(crate::ui::Msg::KeySpace);
+            } else {
+                m.update(crate::ui::Msg::Rune(ch));
+            }
+        }
+        m.update(crate::ui::Msg::KeyBackspace);
+        m.update(crate::ui::Msg::Rune('z'));
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "git checkout --branch 'feature/x z'");
+
+        // next session: the value is offered, filtered by what is typed, and Tab fills it
+        let mut m = checkout_session(&path);
+        m.update(crate::ui::Msg::KeyTab);
+        assert_eq!(m.value_suggestions(), vec!["feature/x z".to_string()]);
+        m.update(crate::ui::Msg::Rune('q'));
+        assert!(m.value_suggestions().is_empty());
+        m.update(crate::ui::Msg::KeyBackspace);
+        m.update(crate::ui::Msg::Rune('f'));
+        m.update(crate::ui::Msg::KeyTab);
+        assert_eq!(m.pending_value, "feature/x z");
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "git checkout --branch 'feature/x z'");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn fixed_value_choices_are_offered_before_history() {
+        let dir = std::env::temp_dir().join(format!("van-value-choices-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (hint, want) in [("true|false", vec!["true", "false"]), ("on|off|auto", vec!["on", "off", "auto"])] {
+            let mut m = checkout_session(&dir.join("values.json"));
+            m.items[0].flag_def.as_mut().unwrap().value_hint = hint.to_string();
+            m.update(crate::ui::Msg::KeyTab);
+            assert!(m.in_value_mode);
+            assert_eq!(m.value_suggestions(), want, "for {hint}");
+        }
+
+        // narrowed by typing; Tab takes the first, and a remembered value follows the choices
+        let mut m = checkout_session(&dir.join("values.json"));
+        let key = ValueHistory::key(&["git", "checkout"], "--branch");
+        m.value_history.record(&key, "often").unwrap();
+        m.items[0].flag_def.as_mut().unwrap().value_hint = "on|off|auto".to_string();
+        m.update(crate::ui::Msg::KeyTab);
+        m.update(crate::ui::Msg::Rune('o'));
+        assert_eq!(m.value_suggestions(), vec!["on", "off", "often"]);
+        // each under the heading of its source
+        let groups = m.value_suggestion_groups();
+        let sources: Vec<_> = groups.iter().map(|(s, v)| (s.heading(), v.len())).collect();
+        assert_eq!(sources, vec![("Choices", 2), ("Previous values", 1)]);
+        m.update(crate::ui::Msg::KeyTab);
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "git checkout --branch on");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn value_commands_offer_their_output_as_values() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("van-value-command-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let branches = dir.join("branches");
+        std::fs::write(&branches, "#!/bin/sh\nprintf 'main\\ndev\\n\\nfeature/x\\ndev\\n'\n").unwrap();
+        std::fs::set_permissions(&branches, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let command = branches.display().to_string();
+
+        // keyed by command and any of the flag's forms; other flags get nothing
+        for (key, want) in [("checkout -b", vec!["main", "dev", "feature/x"]), ("checkout --branch", vec!["main", "dev", "feature/x"]), ("--branch", vec!["main", "dev", "feature/x"]), ("log --branch", vec![])] {
+            let mut m = checkout_session(&dir.join("values.json"));
+            m.settings.value_commands = vec![(key.to_string(), command.clone())];
+            m.update(crate::ui::Msg::KeyTab);
+            assert!(m.in_value_mode);
+            finish_value_command(&mut m);
+            assert_eq!(m.value_suggestions(), want, "for {key}");
+        }
+
+        // narrowed by typing, and taken with Tab like any other value
+        let mut m = checkout_session(&dir.join("values.json"));
+        m.settings.value_commands = vec![("checkout --branch".to_string(), command.clone())];
+        m.update(crate::ui::Msg::KeyTab);
+        finish_value_command(&mut m);
+        m.update(crate::ui::Msg::Rune('f'));
+        assert_eq!(m.value_suggestions(), vec!["feature/x"]);
+        m.update(crate::ui::Msg::KeyTab);
+        m.update(crate::ui::Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "git checkout --branch feature/x");
+
+        // once the value is in (or given up with Esc), later prompts don't offer the output
+        m.update(crate::ui::Msg::KeySpace);
+        assert!(m.in_value_mode && m.pending_pos);
+        m.update(crate::ui::Msg::Rune('m'));
+        assert!(m.value_suggestions().is_empty());
+        let mut m = checkout_session(&dir.join("values.json"));
+        m.settings.value_commands = vec![("checkout --branch".to_string(), command.clone())];
+        m.update(crate::ui::Msg::KeyTab);
+        m.update(crate::ui::Msg::KeyEsc);
+        // output arriving after the prompt is gone is dropped
+        finish_value_command(&mut m);
+        assert!(m.value_candidates.is_empty());
+
+        // a failing command offers nothing (with a history that has not seen feature/x), and a
+        // slow one is given up on; choosing the flag doesn't wait for either
+        for command in ["exit 3", "sleep 10; echo late"] {
+            let mut m = checkout_session(&dir.join("fresh.json"));
+            m.settings.value_commands = vec![("checkout --branch".to_string(), command.to_string())];
+            let started = std::time::Instant::now();
+            m.update(crate::ui::Msg::KeyTab);
+            assert!(started.elapsed() < crate::ui::model::VALUE_COMMAND_TIMEOUT, "{command}");
+            assert_eq!(m.value_command_request.as_deref(), Some(command));
+            finish_value_command(&mut m);
+            assert!(started.elapsed() < std::time::Duration::from_secs(5), "{command}");
+            assert!(m.value_suggestions().is_empty(), "{command}");
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
+
+#[cfg(test)]
+mod kv_flag_tests {
+    use crate::ast::{CommandDef, FlagDef, Segment};
+    use crate::ui::model::{initial_model, Model};
+    use crate::ui::Msg;
+
+    fn session(root: &str, flag: FlagDef) -> Model {
+        let def = CommandDef {
+            name: root.to_string(),
+            flags: vec![flag],
+            ..Default::default()
+        };
+        let mut m = initial_model(vec![]);
+        m.ast = Segment::new_empty(root);
+        m.def_cache.insert(root.to_string(), def.clone());
+        m.current = Some(def.clone());
+        m.build_items_from_command(&def);
+        // the flag is the only candidate: Tab picks it and opens value input
+        m.update(Msg::KeyTab);
+        assert!(m.in_value_mode, "flag should await a value");
+        m
+    }
+
+    fn type_str(m: &mut Model, s: &str) {
+        for ch in s.chars() {
+            m.update(Msg::Rune(ch));
+        }
+    }
+
+    fn git_c() -> FlagDef {
+        FlagDef {
+            shorthand: "c".to_string(),
+            requires_value: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn configured_kv_flag_collects_key_then_value() {
+        let mut m = session("git", git_c());
+        assert!(m.pending_is_kv());
+        type_str(&mut m, "foo.bar");
+        m.update(Msg::KeyEnter);
+        assert_eq!(m.pending_kv_key.as_deref(), Some("foo.bar"));
+        assert!(m.in_value_mode);
+        type_str(&mut m, "baz");
+        m.update(Msg::KeyEnter);
+
+        assert!(!m.in_value_mode);
+        let flag = &m.ast.top().unwrap().flags[0];
+        assert_eq!((flag.form.as_str(), flag.value.as_str()), ("-c", "foo.bar=baz"));
+        assert_eq!(m.ast.render_preview(), "git -c foo.bar=baz");
+    }
+
+    #[test]
+    fn equals_sign_moves_to_the_value_and_backspace_returns_to_the_key() {
+        let mut m = session("git", git_c());
+        type_str(&mut m, "user.name=");
+        assert_eq!(m.pending_kv_key.as_deref(), Some("user.name"));
+        m.update(Msg::KeyBackspace);
+        assert_eq!(m.pending_kv_key, None);
+        assert_eq!(m.pending_value, "user.name");
+        type_str(&mut m, "=me");
+        m.update(Msg::KeyEnter);
+        assert_eq!(m.ast.top().unwrap().flags[0].value, "user.name=me");
+    }
+
+    #[test]
+    fn key_value_type_hint_enables_the_sub_mode_for_any_command() {
+        let label = FlagDef {
+            longhand: "label".to_string(),
+            requires_value: true,
+            value_hint: "key=value".to_string(),
+            ..Default::default()
+        };
+        let mut m = session("docker", label);
+        type_str(&mut m, "tier");
+        m.update(Msg::KeyEnter);
+        type_str(&mut m, "web");
+        m.update(Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "docker --label tier=web");
+
+        // ordinary value flags are untouched
+        let mut m = session("docker", FlagDef {
+            longhand: "name".to_string(),
+            requires_value: true,
+            ..Default::default()
+        });
+        assert!(!m.pending_is_kv());
+        type_str(&mut m, "a=b");
+        m.update(Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "docker --name a=b");
+    }
+}
+
+#[cfg(test)]
+mod env_var_tests {
+    use crate::ui::model::{env_var_suggestions, initial_model};
+    use crate::ui::Msg;
+
+    fn names() -> impl Iterator<Item = String> {
+        ["PATH", "PAGER", "HOME", "PWD"].into_iter().map(String::from)
+    }
+
+    #[test]
+    fn dollar_prefix_lists_matching_variables() {
+        let got = env_var_suggestions("$PA", names()).unwrap();
+        assert_eq!(got, vec!["$PAGER".to_string(), "$PATH".to_string()]);
+        // a reference later in the value keeps what precedes it
+        let got = env_var_suggestions("$HOME/$P", names()).unwrap();
+        assert_eq!(got, vec!["$HOME/$PAGER", "$HOME/$PATH", "$HOME/$PWD"]);
+        assert_eq!(env_var_suggestions("plain", names()), None);
+        assert_eq!(env_var_suggestions("$HOME/x", names()), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn value_input_offers_environment_variables_after_dollar() {
+        use crate::carapace::fake::{lock_env, EnvVars};
+        use crate::ui::model::ValueSource;
+        let _lock = lock_env();
+        let _env = EnvVars::set(&[("VAN_ENV_COMPLETION_TEST", "1")]);
+        let mut m = initial_model(vec![]);
+        m.ast = crate::ast::Segment::new_empty("echo");
+        m.update(Msg::KeySpace);
+        assert!(m.in_value_mode && m.pending_pos);
+        for ch in "$VAN_ENV_COMP".chars() {
+            m.update(Msg::Rune(ch));
+        }
+        assert!(m.value_suggestions().contains(&"$VAN_ENV_COMPLETION_TEST".to_string()));
+        let sources: Vec<_> = m.value_suggestion_groups().into_iter().map(|(s, _)| s).collect();
+        assert_eq!(sources, vec![ValueSource::EnvVars]);
+        m.update(Msg::KeyTab);
+        assert_eq!(m.pending_value, "$VAN_ENV_COMPLETION_TEST");
+        // once the reference is closed, a `$` in the value no longer means variables are listed
+        m.update(Msg::Rune('/'));
+        assert!(m.value_suggestion_groups().iter().all(|(s, _)| *s != ValueSource::EnvVars));
+        m.update(Msg::KeyEnter);
+        // the completed reference stays live for the shell; a `$` typed by hand does not
+        m.update(Msg::KeySpace);
+        for ch in "pa$word".chars() {
+            m.update(Msg::Rune(ch));
+        }
+        m.update(Msg::KeyEnter);
+        assert_eq!(m.ast.render_preview(), "echo \"$VAN_ENV_COMPLETION_TEST/\" 'pa$word'");
+    }
+}